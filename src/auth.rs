@@ -0,0 +1,366 @@
+use core::fmt::{ self, Display };
+
+use crate::base64;
+use crate::syntax::{ self, Quoted, Split };
+
+mod digest;
+pub use digest::{
+    DigestAlgorithm, DigestError, DigestChallenge, DigestResponse, DigestClient, DigestAuthorization,
+};
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// the value doesn't follow the credentials grammar
+    InvalidCredentials,
+    /// the credentials are for another auth scheme
+    SchemeMismatch,
+    /// the token68 isn't valid base64
+    InvalidEncoding,
+    /// the decoded credentials don't fit in the buffer
+    BufferTooSmall,
+    /// the decoded `Basic` credentials aren't utf-8 or have no `:`
+    InvalidBasicCredentials,
+}
+
+/// the `name=value` parameters of a challenge or credentials
+pub struct AuthParams<'a>(Split<'a>);
+
+impl<'a> Iterator for AuthParams<'a> {
+    /// the name and value of the parameter, quoted values are unquoted but their escapes are kept
+    type Item = (&'a str, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(syntax::param)
+    }
+}
+
+/// a challenge from a `WWW-Authenticate` or `Proxy-Authenticate` header, e.g. `Basic realm="admin"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Challenge<'a> {
+    scheme: &'a str,
+    token68: Option<&'a str>,
+    params: &'a str,
+}
+
+impl<'a> Challenge<'a> {
+    pub fn scheme(&self) -> &'a str { self.scheme }
+    /// whether this is a challenge for the given (case-insensitive) auth scheme
+    pub fn is(&self, scheme: &str) -> bool { self.scheme.eq_ignore_ascii_case(scheme) }
+    pub fn token68(&self) -> Option<&'a str> { self.token68 }
+    pub fn params(&self) -> AuthParams<'a> { AuthParams(syntax::list(self.params)) }
+    /// find a parameter by its (case-insensitive) name
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params().find(|(param, _)| param.eq_ignore_ascii_case(name)).map(|(_, value)| value)
+    }
+    pub fn realm(&self) -> Option<&'a str> { self.param("realm") }
+}
+
+impl Display for Challenge<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_auth(f, self.scheme, self.token68, self.params)
+    }
+}
+
+/// the challenges of a `WWW-Authenticate` or `Proxy-Authenticate` header, malformed challenges are skipped
+pub struct Challenges<'a> {
+    input: &'a str,
+    elements: Split<'a>,
+}
+
+/// parse the (possibly several) challenges of a `WWW-Authenticate` or `Proxy-Authenticate` value
+pub fn parse_challenges(input: &str) -> Challenges<'_> {
+    Challenges { input, elements: syntax::list(input) }
+}
+
+impl<'a> Iterator for Challenges<'a> {
+    type Item = Challenge<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        // a challenge starts with a list element of `scheme [ SP ( token68 / auth-param ) ]`, and continues through
+        // the auth-param elements following it
+        while let Some(element) = self.elements.next() {
+            let Some((scheme, rest)) = scheme_start(element) else { continue };
+            if rest.is_empty() { return Some(Challenge { scheme, token68: None, params: "" }); }
+            if !is_param(rest) {
+                if !is_token68(rest) { continue; }
+                return Some(Challenge { scheme, token68: Some(rest), params: "" });
+            }
+
+            let mut params = rest;
+            let mut elements = self.elements.clone();
+            while let Some(param) = elements.next().filter(|element| is_param(element)) {
+                params = span(self.input, params, param);
+                self.elements = elements.clone();
+            }
+            return Some(Challenge { scheme, token68: None, params });
+        }
+        None
+    }
+}
+
+/// the credentials of an `Authorization` or `Proxy-Authorization` header, e.g. `Basic dXNlcjpwYXNz`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Credentials<'a> {
+    scheme: &'a str,
+    token68: Option<&'a str>,
+    params: &'a str,
+}
+
+impl<'a> Credentials<'a> {
+    pub fn parse(input: &'a str) -> Result<Self, AuthError> {
+        let (scheme, rest) = scheme_start(syntax::trim_ows(input)).ok_or(AuthError::InvalidCredentials)?;
+        if rest.is_empty() { return Ok(Self { scheme, token68: None, params: "" }); }
+        if syntax::list(rest).next().is_some() && syntax::list(rest).all(is_param) {
+            return Ok(Self { scheme, token68: None, params: rest });
+        }
+        if is_token68(rest) { return Ok(Self { scheme, token68: Some(rest), params: "" }); }
+        Err(AuthError::InvalidCredentials)
+    }
+
+    pub fn scheme(&self) -> &'a str { self.scheme }
+    /// whether these are credentials for the given (case-insensitive) auth scheme
+    pub fn is(&self, scheme: &str) -> bool { self.scheme.eq_ignore_ascii_case(scheme) }
+    pub fn token68(&self) -> Option<&'a str> { self.token68 }
+    pub fn params(&self) -> AuthParams<'a> { AuthParams(syntax::list(self.params)) }
+    /// find a parameter by its (case-insensitive) name
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params().find(|(param, _)| param.eq_ignore_ascii_case(name)).map(|(_, value)| value)
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Credentials<'a> {
+    type Error = AuthError;
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> { Self::parse(value) }
+}
+
+impl Display for Credentials<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_auth(f, self.scheme, self.token68, self.params)
+    }
+}
+
+/// `Basic` credentials (rfc 7617), a user-id and password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicCredentials<'b> {
+    user_id: &'b str,
+    password: &'b str,
+}
+
+impl<'b> BasicCredentials<'b> {
+    /// the user-id can't contain a `:`
+    pub fn new(user_id: &'b str, password: &'b str) -> Self {
+        Self { user_id, password }
+    }
+
+    /// decode the base64 token of `Basic` credentials into `buf`
+    pub fn decode(credentials: &Credentials<'_>, buf: &'b mut [u8]) -> Result<Self, AuthError> {
+        if !credentials.is("Basic") { return Err(AuthError::SchemeMismatch); }
+        let token = credentials.token68.ok_or(AuthError::InvalidCredentials)?;
+        let len = base64::decoded_len(token).ok_or(AuthError::InvalidEncoding)?;
+        if len > buf.len() { return Err(AuthError::BufferTooSmall); }
+        let decoded = base64::decode(token, buf).ok_or(AuthError::InvalidEncoding)?;
+        let decoded = core::str::from_utf8(decoded).map_err(|_| AuthError::InvalidBasicCredentials)?;
+        let (user_id, password) = decoded.split_once(':').ok_or(AuthError::InvalidBasicCredentials)?;
+        Ok(Self { user_id, password })
+    }
+
+    pub fn user_id(&self) -> &'b str { self.user_id }
+    pub fn password(&self) -> &'b str { self.password }
+}
+
+/// displays the credentials as an `Authorization` value
+impl Display for BasicCredentials<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Basic ")?;
+        let user_pass = self.user_id.bytes().chain([b':']).chain(self.password.bytes());
+        base64::encode(user_pass, f)
+    }
+}
+
+/// a `Bearer` token (rfc 6750)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BearerToken<'a>(&'a str);
+
+impl<'a> BearerToken<'a> {
+    pub fn new(token: &'a str) -> Self { Self(token) }
+
+    pub fn from_credentials(credentials: &Credentials<'a>) -> Result<Self, AuthError> {
+        if !credentials.is("Bearer") { return Err(AuthError::SchemeMismatch); }
+        credentials.token68.map(Self).ok_or(AuthError::InvalidCredentials)
+    }
+
+    pub fn token(&self) -> &'a str { self.0 }
+}
+
+/// displays the token as an `Authorization` value
+impl Display for BearerToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bearer {}", self.0)
+    }
+}
+
+/// builds a challenge for a `WWW-Authenticate` or `Proxy-Authenticate` header
+///
+/// parameter values are always sent as quoted strings
+#[derive(Debug, Clone, Copy)]
+pub struct ChallengeBuilder<'a> {
+    scheme: &'a str,
+    token68: Option<&'a str>,
+    realm: Option<&'a str>,
+    params: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> ChallengeBuilder<'a> {
+    pub fn new(scheme: &'a str) -> Self {
+        Self { scheme, token68: None, realm: None, params: &[] }
+    }
+
+    /// `Basic realm="...", charset="UTF-8"`
+    pub fn basic(realm: &'a str) -> Self {
+        Self { realm: Some(realm), params: &[("charset", "UTF-8")], ..Self::new("Basic") }
+    }
+
+    /// `Bearer realm="..."`, add `error` and `error_description` with [`set_params`](Self::set_params)
+    pub fn bearer(realm: &'a str) -> Self {
+        Self { realm: Some(realm), ..Self::new("Bearer") }
+    }
+
+    /// send a token68 instead of parameters
+    pub fn set_token68(&mut self, token68: &'a str) { self.token68 = Some(token68); }
+    pub fn set_realm(&mut self, realm: &'a str) { self.realm = Some(realm); }
+    /// the parameters sent after the realm
+    pub fn set_params(&mut self, params: &'a [(&'a str, &'a str)]) { self.params = params; }
+}
+
+impl Display for ChallengeBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.scheme)?;
+        if let Some(token68) = self.token68 { return write!(f, " {token68}"); }
+        let params = self.realm.map(|realm| ("realm", realm)).into_iter().chain(self.params.iter().copied());
+        for (idx, (name, value)) in params.enumerate() {
+            let separator = if idx == 0 { " " } else { ", " };
+            write!(f, "{separator}{name}={}", Quoted(value))?;
+        }
+        Ok(())
+    }
+}
+
+fn write_auth(f: &mut fmt::Formatter<'_>, scheme: &str, token68: Option<&str>, params: &str) -> fmt::Result {
+    f.write_str(scheme)?;
+    match (token68, params) {
+        (Some(token68), _) => write!(f, " {token68}"),
+        (None, "") => Ok(()),
+        (None, params) => write!(f, " {params}"),
+    }
+}
+
+// split `scheme [ 1*SP rest ]` off the start of a list element, `None` if the element is an auth-param
+fn scheme_start(element: &str) -> Option<(&str, &str)> {
+    let (scheme, rest) = match element.find([' ', '\t']) {
+        Some(idx) => (&element[..idx], syntax::trim_ows(&element[idx..])),
+        None => (element, ""),
+    };
+    if !syntax::is_token(scheme) || rest.starts_with('=') { return None; }
+    Some((scheme, rest))
+}
+
+// `token BWS "=" BWS ( token / quoted-string )`
+fn is_param(element: &str) -> bool {
+    let Some((name, value)) = element.split_once('=') else { return false };
+    let value = syntax::trim_ows(value);
+    syntax::is_token(syntax::trim_ows(name)) && (syntax::is_token(value) || syntax::is_quoted_string(value))
+}
+
+// `1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="`
+fn is_token68(input: &str) -> bool {
+    let data = input.trim_end_matches('=');
+    !data.is_empty() && data.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~' | b'+' | b'/'))
+}
+
+// the part of `input` from the start of `first` to the end of `last`, both of which are slices of it
+fn span<'a>(input: &'a str, first: &str, last: &str) -> &'a str {
+    let start = first.as_ptr() as usize - input.as_ptr() as usize;
+    let end = last.as_ptr() as usize - input.as_ptr() as usize + last.len();
+    &input[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::ToString, vec::Vec };
+    use super::*;
+
+    #[test]
+    fn challenges() {
+        let input = r#"Newauth realm="apps, and more", type=1, title="Login to \"apps\"", Basic realm="simple", , Bearer"#;
+        let challenges: Vec<_> = parse_challenges(input).collect();
+        assert_eq!(challenges.len(), 3);
+        assert!(challenges[0].is("newauth"));
+        assert_eq!(challenges[0].realm(), Some("apps, and more"));
+        assert_eq!(challenges[0].params().collect::<Vec<_>>(),
+            [("realm", "apps, and more"), ("type", "1"), ("title", r#"Login to \"apps\""#)]);
+        assert_eq!(challenges[1].to_string(), r#"Basic realm="simple""#);
+        assert_eq!((challenges[2].scheme(), challenges[2].token68(), challenges[2].realm()), ("Bearer", None, None));
+
+        // a token68 ends its challenge, malformed challenges are skipped
+        let input = "Negotiate a87421000492aa874209af8bc028==, realm=lost, Basic realm=x, Digest realm=\"unterminated";
+        let challenges: Vec<_> = parse_challenges(input).collect();
+        assert_eq!(challenges.iter().map(Challenge::scheme).collect::<Vec<_>>(), ["Negotiate", "Basic"]);
+        assert_eq!(challenges[0].token68(), Some("a87421000492aa874209af8bc028=="));
+        assert_eq!(challenges[0].param("realm"), None);
+        assert_eq!(challenges[1].realm(), Some("x"));
+    }
+
+    #[test]
+    fn credentials() {
+        let credentials = Credentials::parse(" Digest username=\"a, b\", nc=00000001 ").unwrap();
+        assert_eq!((credentials.token68(), credentials.param("USERNAME")), (None, Some("a, b")));
+        assert_eq!(credentials.to_string(), "Digest username=\"a, b\", nc=00000001");
+
+        // a single `=` can end a token68 or start a parameter value
+        let credentials = Credentials::parse("Custom abc=").unwrap();
+        assert_eq!((credentials.token68(), credentials.params().count()), (Some("abc="), 0));
+        let credentials = Credentials::parse("Custom abc=d").unwrap();
+        assert_eq!((credentials.token68(), credentials.param("abc")), (None, Some("d")));
+        assert_eq!(Credentials::parse("Custom").map(|credentials| credentials.to_string()).ok().as_deref(), Some("Custom"));
+
+        for invalid in ["", "=abc", "Basic a b", "Basic a=b, c", "Basic =abc", "Basic a\"b"] {
+            assert!(matches!(Credentials::parse(invalid), Err(AuthError::InvalidCredentials)), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn basic_and_bearer() {
+        let mut buf = [0; 32];
+        let credentials = Credentials::parse("basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==").unwrap();
+        let basic = BasicCredentials::decode(&credentials, &mut buf).unwrap();
+        assert_eq!((basic.user_id(), basic.password()), ("Aladdin", "open sesame"));
+        assert_eq!(basic.to_string(), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+        assert_eq!(BasicCredentials::new("a", "b:c").to_string(), "Basic YTpiOmM=");
+
+        let decode = |input| BasicCredentials::decode(&Credentials::parse(input).unwrap(), &mut [0; 4]).map(|_| ());
+        assert!(matches!(decode("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="), Err(AuthError::BufferTooSmall)));
+        assert!(matches!(decode("Basic YWI="), Err(AuthError::InvalidBasicCredentials)));
+        assert!(matches!(decode("Basic /w=="), Err(AuthError::InvalidBasicCredentials)));
+        assert!(matches!(decode("Basic YTpi="), Err(AuthError::InvalidEncoding)));
+        assert!(matches!(decode("Basic a-_b"), Err(AuthError::InvalidEncoding)));
+        assert!(matches!(decode("Basic realm=x"), Err(AuthError::InvalidCredentials)));
+        assert!(matches!(decode("Bearer YTpi"), Err(AuthError::SchemeMismatch)));
+
+        let credentials = Credentials::parse("Bearer mF_9.B5f-4.1JqM").unwrap();
+        assert_eq!(BearerToken::from_credentials(&credentials).map(|token| token.token()).ok(), Some("mF_9.B5f-4.1JqM"));
+        assert_eq!(BearerToken::new("abc").to_string(), "Bearer abc");
+    }
+
+    #[test]
+    fn building_challenges() {
+        assert_eq!(ChallengeBuilder::basic("a \"b\"").to_string(), r#"Basic realm="a \"b\"", charset="UTF-8""#);
+        let mut bearer = ChallengeBuilder::bearer("api");
+        bearer.set_params(&[("error", "invalid_token")]);
+        assert_eq!(bearer.to_string(), r#"Bearer realm="api", error="invalid_token""#);
+        let mut negotiate = ChallengeBuilder::new("Negotiate");
+        assert_eq!(negotiate.to_string(), "Negotiate");
+        negotiate.set_token68("abc==");
+        assert_eq!(negotiate.to_string(), "Negotiate abc==");
+        let challenge = bearer.to_string();
+        assert_eq!(parse_challenges(&challenge).next().and_then(|challenge| challenge.param("error")), Some("invalid_token"));
+    }
+}
@@ -0,0 +1,536 @@
+use core::fmt::{ self, Display };
+
+use crate::Request;
+use crate::hash::{ Md5, Sha256 };
+use crate::syntax::{ self, Quoted };
+use super::{ Challenge, Credentials };
+
+/// the hash algorithm of `Digest` authentication (rfc 7616 section 3.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DigestAlgorithm {
+    /// the default when no algorithm is given
+    #[default]
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    pub fn parse(input: &str) -> Option<Self> {
+        [Self::Md5, Self::Md5Sess, Self::Sha256, Self::Sha256Sess].into_iter()
+            .find(|algorithm| algorithm.as_str().eq_ignore_ascii_case(input))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Md5 => "MD5",
+            Self::Md5Sess => "MD5-sess",
+            Self::Sha256 => "SHA-256",
+            Self::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    /// whether the session variant is used, which hashes the client nonce into A1
+    pub fn is_session(&self) -> bool { matches!(self, Self::Md5Sess | Self::Sha256Sess) }
+}
+
+#[derive(Debug)]
+pub enum DigestError {
+    /// the challenge or credentials aren't for the `Digest` scheme
+    SchemeMismatch,
+    /// a required parameter is missing
+    MissingParameter,
+    UnsupportedAlgorithm,
+    /// the only quality of protection supported is `auth`
+    UnsupportedQop,
+    /// the nonce count isn't 8 hex digits
+    InvalidNonceCount,
+    /// a realm, nonce or opaque with an unescaped quote or backslash, or a control character
+    InvalidParameter,
+}
+
+/// a `Digest` challenge, either sent by a server or received by a client
+///
+/// the realm, nonce and opaque are always kept escaped, as the contents of a quoted string: parsed parameters as
+/// they were received, and given ones as they will be sent. the escapes are resolved when they are hashed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestChallenge<'a> {
+    realm: &'a str,
+    nonce: &'a str,
+    opaque: Option<&'a str>,
+    algorithm: DigestAlgorithm,
+    qop: bool,
+    userhash: bool,
+    stale: bool,
+}
+
+impl<'a> DigestChallenge<'a> {
+    /// a challenge with `qop="auth"`, the nonce should be unpredictable and is verified by the server
+    ///
+    /// the realm and nonce are escaped, e.g. `Admin \"area\"` for `Admin "area"`
+    pub fn new(realm: &'a str, nonce: &'a str, algorithm: DigestAlgorithm) -> Result<Self, DigestError> {
+        if !syntax::is_escaped(realm) || !syntax::is_escaped(nonce) { return Err(DigestError::InvalidParameter); }
+        Ok(Self { realm, nonce, opaque: None, algorithm, qop: true, userhash: false, stale: false })
+    }
+
+    pub fn from_challenge(challenge: &Challenge<'a>) -> Result<Self, DigestError> {
+        if !challenge.is("Digest") { return Err(DigestError::SchemeMismatch); }
+        let realm = challenge.realm().ok_or(DigestError::MissingParameter)?;
+        let nonce = challenge.param("nonce").ok_or(DigestError::MissingParameter)?;
+        let algorithm = match challenge.param("algorithm") {
+            Some(algorithm) => DigestAlgorithm::parse(algorithm).ok_or(DigestError::UnsupportedAlgorithm)?,
+            None => DigestAlgorithm::Md5,
+        };
+        let qop = challenge.param("qop");
+        if qop.is_some_and(|qop| !syntax::list(qop).any(|qop| qop.eq_ignore_ascii_case("auth"))) {
+            return Err(DigestError::UnsupportedQop);
+        }
+        let flag = |name| challenge.param(name).is_some_and(|value| value.eq_ignore_ascii_case("true"));
+        Ok(Self {
+            realm,
+            nonce,
+            opaque: challenge.param("opaque"),
+            algorithm,
+            qop: qop.is_some(),
+            userhash: flag("userhash"),
+            stale: flag("stale"),
+        })
+    }
+
+    /// the realm, escaped
+    pub fn realm(&self) -> &'a str { self.realm }
+    /// the nonce, escaped
+    pub fn nonce(&self) -> &'a str { self.nonce }
+    /// the opaque, escaped
+    pub fn opaque(&self) -> Option<&'a str> { self.opaque }
+    pub fn algorithm(&self) -> DigestAlgorithm { self.algorithm }
+    pub fn userhash(&self) -> bool { self.userhash }
+    /// the previous nonce was rejected for being stale, the credentials were otherwise valid
+    pub fn stale(&self) -> bool { self.stale }
+
+    /// sent back unchanged by the client, escaped like the realm
+    pub fn set_opaque(&mut self, opaque: &'a str) -> Result<(), DigestError> {
+        if !syntax::is_escaped(opaque) { return Err(DigestError::InvalidParameter); }
+        self.opaque = Some(opaque);
+        Ok(())
+    }
+    /// let the client send a hash of its username instead of the username
+    pub fn set_userhash(&mut self, userhash: bool) { self.userhash = userhash; }
+    pub fn set_stale(&mut self, stale: bool) { self.stale = stale; }
+}
+
+/// displays the challenge as a `WWW-Authenticate` value
+impl Display for DigestChallenge<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Digest realm=\"{}\"", self.realm)?;
+        if self.qop { f.write_str(", qop=\"auth\"")?; }
+        write!(f, ", algorithm={}, nonce=\"{}\"", self.algorithm.as_str(), self.nonce)?;
+        if let Some(opaque) = self.opaque { write!(f, ", opaque=\"{opaque}\"")?; }
+        if self.userhash { f.write_str(", userhash=true")?; }
+        if self.stale { f.write_str(", stale=true")?; }
+        Ok(())
+    }
+}
+
+/// the parameters of `Digest` credentials, as received by a server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestResponse<'a> {
+    username: &'a str,
+    realm: &'a str,
+    uri: &'a str,
+    nonce: &'a str,
+    response: &'a str,
+    algorithm: DigestAlgorithm,
+    qop: bool,
+    nonce_count: Option<&'a str>,
+    cnonce: Option<&'a str>,
+    opaque: Option<&'a str>,
+    userhash: bool,
+}
+
+impl<'a> DigestResponse<'a> {
+    pub fn from_credentials(credentials: &Credentials<'a>) -> Result<Self, DigestError> {
+        if !credentials.is("Digest") { return Err(DigestError::SchemeMismatch); }
+        let required = |name| credentials.param(name).ok_or(DigestError::MissingParameter);
+        let algorithm = match credentials.param("algorithm") {
+            Some(algorithm) => DigestAlgorithm::parse(algorithm).ok_or(DigestError::UnsupportedAlgorithm)?,
+            None => DigestAlgorithm::Md5,
+        };
+        let qop = match credentials.param("qop") {
+            Some(qop) if qop.eq_ignore_ascii_case("auth") => true,
+            Some(_) => return Err(DigestError::UnsupportedQop),
+            None => false,
+        };
+        let (nonce_count, cnonce) = (credentials.param("nc"), credentials.param("cnonce"));
+        if (qop || algorithm.is_session()) && cnonce.is_none() { return Err(DigestError::MissingParameter); }
+        if qop {
+            let nonce_count = nonce_count.ok_or(DigestError::MissingParameter)?;
+            if nonce_count.len() != 8 || !nonce_count.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(DigestError::InvalidNonceCount);
+            }
+        }
+
+        Ok(Self {
+            username: required("username")?,
+            realm: required("realm")?,
+            uri: required("uri")?,
+            nonce: required("nonce")?,
+            response: required("response")?,
+            algorithm,
+            qop,
+            nonce_count: nonce_count.filter(|_| qop),
+            cnonce,
+            opaque: credentials.param("opaque"),
+            userhash: credentials.param("userhash").is_some_and(|value| value.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    /// the username, or its hash if [`userhash`](Self::userhash) is set
+    pub fn username(&self) -> &'a str { self.username }
+    /// the realm, escaped like [`DigestChallenge::realm`]
+    pub fn realm(&self) -> &'a str { self.realm }
+    pub fn uri(&self) -> &'a str { self.uri }
+    pub fn nonce(&self) -> &'a str { self.nonce }
+    pub fn algorithm(&self) -> DigestAlgorithm { self.algorithm }
+    pub fn cnonce(&self) -> Option<&'a str> { self.cnonce }
+    pub fn opaque(&self) -> Option<&'a str> { self.opaque }
+    pub fn userhash(&self) -> bool { self.userhash }
+
+    /// how many requests the client has sent with this nonce, which should increase to prevent replays
+    pub fn nonce_count(&self) -> Option<u32> {
+        self.nonce_count.and_then(|count| u32::from_str_radix(count, 16).ok())
+    }
+
+    /// whether the credentials are for `username`, comparing against its hash when `userhash` is set
+    pub fn is_user(&self, username: &str) -> bool {
+        if !self.userhash { return unescaped_eq(self.username, username); }
+        let hash = hash(self.algorithm, |hasher| {
+            hasher.update(username.as_bytes());
+            hasher.update(b":");
+            hasher.update_unescaped(self.realm);
+        });
+        constant_time_eq(self.username, hash.as_str())
+    }
+
+    /// check the credentials of `username` with `password` for `request`, `realm` is the server's, escaped like
+    /// [`DigestChallenge::realm`]
+    ///
+    /// the nonce (and its count) have to be validated separately
+    pub fn verify<B>(&self, request: &Request<'_, '_, B>, realm: &str, username: &str, password: &str) -> bool {
+        unescaped(self.realm).eq(unescaped(realm))
+            && self.is_user(username)
+            && self.verify_hash(request, user_hash(self.algorithm, username, self.realm, password).as_str())
+    }
+
+    /// check the credentials against the stored hex of `H(username:realm:password)` for `request`
+    pub fn verify_hash<B>(&self, request: &Request<'_, '_, B>, user_hash: &str) -> bool {
+        if !unescaped_eq(self.uri, request.target()) { return false; }
+        let expected = response(ResponseParts {
+            algorithm: self.algorithm,
+            user_hash,
+            nonce: self.nonce,
+            nonce_count: self.nonce_count.filter(|_| self.qop),
+            cnonce: self.cnonce.unwrap_or(""),
+            method: request.method.as_str(),
+            uri: request.target(),
+        });
+        constant_time_eq(self.response, expected.as_str())
+    }
+}
+
+/// answers a `Digest` challenge, counting the requests sent with its nonce
+#[derive(Debug, Clone, Copy)]
+pub struct DigestClient<'a> {
+    challenge: DigestChallenge<'a>,
+    username: &'a str,
+    password: &'a str,
+    nonce_count: u32,
+}
+
+impl<'a> DigestClient<'a> {
+    pub fn new(challenge: DigestChallenge<'a>, username: &'a str, password: &'a str) -> Self {
+        Self { challenge, username, password, nonce_count: 0 }
+    }
+
+    /// switch to a new challenge, e.g. after the server marked the nonce as stale, which restarts the count
+    pub fn set_challenge(&mut self, challenge: DigestChallenge<'a>) {
+        self.challenge = challenge;
+        self.nonce_count = 0;
+    }
+
+    pub fn nonce_count(&self) -> u32 { self.nonce_count }
+
+    /// the `Authorization` for the next request, `cnonce` is a fresh client nonce made of token characters
+    pub fn authorize<'r, B>(&mut self, request: &Request<'r, '_, B>, cnonce: &'r str) -> DigestAuthorization<'a, 'r> {
+        self.nonce_count = self.nonce_count.wrapping_add(1);
+        let challenge = self.challenge;
+        let algorithm = challenge.algorithm;
+        let mut nonce_count = [0u8; 8];
+        for (idx, digit) in nonce_count.iter_mut().enumerate() {
+            *digit = HEX[(self.nonce_count >> (28 - idx * 4) & 0xf) as usize];
+        }
+
+        let response = response(ResponseParts {
+            algorithm,
+            user_hash: user_hash(algorithm, self.username, challenge.realm, self.password).as_str(),
+            nonce: challenge.nonce,
+            nonce_count: challenge.qop.then(|| core::str::from_utf8(&nonce_count).unwrap_or_default()),
+            cnonce,
+            method: request.method.as_str(),
+            uri: request.target(),
+        });
+        let username_hash = challenge.userhash.then(|| hash(algorithm, |hasher| {
+            hasher.update(self.username.as_bytes());
+            hasher.update(b":");
+            hasher.update_unescaped(challenge.realm);
+        }));
+
+        DigestAuthorization {
+            challenge,
+            username: self.username,
+            username_hash,
+            uri: request.target(),
+            nonce_count,
+            cnonce,
+            response,
+        }
+    }
+}
+
+/// the `Authorization` value of a request answering a `Digest` challenge
+#[derive(Debug, Clone, Copy)]
+pub struct DigestAuthorization<'a, 'r> {
+    challenge: DigestChallenge<'a>,
+    username: &'a str,
+    username_hash: Option<HexDigest>,
+    uri: &'r str,
+    nonce_count: [u8; 8],
+    cnonce: &'r str,
+    response: HexDigest,
+}
+
+impl Display for DigestAuthorization<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let challenge = &self.challenge;
+        match self.username_hash {
+            Some(hash) => write!(f, "Digest username=\"{}\"", hash.as_str())?,
+            None => write!(f, "Digest username={}", Quoted(self.username))?,
+        }
+        // parameters from the challenge are kept escaped
+        write!(f, ", realm=\"{}\", uri={}", challenge.realm, Quoted(self.uri))?;
+        write!(f, ", algorithm={}, nonce=\"{}\"", challenge.algorithm.as_str(), challenge.nonce)?;
+        if challenge.qop {
+            let nonce_count = core::str::from_utf8(&self.nonce_count).unwrap_or_default();
+            write!(f, ", nc={nonce_count}, cnonce=\"{}\", qop=auth", self.cnonce)?;
+        } else if challenge.algorithm.is_session() {
+            write!(f, ", cnonce=\"{}\"", self.cnonce)?;
+        }
+        write!(f, ", response=\"{}\"", self.response.as_str())?;
+        if let Some(opaque) = challenge.opaque { write!(f, ", opaque=\"{opaque}\"")?; }
+        if challenge.userhash { f.write_str(", userhash=true")?; }
+        Ok(())
+    }
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+// a lowercase hex hash, 32 characters for md5 and 64 for sha-256
+#[derive(Debug, Clone, Copy)]
+struct HexDigest {
+    hex: [u8; 64],
+    len: usize,
+}
+
+impl HexDigest {
+    fn new(bytes: &[u8]) -> Self {
+        let mut hex = [0; 64];
+        for (idx, byte) in bytes.iter().enumerate() {
+            hex[idx * 2] = HEX[(byte >> 4) as usize];
+            hex[idx * 2 + 1] = HEX[(byte & 0xf) as usize];
+        }
+        Self { hex, len: bytes.len() * 2 }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.hex[..self.len]).unwrap_or_default()
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, input: &[u8]) {
+        match self {
+            Self::Md5(md5) => md5.update(input),
+            Self::Sha256(sha256) => sha256.update(input),
+        }
+    }
+
+    // hash the contents of a parsed quoted string, resolving its escapes
+    fn update_unescaped(&mut self, input: &str) {
+        let mut escaped = false;
+        for byte in input.bytes() {
+            if byte == b'\\' && !escaped {
+                escaped = true;
+                continue;
+            }
+            escaped = false;
+            self.update(&[byte]);
+        }
+    }
+}
+
+fn hash(algorithm: DigestAlgorithm, input: impl FnOnce(&mut Hasher)) -> HexDigest {
+    let mut hasher = match algorithm {
+        DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => Hasher::Md5(Md5::new()),
+        DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => Hasher::Sha256(Sha256::new()),
+    };
+    input(&mut hasher);
+    match hasher {
+        Hasher::Md5(md5) => HexDigest::new(&md5.finish()),
+        Hasher::Sha256(sha256) => HexDigest::new(&sha256.finish()),
+    }
+}
+
+// `H(username:realm:password)`, the realm is escaped
+fn user_hash(algorithm: DigestAlgorithm, username: &str, realm: &str, password: &str) -> HexDigest {
+    hash(algorithm, |hasher| {
+        hasher.update(username.as_bytes());
+        hasher.update(b":");
+        hasher.update_unescaped(realm);
+        hasher.update(b":");
+        hasher.update(password.as_bytes());
+    })
+}
+
+struct ResponseParts<'p> {
+    algorithm: DigestAlgorithm,
+    user_hash: &'p str,
+    nonce: &'p str,
+    /// `None` without a `qop`
+    nonce_count: Option<&'p str>,
+    cnonce: &'p str,
+    method: &'p str,
+    uri: &'p str,
+}
+
+// the `response` parameter (rfc 7616 section 3.4.1)
+fn response(parts: ResponseParts<'_>) -> HexDigest {
+    let algorithm = parts.algorithm;
+    let session = algorithm.is_session().then(|| hash(algorithm, |hasher| {
+        hasher.update(parts.user_hash.as_bytes());
+        hasher.update(b":");
+        hasher.update_unescaped(parts.nonce);
+        hasher.update(b":");
+        hasher.update_unescaped(parts.cnonce);
+    }));
+    let a1 = session.as_ref().map_or(parts.user_hash, HexDigest::as_str);
+    let a2 = hash(algorithm, |hasher| {
+        hasher.update(parts.method.as_bytes());
+        hasher.update(b":");
+        hasher.update(parts.uri.as_bytes());
+    });
+    hash(algorithm, |hasher| {
+        hasher.update(a1.as_bytes());
+        hasher.update(b":");
+        hasher.update_unescaped(parts.nonce);
+        hasher.update(b":");
+        if let Some(nonce_count) = parts.nonce_count {
+            hasher.update(nonce_count.as_bytes());
+            hasher.update(b":");
+            hasher.update_unescaped(parts.cnonce);
+            hasher.update(b":auth:");
+        }
+        hasher.update(a2.as_str().as_bytes());
+    })
+}
+
+// compare a parsed parameter against a string, resolving the parameter's escapes
+fn unescaped_eq(param: &str, value: &str) -> bool { unescaped(param).eq(value.bytes()) }
+
+// the bytes of an escaped parameter with the escapes resolved
+fn unescaped(param: &str) -> impl Iterator<Item = u8> + '_ {
+    let mut escaped = false;
+    param.bytes().filter(move |&byte| {
+        let skip = byte == b'\\' && !escaped;
+        escaped = skip;
+        !skip
+    })
+}
+
+// compare hex digests without exiting early, ignoring the case of the received digest
+fn constant_time_eq(received: &str, expected: &str) -> bool {
+    if received.len() != expected.len() { return false; }
+    received.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a.to_ascii_lowercase() ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+    use super::*;
+    use crate::{ parse_challenges, parse_request };
+
+    #[test]
+    fn escaped_realm_round_trips() {
+        let challenge = DigestChallenge::new(r#"Admin \"area\" \\ 1"#, "dcd98b7102dd2f0e", DigestAlgorithm::Sha256).unwrap();
+        let www_authenticate = challenge.to_string();
+        assert!(www_authenticate.starts_with(r#"Digest realm="Admin \"area\" \\ 1", qop="auth""#));
+
+        let parsed = parse_challenges(&www_authenticate).next().unwrap();
+        let parsed = DigestChallenge::from_challenge(&parsed).unwrap();
+        assert_eq!(parsed, challenge);
+
+        let mut headers = [crate::header::EMPTY_HEADER; 1];
+        let request = parse_request("GET /admin HTTP/1.1\r\n\r\n", &mut headers).unwrap();
+        let mut client = DigestClient::new(parsed, "Mufasa", "Circle of Life");
+        let authorization = client.authorize(&request, "0a4f113b").to_string();
+        let credentials = Credentials::parse(&authorization).unwrap();
+        let response = DigestResponse::from_credentials(&credentials).unwrap();
+        assert_eq!(response.realm(), challenge.realm());
+        assert!(response.verify(&request, challenge.realm(), "Mufasa", "Circle of Life"));
+        assert!(response.verify(&request, r#"Admin "area" \\ 1"#, "Mufasa", "Circle of Life"));
+        // the hash is over the unescaped realm
+        let expected = hash(DigestAlgorithm::Sha256, |hasher| hasher.update(br#"Mufasa:Admin "area" \ 1:Circle of Life"#));
+        let user_hash = user_hash(DigestAlgorithm::Sha256, "Mufasa", challenge.realm(), "Circle of Life");
+        assert_eq!(user_hash.as_str(), expected.as_str());
+    }
+
+    #[test]
+    fn unescaped_parameters_are_rejected() {
+        for realm in ["a\"b", "a\\", "a\r\nb"] {
+            assert!(matches!(DigestChallenge::new(realm, "n", DigestAlgorithm::Md5), Err(DigestError::InvalidParameter)));
+        }
+        let mut challenge = DigestChallenge::new("realm", "n", DigestAlgorithm::Md5).unwrap();
+        assert!(challenge.set_opaque("\"").is_err());
+        assert!(challenge.set_opaque("5ccc069c403ebaf9").is_ok());
+    }
+
+    #[test]
+    fn rfc_7616_example() {
+        // rfc 7616 section 3.9.1
+        let mut headers = [crate::header::EMPTY_HEADER; 1];
+        let request = parse_request("GET /dir/index.html HTTP/1.1\r\n\r\n", &mut headers).unwrap();
+        let realm = "http-auth@example.org";
+        for (algorithm, response) in [
+            ("SHA-256", "753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1"),
+            ("MD5", "8ca523f5e9506fed4657c9700eebdbec"),
+        ] {
+            let authorization = std::format!("Digest username=\"Mufasa\", realm=\"http-auth@example.org\", \
+                uri=\"/dir/index.html\", algorithm={algorithm}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+                nc=00000001, cnonce=\"f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ\", qop=auth, \
+                response=\"{response}\", opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"");
+            let credentials = Credentials::parse(&authorization).unwrap();
+            let digest = DigestResponse::from_credentials(&credentials).unwrap();
+            assert_eq!((digest.nonce_count(), digest.algorithm().as_str()), (Some(1), algorithm));
+            assert!(digest.verify(&request, realm, "Mufasa", "Circle of Life"), "{algorithm}");
+            assert!(!digest.verify(&request, realm, "Mufasa", "circle of life"), "{algorithm}");
+            // credentials for another realm aren't accepted, even with the right response for it
+            assert!(!digest.verify(&request, "other@example.org", "Mufasa", "Circle of Life"), "{algorithm}");
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use core::fmt::{ self, Write };
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// write `input` as padded base64 (rfc 4648 section 4)
+pub(crate) fn encode(input: impl IntoIterator<Item = u8>, out: &mut impl Write) -> fmt::Result {
+    encode_with(ALPHABET, true, input, out)
+}
+
+/// write `input` as unpadded base64url (rfc 4648 section 5)
+pub(crate) fn encode_url(input: impl IntoIterator<Item = u8>, out: &mut impl Write) -> fmt::Result {
+    encode_with(URL_ALPHABET, false, input, out)
+}
+
+fn encode_with(
+    alphabet: &[u8; 64],
+    padded: bool,
+    input: impl IntoIterator<Item = u8>,
+    out: &mut impl Write,
+) -> fmt::Result {
+    let mut input = input.into_iter();
+    while let Some(first) = input.next() {
+        let (second, third) = (input.next(), input.next());
+        let group = (first as u32) << 16 | (second.unwrap_or(0) as u32) << 8 | third.unwrap_or(0) as u32;
+        let sextet = |shift: u32| alphabet[(group >> shift & 0x3f) as usize] as char;
+        out.write_char(sextet(18))?;
+        out.write_char(sextet(12))?;
+        if second.is_some() { out.write_char(sextet(6))?; } else if padded { out.write_char('=')?; }
+        if third.is_some() { out.write_char(sextet(0))?; } else if padded { out.write_char('=')?; }
+    }
+    Ok(())
+}
+
+/// the number of bytes `input` decodes to, `None` if it isn't validly padded
+pub(crate) fn decoded_len(input: &str) -> Option<usize> {
+    let data = input.trim_end_matches('=');
+    let padding = input.len() - data.len();
+    if padding > 2 || (padding > 0 && !input.len().is_multiple_of(4)) || data.len() % 4 == 1 { return None; }
+    Some(data.len() * 3 / 4)
+}
+
+/// decode base64 with optional padding into `buf`, `None` if it is invalid or doesn't fit
+pub(crate) fn decode<'b>(input: &str, buf: &'b mut [u8]) -> Option<&'b [u8]> { decode_with(ALPHABET, input, buf) }
+
+/// decode base64url with optional padding into `buf`, `None` if it is invalid or doesn't fit
+pub(crate) fn decode_url<'b>(input: &str, buf: &'b mut [u8]) -> Option<&'b [u8]> { decode_with(URL_ALPHABET, input, buf) }
+
+fn decode_with<'b>(alphabet: &[u8; 64], input: &str, buf: &'b mut [u8]) -> Option<&'b [u8]> {
+    let out = buf.get_mut(..decoded_len(input)?)?;
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut idx = 0;
+    for &byte in input.trim_end_matches('=').as_bytes() {
+        let value = alphabet.iter().position(|&c| c == byte)? as u32;
+        bits = (bits << 6 | value) & 0xffff;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out[idx] = (bits >> count) as u8;
+            idx += 1;
+        }
+    }
+    Some(out)
+}
+
+/// encode `input` as padded base64 into `out`, `None` if it doesn't fit
+pub(crate) fn encode_to_slice<'o>(input: &[u8], out: &'o mut [u8]) -> Option<&'o str> {
+    write_to_slice(out, |slice| encode(input.iter().copied(), slice))
+}
+
+/// encode `input` as unpadded base64url into `out`, `None` if it doesn't fit
+pub(crate) fn encode_url_to_slice<'o>(input: &[u8], out: &'o mut [u8]) -> Option<&'o str> {
+    write_to_slice(out, |slice| encode_url(input.iter().copied(), slice))
+}
+
+struct Slice<'s> {
+    out: &'s mut [u8],
+    len: usize,
+}
+
+impl Write for Slice<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let dest = self.out.get_mut(self.len..self.len + s.len()).ok_or(fmt::Error)?;
+        dest.copy_from_slice(s.as_bytes());
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+fn write_to_slice<'o>(out: &'o mut [u8], write: impl FnOnce(&mut Slice<'o>) -> fmt::Result) -> Option<&'o str> {
+    let mut slice = Slice { out, len: 0 };
+    write(&mut slice).ok()?;
+    let Slice { out, len } = slice;
+    core::str::from_utf8(&out[..len]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc_4648_vectors() {
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")];
+        let (mut encoded, mut decoded) = ([0; 8], [0; 6]);
+        for (input, output) in vectors {
+            assert_eq!(encode_to_slice(input.as_bytes(), &mut encoded), Some(output));
+            assert_eq!(decode(output, &mut decoded), Some(input.as_bytes()));
+            // padding is optional when decoding
+            assert_eq!(decode(output.trim_end_matches('='), &mut decoded), Some(input.as_bytes()));
+        }
+        assert_eq!(encode_to_slice(b"foo", &mut [0; 4]), Some("Zm9v"));
+        assert_eq!(encode_to_slice(b"foob", &mut [0; 7]), None);
+        assert_eq!(decode("Zm9vYg", &mut [0; 3]), None);
+    }
+
+    #[test]
+    fn url_alphabet() {
+        let mut out = [0; 8];
+        assert_eq!(encode_url_to_slice(&[0xfb, 0xff, 0xbf, 0xfe], &mut out), Some("-_-__g"));
+        assert_eq!(decode_url("-_-__g==", &mut out), Some(&[0xfb, 0xff, 0xbf, 0xfe][..]));
+        assert_eq!(decode_url("+/+/", &mut out), None);
+        assert_eq!(decode("-_-_", &mut out), None);
+    }
+
+    #[test]
+    fn padding_errors() {
+        let mut out = [0; 8];
+        assert_eq!(decoded_len("Zg=="), Some(1));
+        // too much padding, padding that doesn't complete a group, a lone character and padding inside the data
+        for invalid in ["Zg===", "Z===", "Zm8==", "Zg=", "Zm9vY", "Zm9vY===", "Zg==Zg==", "Z=g="] {
+            assert_eq!(decode(invalid, &mut out), None, "{invalid}");
+        }
+    }
+}
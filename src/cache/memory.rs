@@ -0,0 +1,188 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{ String, ToString };
+use alloc::vec::Vec;
+
+use crate::{ CacheControl, HttpDate, Request, Response, ResponseTimes, StatusCode };
+use crate::header::StandardHeaderName;
+use super::{ CacheEntry, CacheKey, CacheStore, SecondaryKey };
+
+/// an owned copy of a response, along with what is needed to select and age it
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    status_code: StatusCode,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    vary: String,
+    secondary_key: String,
+    request_time: u64,
+    response_time: u64,
+}
+
+impl StoredResponse {
+    /// copy a response to `request`, `request_time` and `response_time` are when the request was sent and the
+    /// response received, in unix seconds
+    pub fn new<B, C: AsRef<[u8]>>(
+        request: &Request<'_, '_, B>,
+        response: &Response<'_, '_, C>,
+        request_time: u64,
+        response_time: u64,
+    ) -> Self {
+        let mut vary = String::new();
+        for header in response.headers().iter().filter(|header| header.is(StandardHeaderName::Vary)) {
+            if !vary.is_empty() { vary.push_str(", "); }
+            vary.push_str(header.value());
+        }
+        let secondary_key = SecondaryKey::new(&vary, request).to_string();
+        let headers = response.headers().iter()
+            .filter(|header| !header.name().as_str().is_empty())
+            .map(|header| (header.name().as_str().to_string(), header.value().to_string()))
+            .collect();
+
+        Self {
+            status_code: response.status_code,
+            headers,
+            body: response.body.as_ref().to_vec(),
+            vary,
+            secondary_key,
+            request_time,
+            response_time,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode { self.status_code }
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+    /// the first header with the given (case-insensitive) name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value)
+    }
+    pub fn body(&self) -> &[u8] { &self.body }
+
+    /// the combined `Cache-Control` directives of the response
+    pub fn cache_control(&self) -> CacheControl<'_> {
+        let mut cache_control = CacheControl::default();
+        for (_, value) in self.headers().filter(|(name, _)| name.eq_ignore_ascii_case("Cache-Control")) {
+            cache_control.merge(value);
+        }
+        cache_control
+    }
+
+    pub fn times(&self) -> ResponseTimes {
+        let date = |name| self.header(name).and_then(|value| HttpDate::parse(value).ok());
+        ResponseTimes {
+            request_time: self.request_time,
+            response_time: self.response_time,
+            date: date("Date"),
+            expires: self.header("Expires").map(|value| HttpDate::parse(value).ok()),
+            last_modified: date("Last-Modified"),
+            age: self.header("Age").and_then(|value| value.trim().parse().ok()),
+        }
+    }
+}
+
+impl CacheEntry for StoredResponse {
+    fn vary(&self) -> &str { &self.vary }
+    fn secondary_key(&self) -> &str { &self.secondary_key }
+}
+
+/// an in-memory [`CacheStore`] without any eviction
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: BTreeMap<String, Vec<StoredResponse>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self { Self::default() }
+
+    /// the number of stored responses, counting every variant
+    pub fn len(&self) -> usize { self.entries.values().map(Vec::len).sum() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+    pub fn clear(&mut self) { self.entries.clear(); }
+}
+
+impl CacheStore for MemoryStore {
+    type Entry = StoredResponse;
+
+    fn variants<'s>(&'s self, key: &CacheKey<'_>) -> impl Iterator<Item = &'s StoredResponse> + use<'s> where StoredResponse: 's {
+        self.entries.get(&key.to_string()).into_iter().flatten()
+    }
+
+    fn insert(&mut self, key: &CacheKey<'_>, entry: StoredResponse) {
+        let variants = self.entries.entry(key.to_string()).or_default();
+        variants.retain(|variant| variant.secondary_key != entry.secondary_key);
+        variants.push(entry);
+    }
+
+    fn remove(&mut self, key: &CacheKey<'_>) {
+        self.entries.remove(&key.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cache;
+    use crate::header::EMPTY_HEADER;
+    use crate::{ parse_request, parse_response };
+
+    // store `response` for `request` in the cache, returns whether it was stored
+    fn insert(cache: &mut Cache<MemoryStore>, request: &str, response: &str) -> bool {
+        let (mut request_headers, mut response_headers) = ([EMPTY_HEADER; 4], [EMPTY_HEADER; 4]);
+        let request = parse_request(request, &mut request_headers).unwrap();
+        let response = parse_response(response, &mut response_headers).unwrap();
+        cache.insert(&request, &response, "https", || StoredResponse::new(&request, &response, 0, 0))
+    }
+
+    fn lookup(cache: &Cache<MemoryStore>, request: &str) -> Option<Vec<u8>> {
+        let mut headers = [EMPTY_HEADER; 4];
+        let request = parse_request(request, &mut headers).unwrap();
+        cache.lookup(&request, "https").map(|entry| entry.body().to_vec())
+    }
+
+    #[test]
+    fn variants_are_selected_by_vary() {
+        let mut cache = Cache::new(MemoryStore::new(), false);
+        let gzip = "GET /a HTTP/1.1\r\nHost: example.com\r\nAccept-Encoding: gzip\r\n\r\n";
+        let br = "GET /a HTTP/1.1\r\nhost: EXAMPLE.com\r\naccept-encoding: br\r\n\r\n";
+        assert!(insert(&mut cache, gzip, "HTTP/1.1 200 OK\r\nVary: Accept-Encoding\r\n\r\ngzipped"));
+        assert!(insert(&mut cache, br, "HTTP/1.1 200 OK\r\nVary: Accept-Encoding\r\n\r\nbrotli"));
+        assert!(insert(&mut cache, gzip, "HTTP/1.1 200 OK\r\nVary: Accept-Encoding\r\n\r\ngzipped again"));
+        assert!(!insert(&mut cache, gzip, "HTTP/1.1 200 OK\r\nCache-Control: no-store\r\n\r\nnot stored"));
+        assert_eq!(cache.store().len(), 2);
+
+        assert_eq!(lookup(&cache, gzip).as_deref(), Some(&b"gzipped again"[..]));
+        assert_eq!(lookup(&cache, br).as_deref(), Some(&b"brotli"[..]));
+        assert_eq!(lookup(&cache, "GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n"), None);
+        assert_eq!(lookup(&cache, "HEAD /a HTTP/1.1\r\nHost: example.com\r\nAccept-Encoding: gzip\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn unsafe_requests_invalidate() {
+        let mut cache = Cache::new(MemoryStore::new(), true);
+        for target in ["/a", "/b", "/c", "/d"] {
+            let request = ["GET ", target, " HTTP/1.1\r\nHost: example.com\r\n\r\n"].concat();
+            assert!(insert(&mut cache, &request, "HTTP/1.1 200 OK\r\n\r\nbody"));
+        }
+        assert!(insert(&mut cache, "HEAD /a HTTP/1.1\r\nHost: example.com\r\n\r\n", "HTTP/1.1 200 OK\r\n\r\n"));
+        let invalidate = |cache: &mut Cache<MemoryStore>, request: &str, response: &str| {
+            let (mut request_headers, mut response_headers) = ([EMPTY_HEADER; 4], [EMPTY_HEADER; 4]);
+            let request = parse_request(request, &mut request_headers).unwrap();
+            let response = parse_response(response, &mut response_headers).unwrap();
+            cache.invalidate(&request, &response, "https");
+        };
+
+        // errors don't invalidate
+        invalidate(&mut cache, "POST /a HTTP/1.1\r\nHost: example.com\r\n\r\n", "HTTP/1.1 500 Internal Server Error\r\n\r\n");
+        assert_eq!(cache.store().len(), 5);
+        // the target, and `Location` and `Content-Location` on the same authority
+        invalidate(&mut cache, "POST /a HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            "HTTP/1.1 201 Created\r\nLocation: https://EXAMPLE.com/b\r\nContent-Location: /c\r\n\r\n");
+        assert_eq!(cache.store().len(), 1);
+        assert!(lookup(&cache, "GET /d HTTP/1.1\r\nHost: example.com\r\n\r\n").is_some());
+        // not on another authority
+        invalidate(&mut cache, "DELETE /x HTTP/1.1\r\nHost: example.com\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nLocation: https://example.org/d\r\n\r\n");
+        assert_eq!(cache.store().len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{ Header, HttpDate, Response };
+use crate::header::StandardHeaderName;
+use crate::syntax::{ self, List, Split };
+
+/// delta-seconds that overflow are clamped to 2^31 (rfc 9111 section 1.2.2)
+const MAX_DELTA_SECONDS: u32 = 1 << 31;
+
+/// one directive of a `Cache-Control` header, e.g. `max-age=60` or `no-store`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Directive<'c> {
+    name: &'c str,
+    value: Option<&'c str>,
+}
+
+impl<'c> Directive<'c> {
+    pub fn name(&self) -> &'c str { self.name }
+    /// the argument, unquoted but with any escapes intact
+    pub fn value(&self) -> Option<&'c str> { self.value }
+
+    /// whether this directive isn't one of the directives defined by rfc 9111, rfc 8246 or rfc 5861
+    pub fn is_extension(&self) -> bool {
+        const KNOWN: [&str; 16] = [
+            "max-age", "s-maxage", "max-stale", "min-fresh", "no-cache", "no-store", "no-transform",
+            "only-if-cached", "must-revalidate", "proxy-revalidate", "must-understand", "private", "public",
+            "immutable", "stale-while-revalidate", "stale-if-error",
+        ];
+        !KNOWN.iter().any(|known| known.eq_ignore_ascii_case(self.name))
+    }
+}
+
+/// the directives of a `Cache-Control` header value
+pub struct Directives<'c>(Split<'c>);
+
+impl<'c> Directives<'c> {
+    pub fn new(input: &'c str) -> Self { Self(syntax::list(input)) }
+}
+
+impl<'c> Iterator for Directives<'c> {
+    type Item = Directive<'c>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|directive| match directive.split_once('=') {
+            Some((name, value)) => Directive {
+                name: syntax::trim_ows(name),
+                value: Some(syntax::unquote(syntax::trim_ows(value))),
+            },
+            None => Directive { name: directive, value: None },
+        })
+    }
+}
+
+/// the request and response directives of one or more `Cache-Control` headers
+///
+/// directives with malformed or duplicated arguments make a response stale (rfc 9111 section 4.2.1), so their
+/// argument is taken to be `0`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheControl<'c> {
+    max_age: Option<u32>,
+    s_maxage: Option<u32>,
+    max_stale: Option<Option<u32>>,
+    min_fresh: Option<u32>,
+    no_cache: Option<Option<&'c str>>,
+    private: Option<Option<&'c str>>,
+    no_store: bool,
+    no_transform: bool,
+    only_if_cached: bool,
+    must_revalidate: bool,
+    proxy_revalidate: bool,
+    must_understand: bool,
+    public: bool,
+    immutable: bool,
+    stale_while_revalidate: Option<u32>,
+    stale_if_error: Option<u32>,
+}
+
+impl<'c> CacheControl<'c> {
+    pub fn parse(input: &'c str) -> Self {
+        let mut cache_control = Self::default();
+        cache_control.merge(input);
+        cache_control
+    }
+
+    /// combine every `Cache-Control` header in `headers`
+    pub fn from_headers(headers: &[Header<'c>]) -> Self {
+        let mut cache_control = Self::default();
+        for header in headers.iter().filter(|header| header.is(StandardHeaderName::CacheControl)) {
+            cache_control.merge(header.value());
+        }
+        cache_control
+    }
+
+    /// add the directives of another `Cache-Control` header value
+    pub fn merge(&mut self, input: &'c str) {
+        for directive in Directives::new(input) {
+            let seconds = directive.value.map_or(Some(0), delta_seconds).unwrap_or(0);
+            let name = directive.name;
+            match name {
+                _ if name.eq_ignore_ascii_case("max-age") => set_once(&mut self.max_age, seconds),
+                _ if name.eq_ignore_ascii_case("s-maxage") => set_once(&mut self.s_maxage, seconds),
+                _ if name.eq_ignore_ascii_case("min-fresh") => set_once(&mut self.min_fresh, seconds),
+                _ if name.eq_ignore_ascii_case("max-stale") => {
+                    self.max_stale = Some(directive.value.map(|value| delta_seconds(value).unwrap_or(0)));
+                }
+                _ if name.eq_ignore_ascii_case("stale-while-revalidate") => set_once(&mut self.stale_while_revalidate, seconds),
+                _ if name.eq_ignore_ascii_case("stale-if-error") => set_once(&mut self.stale_if_error, seconds),
+                _ if name.eq_ignore_ascii_case("no-cache") => self.no_cache = Some(directive.value),
+                _ if name.eq_ignore_ascii_case("private") => self.private = Some(directive.value),
+                _ if name.eq_ignore_ascii_case("no-store") => self.no_store = true,
+                _ if name.eq_ignore_ascii_case("no-transform") => self.no_transform = true,
+                _ if name.eq_ignore_ascii_case("only-if-cached") => self.only_if_cached = true,
+                _ if name.eq_ignore_ascii_case("must-revalidate") => self.must_revalidate = true,
+                _ if name.eq_ignore_ascii_case("proxy-revalidate") => self.proxy_revalidate = true,
+                _ if name.eq_ignore_ascii_case("must-understand") => self.must_understand = true,
+                _ if name.eq_ignore_ascii_case("public") => self.public = true,
+                _ if name.eq_ignore_ascii_case("immutable") => self.immutable = true,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn max_age(&self) -> Option<u32> { self.max_age }
+    pub fn s_maxage(&self) -> Option<u32> { self.s_maxage }
+    /// `Some(None)` for a `max-stale` without a limit
+    pub fn max_stale(&self) -> Option<Option<u32>> { self.max_stale }
+    pub fn min_fresh(&self) -> Option<u32> { self.min_fresh }
+    /// whether `no-cache` applies to the whole response (it has no field names)
+    pub fn no_cache(&self) -> bool { matches!(self.no_cache, Some(None)) }
+    /// the fields named by a qualified `no-cache="..."`
+    pub fn no_cache_fields(&self) -> Option<List<'c>> { self.no_cache.flatten().map(List::new) }
+    /// whether `private` applies to the whole response (it has no field names)
+    pub fn private(&self) -> bool { matches!(self.private, Some(None)) }
+    /// the fields named by a qualified `private="..."`
+    pub fn private_fields(&self) -> Option<List<'c>> { self.private.flatten().map(List::new) }
+    pub fn no_store(&self) -> bool { self.no_store }
+    pub fn no_transform(&self) -> bool { self.no_transform }
+    pub fn only_if_cached(&self) -> bool { self.only_if_cached }
+    pub fn must_revalidate(&self) -> bool { self.must_revalidate }
+    pub fn proxy_revalidate(&self) -> bool { self.proxy_revalidate }
+    pub fn must_understand(&self) -> bool { self.must_understand }
+    pub fn public(&self) -> bool { self.public }
+    pub fn immutable(&self) -> bool { self.immutable }
+    pub fn stale_while_revalidate(&self) -> Option<u32> { self.stale_while_revalidate }
+    pub fn stale_if_error(&self) -> Option<u32> { self.stale_if_error }
+}
+
+fn delta_seconds(input: &str) -> Option<u32> {
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) { return None; }
+    Some(input.parse::<u32>().map_or(MAX_DELTA_SECONDS, |seconds| seconds.min(MAX_DELTA_SECONDS)))
+}
+
+// a repeated directive is invalid, which makes the response stale
+fn set_once(directive: &mut Option<u32>, seconds: u32) {
+    *directive = Some(if directive.is_some() { 0 } else { seconds });
+}
+
+/// the timing information of a stored response that its freshness depends on, in unix seconds
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResponseTimes {
+    /// when the request that led to the response was sent
+    pub request_time: u64,
+    /// when the response was received
+    pub response_time: u64,
+    pub date: Option<HttpDate>,
+    /// `Some(None)` for an `Expires` header with an invalid date, which means already expired
+    pub expires: Option<Option<HttpDate>>,
+    pub last_modified: Option<HttpDate>,
+    /// the `Age` header
+    pub age: Option<u64>,
+}
+
+impl ResponseTimes {
+    pub fn from_response<B>(response: &Response<'_, '_, B>, request_time: u64, response_time: u64) -> Self {
+        let date = |name| response.header(name).and_then(|header| HttpDate::parse(header.value()).ok());
+        Self {
+            request_time,
+            response_time,
+            date: date(StandardHeaderName::Date),
+            expires: response.header(StandardHeaderName::Expires).map(|header| HttpDate::parse(header.value()).ok()),
+            last_modified: date(StandardHeaderName::LastModified),
+            age: response.header(StandardHeaderName::Age).and_then(|header| header.value().trim().parse().ok()),
+        }
+    }
+
+    /// the current age of the response at `now` (rfc 9111 section 4.2.3)
+    pub fn current_age(&self, now: u64) -> u64 {
+        let date = self.date.map_or(self.response_time, |date| date.unix());
+        let apparent_age = self.response_time.saturating_sub(date);
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        let corrected_age = self.age.unwrap_or(0).saturating_add(response_delay);
+        apparent_age.max(corrected_age).saturating_add(now.saturating_sub(self.response_time))
+    }
+}
+
+/// how long a response stays fresh (rfc 9111 section 4.2.1)
+///
+/// `heuristic` allows a heuristic lifetime of 10% of the time since `Last-Modified`, for responses whose status code
+/// is heuristically cacheable
+pub fn freshness_lifetime(cache_control: &CacheControl<'_>, times: &ResponseTimes, shared: bool, heuristic: bool) -> u64 {
+    if let (true, Some(s_maxage)) = (shared, cache_control.s_maxage) { return s_maxage as u64; }
+    if let Some(max_age) = cache_control.max_age { return max_age as u64; }
+    let date = times.date.map_or(times.response_time, |date| date.unix());
+    match times.expires {
+        Some(Some(expires)) => return expires.unix().saturating_sub(date),
+        Some(None) => return 0,
+        None => {}
+    }
+    match (heuristic || cache_control.public, times.last_modified) {
+        (true, Some(last_modified)) => date.saturating_sub(last_modified.unix()) / 10,
+        _ => 0,
+    }
+}
+
+/// whether a stored response can be used to satisfy a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreshnessState {
+    /// the response is fresh (or stale within the request's `max-stale`) and can be used as is
+    Fresh,
+    /// the response can be used while it is revalidated in the background (rfc 5861 section 3)
+    StaleWhileRevalidate,
+    /// the response can be used only if revalidating it fails with an error (rfc 5861 section 4)
+    StaleIfError,
+    /// the response has to be revalidated before it is used
+    MustRevalidate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freshness {
+    pub lifetime: u64,
+    pub age: u64,
+    pub state: FreshnessState,
+}
+
+/// decide whether a stored response is fresh at `now`, given its `Cache-Control` and that of the request (if any)
+///
+/// `shared` caches also obey `s-maxage` and `proxy-revalidate`
+pub fn freshness(
+    response: &CacheControl<'_>,
+    times: &ResponseTimes,
+    request: Option<&CacheControl<'_>>,
+    now: u64,
+    shared: bool,
+    heuristic: bool,
+) -> Freshness {
+    let lifetime = freshness_lifetime(response, times, shared, heuristic);
+    let age = times.current_age(now);
+    let request = request.copied().unwrap_or_default();
+    let result = |state| Freshness { lifetime, age, state };
+
+    if response.no_cache() || request.no_cache() { return result(FreshnessState::MustRevalidate); }
+
+    if age < lifetime {
+        let within_max_age = request.max_age.is_none_or(|max_age| age <= max_age as u64);
+        let within_min_fresh = request.min_fresh.is_none_or(|min_fresh| lifetime - age >= min_fresh as u64);
+        let state = if within_max_age && within_min_fresh { FreshnessState::Fresh } else { FreshnessState::MustRevalidate };
+        return result(state);
+    }
+
+    let staleness = age - lifetime;
+    let must_revalidate = response.must_revalidate
+        || (shared && (response.proxy_revalidate || response.s_maxage.is_some()));
+    if must_revalidate { return result(FreshnessState::MustRevalidate); }
+
+    let within = |limit: Option<u32>| limit.is_some_and(|limit| staleness <= limit as u64);
+    let state = match request.max_stale {
+        Some(None) => FreshnessState::Fresh,
+        Some(max_stale) if within(max_stale) => FreshnessState::Fresh,
+        _ if within(response.stale_while_revalidate) => FreshnessState::StaleWhileRevalidate,
+        _ if within(response.stale_if_error) || within(request.stale_if_error) => FreshnessState::StaleIfError,
+        _ => FreshnessState::MustRevalidate,
+    };
+    result(state)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+    use super::*;
+    use crate::header::EMPTY_HEADER;
+    use crate::parse_response;
+
+    #[test]
+    fn directives() {
+        let directives: Vec<_> = Directives::new("max-age = 60 , No-Cache=\"Set-Cookie, Vary\", x-ext, private")
+            .map(|directive| (directive.name(), directive.value(), directive.is_extension()))
+            .collect();
+        assert_eq!(directives, [
+            ("max-age", Some("60"), false),
+            ("No-Cache", Some("Set-Cookie, Vary"), false),
+            ("x-ext", None, true),
+            ("private", None, false),
+        ]);
+
+        let cache_control = CacheControl::parse("public, MAX-AGE=60, s-maxage=99999999999, max-stale, no-cache=\"a, b\"");
+        assert!(cache_control.public());
+        assert_eq!(cache_control.max_age(), Some(60));
+        assert_eq!(cache_control.s_maxage(), Some(MAX_DELTA_SECONDS));
+        assert_eq!(cache_control.max_stale(), Some(None));
+        assert!(!cache_control.no_cache() && !cache_control.private());
+        assert_eq!(cache_control.no_cache_fields().unwrap().collect::<Vec<_>>(), ["a", "b"]);
+
+        // malformed and repeated arguments count as 0
+        let cache_control = CacheControl::parse("max-age=-1, min-fresh=1.5, stale-if-error, max-stale=x");
+        assert_eq!(cache_control.max_age(), Some(0));
+        assert_eq!(cache_control.min_fresh(), Some(0));
+        assert_eq!(cache_control.stale_if_error(), Some(0));
+        assert_eq!(cache_control.max_stale(), Some(Some(0)));
+
+        let mut headers = [EMPTY_HEADER; 3];
+        let input = "HTTP/1.1 200 OK\r\nCache-Control: max-age=60, private\r\nCache-Control: MAX-AGE=120, no-store\r\n\r\n";
+        let response = parse_response(input, &mut headers).unwrap();
+        let cache_control = CacheControl::from_headers(response.headers);
+        assert_eq!(cache_control.max_age(), Some(0));
+        assert!(cache_control.private() && cache_control.no_store());
+    }
+
+    const DATE: u64 = 784_111_777;
+
+    #[test]
+    fn age_and_lifetime() {
+        let mut headers = [EMPTY_HEADER; 5];
+        let input = "HTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nAge: 30\r\n\
+            Expires: Sun, 06 Nov 1994 09:49:37 GMT\r\nLast-Modified: Sat, 05 Nov 1994 08:49:37 GMT\r\n\r\n";
+        let response = parse_response(input, &mut headers).unwrap();
+        // the response took 10 seconds to arrive and is dated 5 seconds before it did
+        let times = ResponseTimes::from_response(&response, DATE - 5, DATE + 5);
+        assert_eq!(times.age, Some(30));
+        assert_eq!(times.current_age(DATE + 5), 40);
+        assert_eq!(times.current_age(DATE + 105), 140);
+        let times = ResponseTimes { age: None, ..times };
+        assert_eq!(times.current_age(DATE + 5), 10);
+        let times = ResponseTimes { age: Some(0), request_time: DATE + 5, ..times };
+        assert_eq!(times.current_age(DATE), 5);
+
+        // s-maxage for shared caches, then max-age, expires and the heuristic
+        let cache_control = CacheControl::parse("max-age=60, s-maxage=120");
+        assert_eq!(freshness_lifetime(&cache_control, &times, true, false), 120);
+        assert_eq!(freshness_lifetime(&cache_control, &times, false, false), 60);
+        assert_eq!(freshness_lifetime(&CacheControl::default(), &times, false, false), 3600);
+        let no_expires = ResponseTimes { expires: None, ..times };
+        assert_eq!(freshness_lifetime(&CacheControl::default(), &no_expires, false, false), 0);
+        assert_eq!(freshness_lifetime(&CacheControl::default(), &no_expires, false, true), 8640);
+        assert_eq!(freshness_lifetime(&CacheControl::parse("public"), &no_expires, false, false), 8640);
+        let invalid_expires = ResponseTimes { expires: Some(None), ..times };
+        assert_eq!(freshness_lifetime(&CacheControl::default(), &invalid_expires, false, true), 0);
+    }
+
+    #[test]
+    fn freshness_states() {
+        let times = ResponseTimes { request_time: DATE, response_time: DATE, ..Default::default() };
+        let state = |response: &str, request: Option<&str>, age: u64, shared: bool| {
+            let request = request.map(CacheControl::parse);
+            freshness(&CacheControl::parse(response), &times, request.as_ref(), DATE + age, shared, false).state
+        };
+        assert_eq!(freshness(&CacheControl::parse("max-age=60"), &times, None, DATE + 10, false, false),
+            Freshness { lifetime: 60, age: 10, state: FreshnessState::Fresh });
+        assert_eq!(state("max-age=60", None, 60, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60, no-cache", None, 0, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60", Some("no-cache"), 0, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60", Some("max-age=5"), 10, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60", Some("min-fresh=50"), 10, false), FreshnessState::Fresh);
+        assert_eq!(state("max-age=60", Some("min-fresh=51"), 10, false), FreshnessState::MustRevalidate);
+
+        assert_eq!(state("max-age=60", Some("max-stale"), 1000, false), FreshnessState::Fresh);
+        assert_eq!(state("max-age=60", Some("max-stale=10"), 70, false), FreshnessState::Fresh);
+        assert_eq!(state("max-age=60", Some("max-stale=10"), 71, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60, must-revalidate", Some("max-stale"), 70, false), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60, proxy-revalidate", Some("max-stale"), 70, false), FreshnessState::Fresh);
+        assert_eq!(state("max-age=60, proxy-revalidate", Some("max-stale"), 70, true), FreshnessState::MustRevalidate);
+        assert_eq!(state("max-age=60, stale-while-revalidate=30, stale-if-error=300", None, 90, false),
+            FreshnessState::StaleWhileRevalidate);
+        assert_eq!(state("max-age=60, stale-while-revalidate=30, stale-if-error=300", None, 91, false),
+            FreshnessState::StaleIfError);
+        assert_eq!(state("max-age=60", Some("stale-if-error=30"), 90, false), FreshnessState::StaleIfError);
+    }
+}
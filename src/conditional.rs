@@ -0,0 +1,275 @@
+use crate::{ Header, HttpDate, Method, Request, Response, StatusCode };
+use crate::header::StandardHeaderName;
+use crate::syntax;
+
+/// an opaque validator from an `ETag` header, e.g. `"xyzzy"` or `W/"xyzzy"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityTag<'e> {
+    weak: bool,
+    tag: &'e str,
+}
+
+#[derive(Debug)]
+pub struct InvalidEntityTag;
+
+impl<'e> EntityTag<'e> {
+    /// the tag is given without its surrounding quotes
+    pub fn strong(tag: &'e str) -> Self { Self { weak: false, tag } }
+    pub fn weak(tag: &'e str) -> Self { Self { weak: true, tag } }
+
+    pub fn parse(input: &'e str) -> Result<Self, InvalidEntityTag> {
+        let input = syntax::trim_ows(input);
+        let (weak, quoted) = match input.strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, input),
+        };
+        let tag = quoted.strip_prefix('"').and_then(|tag| tag.strip_suffix('"')).ok_or(InvalidEntityTag)?;
+        // etagc = %x21 / %x23-7E / obs-text
+        if tag.bytes().any(|b| b == b'"' || b <= b' ' || b == 0x7f) { return Err(InvalidEntityTag); }
+        Ok(Self { weak, tag })
+    }
+
+    pub fn is_weak(&self) -> bool { self.weak }
+    /// the tag without its quotes or weakness indicator
+    pub fn tag(&self) -> &'e str { self.tag }
+
+    /// both tags are strong and have the same opaque tag (rfc 9110 section 8.8.3.2)
+    pub fn strong_eq(&self, other: &EntityTag<'_>) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// the tags have the same opaque tag, regardless of either being weak
+    pub fn weak_eq(&self, other: &EntityTag<'_>) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl<'e> TryFrom<&'e str> for EntityTag<'e> {
+    type Error = InvalidEntityTag;
+    fn try_from(value: &'e str) -> Result<Self, Self::Error> { Self::parse(value) }
+}
+
+impl core::fmt::Display for EntityTag<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.weak { f.write_str("W/")?; }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// the entity tags of an `If-Match` or `If-None-Match` list, malformed tags are skipped
+///
+/// a `*` list yields no tags, check for it with [`is_any`]
+pub struct EntityTags<'e>(syntax::Split<'e>);
+
+impl<'e> EntityTags<'e> {
+    pub fn new(input: &'e str) -> Self { Self(syntax::list(input)) }
+}
+
+impl<'e> Iterator for EntityTags<'e> {
+    type Item = EntityTag<'e>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|tag| EntityTag::parse(tag).ok())
+    }
+}
+
+/// whether an `If-Match` or `If-None-Match` value is `*`
+pub fn is_any(input: &str) -> bool {
+    syntax::trim_ows(input) == "*"
+}
+
+/// the current validators of the target resource's selected representation
+#[derive(Debug, Clone, Copy)]
+pub struct Validators<'v> {
+    exists: bool,
+    etag: Option<EntityTag<'v>>,
+    last_modified: Option<HttpDate>,
+}
+
+impl<'v> Validators<'v> {
+    pub fn new(etag: Option<EntityTag<'v>>, last_modified: Option<HttpDate>) -> Self {
+        Self { exists: true, etag, last_modified }
+    }
+
+    /// the target resource has no current representation (`*` never matches)
+    pub fn missing() -> Self {
+        Self { exists: false, etag: None, last_modified: None }
+    }
+
+    /// the `ETag` and `Last-Modified` headers of a response, malformed values are ignored
+    pub fn from_response<B>(response: &Response<'v, '_, B>) -> Self {
+        let etag = response.header(StandardHeaderName::Etag).and_then(|header| EntityTag::parse(header.value()).ok());
+        let last_modified = response.header(StandardHeaderName::LastModified)
+            .and_then(|header| HttpDate::parse(header.value()).ok());
+        Self::new(etag, last_modified)
+    }
+
+    pub fn etag(&self) -> Option<EntityTag<'v>> { self.etag }
+    pub fn last_modified(&self) -> Option<HttpDate> { self.last_modified }
+}
+
+/// the outcome of evaluating a request's preconditions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precondition {
+    /// perform the method (honouring any `Range` header)
+    Proceed,
+    /// perform the method, but ignore the `Range` header as its `If-Range` condition failed
+    IgnoreRange,
+    /// respond with `304 Not Modified`
+    NotModified,
+    /// respond with `412 Precondition Failed`
+    PreconditionFailed,
+}
+
+impl Precondition {
+    /// the status code to respond with instead of performing the method, if any
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            Self::Proceed | Self::IgnoreRange => None,
+            Self::NotModified => Some(StatusCode::NotModified),
+            Self::PreconditionFailed => Some(StatusCode::PreconditionFailed),
+        }
+    }
+}
+
+/// evaluate the conditional headers of a request in the order of rfc 9110 section 13.2.2
+///
+/// dates that fail to parse cause their header to be ignored
+pub fn evaluate<B>(request: &Request<'_, '_, B>, validators: &Validators<'_>) -> Precondition {
+    let headers = request.headers();
+    let is_get_or_head = matches!(request.method, Method::Get | Method::Head);
+    let date = |name| request.header(name).and_then(|header| HttpDate::parse(header.value()).ok());
+
+    if has(headers, StandardHeaderName::IfMatch) {
+        if !list_matches(headers, StandardHeaderName::IfMatch, validators, |tag, etag| tag.strong_eq(etag)) {
+            return Precondition::PreconditionFailed;
+        }
+    } else if let (Some(since), Some(last_modified)) = (date(StandardHeaderName::IfUnmodifiedSince), validators.last_modified) {
+        if last_modified > since { return Precondition::PreconditionFailed; }
+    }
+
+    if has(headers, StandardHeaderName::IfNoneMatch) {
+        if list_matches(headers, StandardHeaderName::IfNoneMatch, validators, |tag, etag| tag.weak_eq(etag)) {
+            return if is_get_or_head { Precondition::NotModified } else { Precondition::PreconditionFailed };
+        }
+    } else if let (true, Some(since), Some(last_modified)) = (is_get_or_head, date(StandardHeaderName::IfModifiedSince), validators.last_modified) {
+        if last_modified <= since { return Precondition::NotModified; }
+    }
+
+    if request.method == Method::Get && has(headers, StandardHeaderName::Range) {
+        if let Some(if_range) = request.header(StandardHeaderName::IfRange) {
+            if !if_range_matches(if_range.value(), validators) { return Precondition::IgnoreRange; }
+        }
+    }
+    Precondition::Proceed
+}
+
+/// evaluate an `If-Range` value, either a strong entity tag or the exact last modification date
+pub fn if_range_matches(input: &str, validators: &Validators<'_>) -> bool {
+    match EntityTag::parse(input) {
+        Ok(tag) => validators.etag.is_some_and(|etag| etag.strong_eq(&tag)),
+        Err(_) => match (HttpDate::parse(syntax::trim_ows(input)), validators.last_modified) {
+            (Ok(date), Some(last_modified)) => date == last_modified,
+            _ => false,
+        },
+    }
+}
+
+fn has(headers: &[Header<'_>], name: StandardHeaderName) -> bool {
+    headers.iter().any(|header| header.is(name))
+}
+
+// whether any tag in any of the (possibly repeated) list headers matches the current entity tag
+fn list_matches(
+    headers: &[Header<'_>],
+    name: StandardHeaderName,
+    validators: &Validators<'_>,
+    eq: impl Fn(&EntityTag<'_>, &EntityTag<'_>) -> bool,
+) -> bool {
+    headers.iter().filter(|header| header.is(name)).any(|header| {
+        if is_any(header.value()) { return validators.exists; }
+        validators.etag.is_some_and(|etag| EntityTags::new(header.value()).any(|tag| eq(&tag, &etag)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ format, vec::Vec };
+    use super::*;
+    use crate::header::EMPTY_HEADER;
+    use crate::parse_request;
+
+    #[test]
+    fn weak_and_strong_comparison() {
+        let (strong, weak) = (EntityTag::parse("\"1\"").unwrap(), EntityTag::parse(" W/\"1\" ").unwrap());
+        assert_eq!((strong, weak), (EntityTag::strong("1"), EntityTag::weak("1")));
+        assert!(strong.strong_eq(&strong) && strong.weak_eq(&weak) && weak.weak_eq(&weak));
+        assert!(!strong.strong_eq(&weak) && !weak.strong_eq(&weak));
+        assert!(!strong.weak_eq(&EntityTag::strong("2")));
+        assert_eq!(format!("{weak}"), "W/\"1\"");
+        assert_eq!(EntityTag::parse("\"\"").ok(), Some(EntityTag::strong("")));
+        for invalid in ["1", "w/\"1\"", "\"1", "\"a b\"", "\"a\"b\""] {
+            assert!(EntityTag::parse(invalid).is_err(), "{invalid}");
+        }
+
+        let tags: Vec<_> = EntityTags::new("\"a\", W/\"b\", c, \"d\"").map(|tag| tag.tag()).collect();
+        assert_eq!(tags, ["a", "b", "d"]);
+        assert!(is_any(" * ") && !is_any("\"*\""));
+    }
+
+    const ETAG: EntityTag<'static> = EntityTag { weak: false, tag: "v2" };
+
+    fn evaluate_with(method: &str, conditions: &str, validators: &Validators<'_>) -> Precondition {
+        let input = format!("{method} / HTTP/1.1\r\nHost: a\r\n{conditions}\r\n");
+        let mut headers = [EMPTY_HEADER; 6];
+        let request = parse_request(&input, &mut headers).unwrap();
+        evaluate(&request, validators)
+    }
+
+    #[test]
+    fn evaluation_order() {
+        let validators = Validators::new(Some(ETAG), Some(HttpDate::from_unix(784_111_777)));
+        let current = |method: &str, conditions: &str| evaluate_with(method, conditions, &validators);
+        const OLD: &str = "Sat, 05 Nov 1994 08:49:37 GMT";
+        const NEW: &str = "Mon, 07 Nov 1994 08:49:37 GMT";
+
+        assert_eq!(current("GET", ""), Precondition::Proceed);
+        // if-match is strong and overrides if-unmodified-since
+        assert_eq!(current("PUT", "If-Match: \"v1\", \"v2\"\r\n"), Precondition::Proceed);
+        assert_eq!(current("PUT", "If-Match: W/\"v2\"\r\n"), Precondition::PreconditionFailed);
+        assert_eq!(current("PUT", &format!("If-Match: *\r\nIf-Unmodified-Since: {OLD}\r\n")), Precondition::Proceed);
+        assert_eq!(current("PUT", &format!("If-Unmodified-Since: {OLD}\r\n")), Precondition::PreconditionFailed);
+        assert_eq!(current("PUT", "If-Unmodified-Since: yesterday\r\n"), Precondition::Proceed);
+        // a failed if-match wins over a matching if-none-match
+        assert_eq!(current("GET", "If-Match: \"v1\"\r\nIf-None-Match: \"v2\"\r\n"), Precondition::PreconditionFailed);
+
+        // if-none-match is weak and overrides if-modified-since
+        assert_eq!(current("GET", "If-None-Match: \"v1\"\r\nIf-None-Match: W/\"v2\"\r\n"), Precondition::NotModified);
+        assert_eq!(current("HEAD", "If-None-Match: *\r\n"), Precondition::NotModified);
+        assert_eq!(current("POST", "If-None-Match: \"v2\"\r\n"), Precondition::PreconditionFailed);
+        assert_eq!(current("GET", &format!("If-None-Match: \"v1\"\r\nIf-Modified-Since: {NEW}\r\n")), Precondition::Proceed);
+        assert_eq!(current("GET", &format!("If-Modified-Since: {NEW}\r\n")), Precondition::NotModified);
+        assert_eq!(current("GET", &format!("If-Modified-Since: {OLD}\r\n")), Precondition::Proceed);
+        assert_eq!(current("POST", &format!("If-Modified-Since: {NEW}\r\n")), Precondition::Proceed);
+
+        // if-range only applies to a get with a range
+        assert_eq!(current("GET", "Range: bytes=0-1\r\nIf-Range: \"v2\"\r\n"), Precondition::Proceed);
+        assert_eq!(current("GET", "Range: bytes=0-1\r\nIf-Range: W/\"v2\"\r\n"), Precondition::IgnoreRange);
+        assert_eq!(current("GET", "Range: bytes=0-1\r\nIf-Range: Sun, 06 Nov 1994 08:49:37 GMT\r\n"), Precondition::Proceed);
+        assert_eq!(current("GET", &format!("Range: bytes=0-1\r\nIf-Range: {NEW}\r\n")), Precondition::IgnoreRange);
+        assert_eq!(current("GET", "If-Range: \"v1\"\r\n"), Precondition::Proceed);
+        assert_eq!(current("HEAD", "Range: bytes=0-1\r\nIf-Range: \"v1\"\r\n"), Precondition::Proceed);
+        assert_eq!(Precondition::NotModified.status_code(), Some(StatusCode::NotModified));
+        assert_eq!(Precondition::IgnoreRange.status_code(), None);
+    }
+
+    #[test]
+    fn missing_representations_never_match_any() {
+        let missing = Validators::missing();
+        assert_eq!(evaluate_with("PUT", "If-Match: *\r\n", &missing), Precondition::PreconditionFailed);
+        assert_eq!(evaluate_with("PUT", "If-None-Match: *\r\n", &missing), Precondition::Proceed);
+        let unmodified = Validators::new(None, Some(HttpDate::UNIX_EPOCH));
+        assert_eq!(evaluate_with("PUT", "If-Match: *\r\n", &unmodified), Precondition::Proceed);
+        assert_eq!(evaluate_with("PUT", "If-Match: \"v2\"\r\n", &unmodified), Precondition::PreconditionFailed);
+    }
+}
@@ -0,0 +1,291 @@
+use core::fmt::{ self, Display, Write };
+use crate::syntax::{ self, Params };
+use crate::form::percent_decoded;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentDispositionError {
+    /// the disposition type isn't a token
+    InvalidType,
+    /// a parameter that isn't `name=token`, `name=quoted-string` or, for `name*`, `name*=charset'lang'value`
+    InvalidParameter,
+    /// the buffer is too small for the decoded value
+    BufferTooSmall,
+}
+
+/// a `Content-Disposition` header (rfc 6266), or the header of a `multipart/form-data` part (rfc 7578)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentDisposition<'c> {
+    disposition: &'c str,
+    params: &'c str,
+}
+
+impl<'c> ContentDisposition<'c> {
+    pub fn parse(input: &'c str) -> Result<Self, ContentDispositionError> {
+        let input = syntax::trim_ows(input);
+        let (disposition, params) = input.split_once(';').unwrap_or((input, ""));
+        let disposition = syntax::trim_ows(disposition);
+        if !syntax::is_token(disposition) { return Err(ContentDispositionError::InvalidType); }
+        for param in syntax::Split::new(params, b';') {
+            let (name, value) = param.split_once('=').ok_or(ContentDispositionError::InvalidParameter)?;
+            let (name, value) = (syntax::trim_ows(name), syntax::trim_ows(value));
+            let valid_value = match name.strip_suffix('*') {
+                Some(_) => ExtValue::parse(value).is_some(),
+                None => syntax::is_token(value) || syntax::is_quoted_string(value),
+            };
+            if !syntax::is_token(name) || !valid_value { return Err(ContentDispositionError::InvalidParameter); }
+        }
+        Ok(Self { disposition, params })
+    }
+
+    /// the disposition type, e.g. `attachment`
+    pub fn disposition(&self) -> &'c str { self.disposition }
+    pub fn is_inline(&self) -> bool { self.disposition.eq_ignore_ascii_case("inline") }
+    /// an unknown disposition type is handled as `attachment`
+    pub fn is_attachment(&self) -> bool { !self.is_inline() && !self.is_form_data() }
+    pub fn is_form_data(&self) -> bool { self.disposition.eq_ignore_ascii_case("form-data") }
+
+    pub fn params(&self) -> Params<'c> { Params::new(self.params) }
+    /// look up a parameter by its (case-insensitive) name, quoted values are unquoted but keep their escapes
+    pub fn param(&self, name: &str) -> Option<&'c str> { syntax::find_param(self.params, name) }
+
+    /// the decoded `name` of a form field, with `"`, `%` and control characters percent-decoded like in
+    /// [`filename`](Self::filename)
+    pub fn name<'a>(&self, buf: &'a mut [u8]) -> Result<Option<&'a str>, ContentDispositionError>
+    where
+        'c: 'a,
+    {
+        match self.param("name") {
+            Some(name) => decode_field(name, buf).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// the decoded file name, `filename*` is preferred over `filename` unless its charset isn't supported
+    ///
+    /// a `filename*` in `UTF-8` or `ISO-8859-1` is decoded into `buf`, as is a `filename` with `"` or control
+    /// characters percent-encoded the way browsers send them, and `%` the way [`ContentDispositionBuilder`] does.
+    /// backslashes are kept, browsers don't escape them. the name has to be sanitized before it is used as a path
+    pub fn filename<'a>(&self, buf: &'a mut [u8]) -> Result<Option<&'a str>, ContentDispositionError>
+    where
+        'c: 'a,
+    {
+        if let Some(ext_value) = self.param("filename*").and_then(ExtValue::parse).filter(ExtValue::is_supported) {
+            return ext_value.decode(buf).map(Some);
+        }
+        match self.param("filename") {
+            Some(filename) => decode_field(filename, buf).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// `ext-value` from rfc 8187 section 3.2.1
+#[derive(Debug, Clone, Copy)]
+struct ExtValue<'v> {
+    charset: &'v str,
+    value: &'v str,
+}
+
+impl<'v> ExtValue<'v> {
+    fn parse(input: &'v str) -> Option<Self> {
+        let (charset, rest) = input.split_once('\'')?;
+        let (language, value) = rest.split_once('\'')?;
+        let valid_charset = !charset.is_empty()
+            && charset.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&+-^_`{}~".contains(&b));
+        let valid_language = language.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        let valid_value = value.bytes().all(|b| is_attr_char(b) || b == b'%');
+        (valid_charset && valid_language && valid_value).then_some(Self { charset, value })
+    }
+
+    fn is_supported(&self) -> bool {
+        self.charset.eq_ignore_ascii_case("utf-8") || self.charset.eq_ignore_ascii_case("iso-8859-1")
+    }
+
+    fn decode<'b>(&self, buf: &'b mut [u8]) -> Result<&'b str, ContentDispositionError> {
+        let latin1 = self.charset.eq_ignore_ascii_case("iso-8859-1");
+        let mut len = 0;
+        for byte in percent_decoded(self.value, false) {
+            // iso-8859-1 maps its bytes to the first 256 code points
+            let mut encoded = [0; 4];
+            let encoded = match latin1 {
+                true => char::from(byte).encode_utf8(&mut encoded).as_bytes(),
+                false => core::slice::from_ref(&byte),
+            };
+            buf.get_mut(len..len + encoded.len()).ok_or(ContentDispositionError::BufferTooSmall)?.copy_from_slice(encoded);
+            len += encoded.len();
+        }
+        core::str::from_utf8(&buf[..len]).map_err(|_| ContentDispositionError::InvalidParameter)
+    }
+}
+
+// `attr-char` from rfc 8187 section 3.2.1
+fn is_attr_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte)
+}
+
+/// builds a `Content-Disposition` header
+///
+/// `name` and `filename` are quoted the way browsers send them, with `"` and control characters percent-encoded
+/// instead of backslash escaped, and `%` encoded as well so they decode to what was given. a file name with characters other than printable ascii is sent as an ascii
+/// `filename` fallback, in which those characters are replaced by `_`, along with a `filename*` in `UTF-8`, except
+/// for `form-data` where it is sent as it is (rfc 7578 section 4.2)
+#[derive(Debug, Clone, Copy)]
+pub struct ContentDispositionBuilder<'a> {
+    disposition: &'a str,
+    name: Option<&'a str>,
+    filename: Option<&'a str>,
+}
+
+impl<'a> ContentDispositionBuilder<'a> {
+    pub fn new(disposition: &'a str) -> Result<Self, ContentDispositionError> {
+        if !syntax::is_token(disposition) { return Err(ContentDispositionError::InvalidType); }
+        Ok(Self::from_token(disposition))
+    }
+
+    // `disposition` is known to be a token
+    fn from_token(disposition: &'a str) -> Self {
+        Self { disposition, name: None, filename: None }
+    }
+
+    /// `inline`, to display the content in the browser
+    pub fn inline() -> Self { Self::from_token("inline") }
+
+    /// `attachment; filename="..."`, to download the content under `filename`
+    pub fn attachment(filename: &'a str) -> Self {
+        Self { filename: Some(filename), ..Self::from_token("attachment") }
+    }
+
+    /// `form-data; name="..."`, for a part of a `multipart/form-data` body
+    pub fn form_data(name: &'a str) -> Self {
+        Self { name: Some(name), ..Self::from_token("form-data") }
+    }
+
+    pub fn set_name(&mut self, name: &'a str) { self.name = Some(name); }
+    pub fn set_filename(&mut self, filename: &'a str) { self.filename = Some(filename); }
+}
+
+impl Display for ContentDispositionBuilder<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.disposition)?;
+        if let Some(name) = self.name { write!(f, "; name={}", QuotedField { value: name, ascii: false })?; }
+        let Some(filename) = self.filename else { return Ok(()) };
+        let form_data = self.disposition.eq_ignore_ascii_case("form-data");
+        write!(f, "; filename={}", QuotedField { value: filename, ascii: !form_data })?;
+        if !form_data && !filename.is_ascii() { write!(f, "; filename*=UTF-8''{}", ExtEncoded(filename))?; }
+        Ok(())
+    }
+}
+
+// a quoted `name` or `filename` with `"`, `%` and control characters percent-encoded, and everything but ascii
+// replaced by `_` if `ascii` is set
+struct QuotedField<'a> {
+    value: &'a str,
+    ascii: bool,
+}
+
+impl Display for QuotedField<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.value.chars() {
+            match c {
+                '"' | '%' | '\0'..='\x1f' | '\x7f' => write!(f, "%{:02X}", c as u32)?,
+                c if self.ascii && !c.is_ascii() => f.write_char('_')?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+// decode the percent escapes `QuotedField` writes into `buf`, only if there are any. other escapes are kept, as
+// browsers send a `%` as it is
+fn decode_field<'a>(value: &'a str, buf: &'a mut [u8]) -> Result<&'a str, ContentDispositionError> {
+    if !value.contains('%') { return Ok(value); }
+    let value = value.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+    while i < value.len() {
+        let escaped = match value[i..] {
+            [b'%', high, low, ..] => core::str::from_utf8(&[high, low]).ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .filter(|&byte| byte == b'"' || byte == b'%' || byte.is_ascii_control()),
+            _ => None,
+        };
+        *buf.get_mut(len).ok_or(ContentDispositionError::BufferTooSmall)? = escaped.unwrap_or(value[i]);
+        len += 1;
+        i += if escaped.is_some() { 3 } else { 1 };
+    }
+    // only ascii escapes are decoded, so the rest is still utf-8
+    core::str::from_utf8(&buf[..len]).map_err(|_| ContentDispositionError::InvalidParameter)
+}
+
+// the percent-encoded value of an `ext-value`
+struct ExtEncoded<'a>(&'a str);
+
+impl Display for ExtEncoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.bytes() {
+            match is_attr_char(byte) {
+                true => f.write_char(byte as char)?,
+                false => write!(f, "%{byte:02X}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+    use super::*;
+
+    #[test]
+    fn filename_star_is_preferred() {
+        let mut buf = [0; 32];
+        let disposition = ContentDisposition::parse("attachment; filename=\"EURO rates\"; filename*=utf-8''%e2%82%ac%20rates").unwrap();
+        assert_eq!(disposition.filename(&mut buf), Ok(Some("€ rates")));
+        let disposition = ContentDisposition::parse("inline; filename*=iso-8859-1'en'%A3%20rates").unwrap();
+        assert_eq!(disposition.filename(&mut buf), Ok(Some("£ rates")));
+        // an unsupported charset falls back to `filename`
+        let disposition = ContentDisposition::parse("attachment; filename=rates.txt; filename*=koi8-r''%C1").unwrap();
+        assert_eq!(disposition.filename(&mut buf), Ok(Some("rates.txt")));
+    }
+
+    #[test]
+    fn filename_is_decoded_as_browsers_send_it() {
+        let mut buf = [0; 32];
+        let disposition = ContentDisposition::parse(r#"form-data; name="f"; filename="C:\dir\a%22b%0A100%.txt""#).unwrap();
+        assert_eq!(disposition.filename(&mut buf), Ok(Some("C:\\dir\\a\"b\n100%.txt")));
+    }
+
+    #[test]
+    fn builder_escapes_quotes_and_control_characters() {
+        let header = ContentDispositionBuilder::form_data("a\r\nX-Evil: 1\"").to_string();
+        assert_eq!(header, "form-data; name=\"a%0D%0AX-Evil: 1%22\"");
+        assert!(ContentDispositionBuilder::new("attachment\r\nX-Evil: 1").is_err());
+        assert_eq!(ContentDispositionBuilder::new("attachment").unwrap().to_string(), "attachment");
+    }
+
+    #[test]
+    fn builder_round_trips() {
+        let mut buf = [0; 64];
+        let header = ContentDispositionBuilder::attachment("100%22.txt").to_string();
+        assert_eq!(header, "attachment; filename=\"100%2522.txt\"");
+        assert_eq!(ContentDisposition::parse(&header).unwrap().filename(&mut buf), Ok(Some("100%22.txt")));
+
+        let header = ContentDispositionBuilder::form_data("a\"b%0A").to_string();
+        assert_eq!(header, "form-data; name=\"a%22b%250A\"");
+        assert_eq!(ContentDisposition::parse(&header).unwrap().name(&mut buf), Ok(Some("a\"b%0A")));
+
+        let header = ContentDispositionBuilder::attachment("€ \"rates\"\n.pdf").to_string();
+        assert_eq!(header, "attachment; filename=\"_ %22rates%22%0A.pdf\"; filename*=UTF-8''%E2%82%AC%20%22rates%22%0A.pdf");
+        assert_eq!(ContentDisposition::parse(&header).unwrap().filename(&mut buf), Ok(Some("€ \"rates\"\n.pdf")));
+
+        let mut builder = ContentDispositionBuilder::form_data("upload");
+        builder.set_filename("C:\\€\\\"x\".txt");
+        let header = builder.to_string();
+        assert_eq!(header, "form-data; name=\"upload\"; filename=\"C:\\€\\%22x%22.txt\"");
+        assert_eq!(ContentDisposition::parse(&header).unwrap().filename(&mut buf), Ok(Some("C:\\€\\\"x\".txt")));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::syntax::{ self, Split };
+
+#[cfg(feature = "alloc")]
+mod jar;
+#[cfg(feature = "alloc")]
+pub use jar::{ CookieJar, CookieJarError };
+
+/// a `name=value` pair from a `Cookie` request header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CookiePair<'c> {
+    name: &'c str,
+    value: &'c str,
+}
+
+impl<'c> CookiePair<'c> {
+    pub fn name(&self) -> &'c str { self.name }
+    pub fn value(&self) -> &'c str { self.value }
+}
+
+/// the pairs of a `Cookie` header value (`a=1; b=2`), pairs without a `=` are skipped
+pub struct Cookies<'c>(Split<'c>);
+
+impl<'c> Iterator for Cookies<'c> {
+    type Item = CookiePair<'c>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            Some(CookiePair { name: syntax::trim_ows(name), value: syntax::unquote(syntax::trim_ows(value)) })
+        })
+    }
+}
+
+pub fn parse_cookie(input: &str) -> Cookies<'_> {
+    Cookies(Split::new(input, b';'))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SetCookieError {
+    /// both the name and the value are empty
+    EmptyCookie,
+    /// the name, value or an attribute contains `;` or control characters, or the name contains `=`
+    InvalidCookie,
+    /// a `__Secure-` cookie without the `Secure` attribute
+    InvalidSecurePrefix,
+    /// a `__Host-` cookie that isn't `Secure`, has a `Domain` or has a `Path` other than `/`
+    InvalidHostPrefix,
+}
+
+/// a `Set-Cookie` response header (rfc 6265bis section 4.1)
+///
+/// unknown or malformed attributes are ignored, as is required of user agents
+#[derive(Debug, Clone, Default)]
+pub struct SetCookie<'c> {
+    name: &'c str,
+    value: &'c str,
+    expires: Option<&'c str>,
+    max_age: Option<i64>,
+    domain: Option<&'c str>,
+    path: Option<&'c str>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+    partitioned: bool,
+}
+
+impl<'c> SetCookie<'c> {
+    /// a nameless cookie (an empty `name`) is sent as just its value
+    pub fn new(name: &'c str, value: &'c str) -> Result<Self, SetCookieError> {
+        if name.is_empty() && value.is_empty() { return Err(SetCookieError::EmptyCookie); }
+        if name.contains('=') || !is_cookie_text(name) || !is_cookie_text(value) {
+            return Err(SetCookieError::InvalidCookie);
+        }
+        Ok(Self { name, value, ..Self::default() })
+    }
+
+    pub fn parse(input: &'c str) -> Result<Self, SetCookieError> {
+        let (pair, attributes) = input.split_once(';').unwrap_or((input, ""));
+        let (name, value) = match pair.split_once('=') {
+            Some((name, value)) => (syntax::trim_ows(name), syntax::trim_ows(value)),
+            None => ("", syntax::trim_ows(pair)),
+        };
+        let mut cookie = Self::new(name, value)?;
+        for attribute in attributes.split(';') {
+            let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let (name, value) = (syntax::trim_ows(name), syntax::trim_ows(value));
+            match name {
+                _ if !is_cookie_text(value) => {}
+                _ if name.eq_ignore_ascii_case("Expires") => cookie.expires = Some(value),
+                _ if name.eq_ignore_ascii_case("Max-Age") => cookie.max_age = parse_max_age(value).or(cookie.max_age),
+                _ if name.eq_ignore_ascii_case("Domain") && !value.is_empty() => {
+                    cookie.domain = Some(value.strip_prefix('.').unwrap_or(value));
+                }
+                // a path that isn't absolute falls back to the default path
+                _ if name.eq_ignore_ascii_case("Path") => cookie.path = value.starts_with('/').then_some(value),
+                _ if name.eq_ignore_ascii_case("Secure") => cookie.secure = true,
+                _ if name.eq_ignore_ascii_case("HttpOnly") => cookie.http_only = true,
+                _ if name.eq_ignore_ascii_case("Partitioned") => cookie.partitioned = true,
+                _ if name.eq_ignore_ascii_case("SameSite") => cookie.same_site = match value {
+                    _ if value.eq_ignore_ascii_case("Strict") => Some(SameSite::Strict),
+                    _ if value.eq_ignore_ascii_case("Lax") => Some(SameSite::Lax),
+                    _ if value.eq_ignore_ascii_case("None") => Some(SameSite::None),
+                    _ => cookie.same_site,
+                },
+                _ => {}
+            }
+        }
+
+        cookie.validate_prefix()?;
+        Ok(cookie)
+    }
+
+    /// check the `__Secure-` and `__Host-` name prefix requirements (rfc 6265bis section 4.1.3)
+    pub fn validate_prefix(&self) -> Result<(), SetCookieError> {
+        let prefixed = |prefix: &str| self.name.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix));
+        if prefixed("__Secure-") && !self.secure {
+            return Err(SetCookieError::InvalidSecurePrefix);
+        }
+        if prefixed("__Host-") && (!self.secure || self.domain.is_some() || self.path != Some("/")) {
+            return Err(SetCookieError::InvalidHostPrefix);
+        }
+        Ok(())
+    }
+
+    pub fn name(&self) -> &'c str { self.name }
+    pub fn value(&self) -> &'c str { self.value }
+    /// the raw `Expires` date, left unparsed
+    pub fn expires(&self) -> Option<&'c str> { self.expires }
+    pub fn max_age(&self) -> Option<i64> { self.max_age }
+    /// the `Domain` attribute, without a leading `.`
+    pub fn domain(&self) -> Option<&'c str> { self.domain }
+    pub fn path(&self) -> Option<&'c str> { self.path }
+    pub fn secure(&self) -> bool { self.secure }
+    pub fn http_only(&self) -> bool { self.http_only }
+    pub fn same_site(&self) -> Option<SameSite> { self.same_site }
+    pub fn partitioned(&self) -> bool { self.partitioned }
+
+    pub fn set_expires(&mut self, expires: &'c str) -> Result<(), SetCookieError> {
+        self.expires = Some(attribute_value(expires)?);
+        Ok(())
+    }
+    pub fn set_max_age(&mut self, seconds: i64) { self.max_age = Some(seconds); }
+    pub fn set_domain(&mut self, domain: &'c str) -> Result<(), SetCookieError> {
+        self.domain = Some(attribute_value(domain)?);
+        Ok(())
+    }
+    pub fn set_path(&mut self, path: &'c str) -> Result<(), SetCookieError> {
+        self.path = Some(attribute_value(path)?);
+        Ok(())
+    }
+    pub fn set_secure(&mut self, secure: bool) { self.secure = secure; }
+    pub fn set_http_only(&mut self, http_only: bool) { self.http_only = http_only; }
+    pub fn set_same_site(&mut self, same_site: SameSite) { self.same_site = Some(same_site); }
+    pub fn set_partitioned(&mut self, partitioned: bool) { self.partitioned = partitioned; }
+}
+
+// text that can't end the pair or an attribute early or break the header line
+fn is_cookie_text(input: &str) -> bool {
+    !input.bytes().any(|b| b == b';' || (b.is_ascii_control() && b != b'\t'))
+}
+
+fn attribute_value(value: &str) -> Result<&str, SetCookieError> {
+    is_cookie_text(value).then_some(value).ok_or(SetCookieError::InvalidCookie)
+}
+
+// an optional `-` followed by digits, saturating on overflow
+fn parse_max_age(input: &str) -> Option<i64> {
+    let (negative, digits) = match input.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, input),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) { return None; }
+    let seconds = digits.bytes().fold(0i64, |acc, b| acc.saturating_mul(10).saturating_add((b - b'0') as i64));
+    Some(if negative { -seconds } else { seconds })
+}
+
+impl<'c> TryFrom<&'c str> for SetCookie<'c> {
+    type Error = SetCookieError;
+    fn try_from(value: &'c str) -> Result<Self, Self::Error> { Self::parse(value) }
+}
+
+impl core::fmt::Display for SetCookie<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // a nameless cookie is sent as just its value
+        match self.name {
+            "" => f.write_str(self.value)?,
+            name => write!(f, "{name}={}", self.value)?,
+        }
+        if let Some(expires) = self.expires { write!(f, "; Expires={expires}")?; }
+        if let Some(max_age) = self.max_age { write!(f, "; Max-Age={max_age}")?; }
+        if let Some(domain) = self.domain { write!(f, "; Domain={domain}")?; }
+        if let Some(path) = self.path { write!(f, "; Path={path}")?; }
+        if self.secure { f.write_str("; Secure")?; }
+        if self.http_only { f.write_str("; HttpOnly")?; }
+        if let Some(same_site) = self.same_site { write!(f, "; SameSite={}", same_site.as_str())?; }
+        if self.partitioned { f.write_str("; Partitioned")?; }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::ToString, vec::Vec };
+    use super::*;
+
+    #[test]
+    fn cookie_pairs() {
+        let pairs: Vec<_> = parse_cookie("SID=31d4d96e407aad42; lang=\"en-US\"; junk;  =x ; e=").map(|pair| (pair.name(), pair.value())).collect();
+        assert_eq!(pairs, [("SID", "31d4d96e407aad42"), ("lang", "en-US"), ("", "x"), ("e", "")]);
+    }
+
+    #[test]
+    fn set_cookie_attributes() {
+        let cookie = SetCookie::parse("id=a3fWa; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=-5; max-age=x; \
+            Domain=.example.com; Path=docs; Secure; HttpOnly; SameSite=lax; SameSite=bogus; Partitioned; Unknown").unwrap();
+        assert_eq!((cookie.name(), cookie.value()), ("id", "a3fWa"));
+        assert_eq!(cookie.expires(), Some("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!((cookie.max_age(), cookie.domain(), cookie.path()), (Some(-5), Some("example.com"), None));
+        assert!(cookie.secure() && cookie.http_only() && cookie.partitioned());
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(SetCookie::parse("a=b; Max-Age=99999999999999999999").unwrap().max_age(), Some(i64::MAX));
+
+        let nameless = SetCookie::parse("value").unwrap();
+        assert_eq!((nameless.name(), nameless.value(), nameless.to_string().as_str()), ("", "value", "value"));
+        assert!(matches!(SetCookie::parse(" = ; Path=/"), Err(SetCookieError::EmptyCookie)));
+        assert!(matches!(SetCookie::parse("a=b\x01"), Err(SetCookieError::InvalidCookie)));
+        // an attribute with control characters is ignored like any other malformed one
+        assert_eq!(SetCookie::parse("a=b; Path=/\x01; Secure").unwrap().path(), None);
+    }
+
+    #[test]
+    fn name_prefixes() {
+        assert!(matches!(SetCookie::parse("__Secure-id=1"), Err(SetCookieError::InvalidSecurePrefix)));
+        assert!(SetCookie::parse("__secure-id=1; Secure").is_ok());
+        for input in ["__Host-id=1; Path=/", "__Host-id=1; Secure", "__Host-id=1; Secure; Path=/; Domain=example.com"] {
+            assert!(matches!(SetCookie::parse(input), Err(SetCookieError::InvalidHostPrefix)), "{input}");
+        }
+        assert!(SetCookie::parse("__HOST-id=1; Secure; Path=/").is_ok());
+    }
+
+    #[test]
+    fn set_cookie_serialization() {
+        let mut cookie = SetCookie::new("id", "a3fWa").unwrap();
+        cookie.set_expires("Wed, 21 Oct 2015 07:28:00 GMT").unwrap();
+        cookie.set_max_age(3600);
+        cookie.set_domain("example.com").unwrap();
+        cookie.set_path("/docs").unwrap();
+        cookie.set_secure(true);
+        cookie.set_http_only(true);
+        cookie.set_same_site(SameSite::Strict);
+        cookie.set_partitioned(true);
+        let header = cookie.to_string();
+        assert_eq!(header, "id=a3fWa; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=3600; Domain=example.com; Path=/docs; \
+            Secure; HttpOnly; SameSite=Strict; Partitioned");
+        assert_eq!(SetCookie::parse(&header).unwrap().to_string(), header);
+    }
+
+    #[test]
+    fn injection_is_rejected() {
+        for (name, value) in [("a;b", "1"), ("a=b", "1"), ("a", "1; Domain=evil"), ("a", "1\r\nX-Evil: 1"), ("", "")] {
+            assert!(SetCookie::new(name, value).is_err(), "{name:?}={value:?}");
+        }
+        let mut cookie = SetCookie::new("a", "1").unwrap();
+        assert!(matches!(cookie.set_path("/; Domain=evil"), Err(SetCookieError::InvalidCookie)));
+        assert!(matches!(cookie.set_domain("example.com\r\nX-Evil: 1"), Err(SetCookieError::InvalidCookie)));
+        assert!(matches!(cookie.set_expires("never;"), Err(SetCookieError::InvalidCookie)));
+        assert_eq!(cookie.to_string(), "a=1");
+    }
+}
@@ -0,0 +1,383 @@
+use alloc::string::{ String, ToString };
+use alloc::vec::Vec;
+
+use crate::{ Request, Response };
+use crate::date::{ days_from_civil, days_in_month };
+use crate::header::{ self, StandardHeaderName };
+use crate::syntax;
+use super::SetCookie;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CookieJarError {
+    /// there is no unused slot in the request's header buffer for the `Cookie` header
+    HeaderBufferFull,
+}
+
+// the parts of a request's url that cookie storage and retrieval depend on
+#[derive(Debug, Clone, Copy)]
+struct RequestUrl<'u> {
+    host: &'u str,
+    path: &'u str,
+    secure: bool,
+}
+
+impl<'u> RequestUrl<'u> {
+    // an origin-form target is combined with the `Host` header, `None` if there is no `Host` to combine it with
+    fn new<B>(request: &Request<'u, '_, B>, scheme: &str) -> Option<Self> {
+        let target = request.target();
+        let (scheme, authority, path) = match target.split_once("://") {
+            Some((scheme, rest)) => {
+                let (authority, path) = rest.find('/').map_or((rest, "/"), |idx| rest.split_at(idx));
+                (scheme, authority.rsplit_once('@').map_or(authority, |(_, host)| host), path)
+            }
+            None => (scheme, syntax::trim_ows(request.header(StandardHeaderName::Host)?.value()), target),
+        };
+        let host = match authority.find(']') {
+            Some(end) if authority.starts_with('[') => &authority[..=end],
+            _ => authority.split_once(':').map_or(authority, |(host, _)| host),
+        };
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let secure = scheme.eq_ignore_ascii_case("https") || scheme.eq_ignore_ascii_case("wss");
+        Some(Self { host, path, secure })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    path: String,
+    /// unix seconds, `None` for session cookies
+    expiry: Option<u64>,
+    creation: u64,
+    host_only: bool,
+    secure: bool,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= now)
+    }
+}
+
+/// a cookie store following the storage model of rfc 6265bis section 5.7
+///
+/// requests are identified by their target and `Host`, with the `scheme` they are sent over (`http`, `https`, `ws`
+/// or `wss`). the jar has no clock of its own, every operation takes the current time in unix seconds
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: Vec<StoredCookie>,
+    next_creation: u64,
+    is_public_suffix: Option<fn(&str) -> bool>,
+}
+
+impl CookieJar {
+    /// a jar that only knows bare top-level domains like `com` as public suffixes
+    pub fn new() -> Self { Self::default() }
+
+    /// a jar that also rejects cookies for the domains `is_public_suffix` returns `true` for, like `co.uk`, e.g.
+    /// from the public suffix list
+    pub fn with_public_suffixes(is_public_suffix: fn(&str) -> bool) -> Self {
+        Self { is_public_suffix: Some(is_public_suffix), ..Self::default() }
+    }
+
+    pub fn len(&self) -> usize { self.cookies.len() }
+    pub fn is_empty(&self) -> bool { self.cookies.is_empty() }
+    pub fn clear(&mut self) { self.cookies.clear(); }
+
+    /// store every valid `Set-Cookie` header of the response to `request`
+    pub fn store_response<B, C>(
+        &mut self,
+        request: &Request<'_, '_, B>,
+        response: &Response<'_, '_, C>,
+        scheme: &str,
+        now: u64,
+    ) {
+        for header in response.headers.iter().filter(|header| header.is(StandardHeaderName::SetCookie)) {
+            if let Ok(cookie) = SetCookie::parse(header.value()) {
+                self.store(&cookie, request, scheme, now);
+            }
+        }
+    }
+
+    /// store a single cookie set by the response to `request`, returns whether the cookie was accepted
+    ///
+    /// a cookie that is already expired is not stored, but still removes any cookie it replaces. a cookie with a
+    /// public suffix as its `Domain` is only accepted from that very host, and only sent back to it
+    pub fn store<B>(&mut self, cookie: &SetCookie<'_>, request: &Request<'_, '_, B>, scheme: &str, now: u64) -> bool {
+        let Some(url) = RequestUrl::new(request, scheme) else { return false };
+        let host = url.host.to_ascii_lowercase();
+        if cookie.secure() && !url.secure { return false; }
+        if cookie.validate_prefix().is_err() { return false; }
+        if is_prefixed(cookie.name()) && !url.secure { return false; }
+
+        let (domain, host_only) = match cookie.domain() {
+            Some(domain) => {
+                let domain = domain.to_ascii_lowercase();
+                if self.is_public_suffix(&domain) {
+                    if domain != host { return false; }
+                    (host, true)
+                } else {
+                    if !domain_match(&host, &domain) { return false; }
+                    (domain, false)
+                }
+            }
+            None => (host, true),
+        };
+        let path = match cookie.path() {
+            Some(path) => path.to_string(),
+            None => default_path(url.path).to_string(),
+        };
+
+        // an insecure origin can't shadow a secure cookie
+        if !url.secure && self.cookies.iter().any(|stored| {
+            stored.secure && stored.name == cookie.name()
+                && (domain_match(&domain, &stored.domain) || domain_match(&stored.domain, &domain))
+                && path_match(&path, &stored.path)
+        }) {
+            return false;
+        }
+
+        let expiry = match (cookie.max_age(), cookie.expires().and_then(parse_cookie_date)) {
+            (Some(max_age), _) if max_age <= 0 => Some(0),
+            (Some(max_age), _) => Some(now.saturating_add(max_age as u64)),
+            (None, Some(expires)) => Some(expires),
+            (None, None) => None,
+        };
+
+        let mut creation = self.next_creation;
+        if let Some(idx) = self.cookies.iter().position(|stored| {
+            stored.name == cookie.name() && stored.domain == domain && stored.path == path
+        }) {
+            creation = self.cookies.remove(idx).creation;
+        } else {
+            self.next_creation += 1;
+        }
+
+        if expiry.is_some_and(|expiry| expiry <= now) { return false; }
+        self.cookies.push(StoredCookie {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain,
+            path,
+            expiry,
+            creation,
+            host_only,
+            secure: cookie.secure(),
+        });
+        true
+    }
+
+    /// drop every cookie that has expired by `now`
+    pub fn remove_expired(&mut self, now: u64) {
+        self.cookies.retain(|cookie| !cookie.is_expired(now));
+    }
+
+    /// add a `Cookie` header with the cookies that apply to `request` to the first unused (empty) slot of its
+    /// header buffer, its value is written to `buf`. nothing is added if no cookies apply
+    ///
+    /// cookies with longer paths come first, then cookies that were created earlier
+    pub fn add_cookie_header<'r, B>(
+        &self,
+        request: &mut Request<'r, '_, B>,
+        scheme: &str,
+        buf: &'r mut String,
+        now: u64,
+    ) -> Result<(), CookieJarError> {
+        let Some(url) = RequestUrl::new(request, scheme) else { return Ok(()) };
+        let host = url.host.to_ascii_lowercase();
+        let mut matching: Vec<&StoredCookie> = self.cookies.iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .filter(|cookie| if cookie.host_only { cookie.domain == host } else { domain_match(&host, &cookie.domain) })
+            .filter(|cookie| path_match(url.path, &cookie.path))
+            .filter(|cookie| url.secure || !cookie.secure)
+            .collect();
+        if matching.is_empty() { return Ok(()); }
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()).then(a.creation.cmp(&b.creation)));
+
+        buf.clear();
+        for cookie in matching {
+            if !buf.is_empty() { buf.push_str("; "); }
+            if !cookie.name.is_empty() {
+                buf.push_str(&cookie.name);
+                buf.push('=');
+            }
+            buf.push_str(&cookie.value);
+        }
+        let value: &'r String = buf;
+        match header::push_header(request.headers, StandardHeaderName::Cookie, value) {
+            true => Ok(()),
+            false => Err(CookieJarError::HeaderBufferFull),
+        }
+    }
+
+    // bare top-level domains are always public suffixes
+    fn is_public_suffix(&self, domain: &str) -> bool {
+        !domain.contains('.') || self.is_public_suffix.is_some_and(|is_public_suffix| is_public_suffix(domain))
+    }
+}
+
+fn is_prefixed(name: &str) -> bool {
+    ["__Secure-", "__Host-"].iter().any(|prefix| name.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix)))
+}
+
+/// rfc 6265bis section 5.1.3, both arguments are expected to be lowercase
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain { return true; }
+    let is_ip = host.parse::<core::net::IpAddr>().is_ok() || host.starts_with('[');
+    !is_ip && host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// rfc 6265bis section 5.1.4
+fn default_path(path: &str) -> &str {
+    if !path.starts_with('/') { return "/"; }
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(idx) => &path[..idx],
+    }
+}
+
+/// rfc 6265bis section 5.1.4
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    let request_path = if request_path.is_empty() { "/" } else { request_path };
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// the lenient cookie date algorithm of rfc 6265bis section 5.1.1, returning unix seconds
+fn parse_cookie_date(input: &str) -> Option<u64> {
+    let is_delimiter = |c: char| matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~');
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+
+    for token in input.split(is_delimiter).filter(|token| !token.is_empty()) {
+        if time.is_none() {
+            if let Some(hms) = parse_time(token) { time = Some(hms); continue; }
+        }
+        if day.is_none() {
+            if let Some(value) = leading_digits(token, 1, 2) { day = Some(value); continue; }
+        }
+        if month.is_none() {
+            if let Some(value) = parse_month(token) { month = Some(value); continue; }
+        }
+        if year.is_none() {
+            if let Some(value) = leading_digits(token, 2, 4) { year = Some(value); continue; }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, mut year) = (day?, month?, year?);
+    if (70..=99).contains(&year) { year += 1900; }
+    if year <= 69 { year += 2000; }
+    if year < 1601 || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 59 { return None; }
+    if day > days_in_month(year, month) { return None; }
+
+    let seconds = days_from_civil(year, month, day) * 86400
+        + (hour * 3600 + minute * 60 + second) as i64;
+    Some(seconds.max(0) as u64)
+}
+
+// `1*2DIGIT ":" 1*2DIGIT ":" 1*2DIGIT`, optionally followed by non-digits
+fn parse_time(token: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = parts.next().and_then(|part| exact_digits(part, 1, 2))?;
+    let minute = parts.next().and_then(|part| exact_digits(part, 1, 2))?;
+    let second = parts.next().and_then(|part| leading_digits(part, 1, 2))?;
+    Some((hour, minute, second))
+}
+
+fn exact_digits(token: &str, min: usize, max: usize) -> Option<u32> {
+    (token.len() >= min && token.len() <= max && token.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| token.parse().ok())?
+}
+
+// `min*maxDIGIT`, optionally followed by non-digits
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u32> {
+    let digits = token.bytes().take_while(|b| b.is_ascii_digit()).count();
+    exact_digits(&token[..digits], min, max)
+}
+
+fn parse_month(token: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+    let prefix = token.get(..3)?;
+    MONTHS.iter().position(|month| month.eq_ignore_ascii_case(prefix)).map(|idx| idx as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use super::*;
+    use crate::{ parse_request, parse_response };
+    use crate::header::EMPTY_HEADER;
+
+    fn cookies<'r, B>(jar: &CookieJar, request: &mut Request<'r, '_, B>, scheme: &str, buf: &'r mut String, now: u64) -> Option<&'r str> {
+        jar.add_cookie_header(request, scheme, buf, now).unwrap();
+        request.header(StandardHeaderName::Cookie).map(|header| header.value())
+    }
+
+    #[test]
+    fn cookies_are_sent_back_to_matching_requests() {
+        let mut headers = [EMPTY_HEADER; 4];
+        let input = "HTTP/1.1 200 OK\r\nSet-Cookie: a=1; Path=/; Domain=example.com\r\n\
+            Set-Cookie: b=2; Expires=Wed, 09 Jun 2021 10:18:14 GMT\r\nSet-Cookie: c=3; Secure\r\nSet-Cookie: d=4; Max-Age=100\r\n\r\n";
+        let response = parse_response(input, &mut headers).unwrap();
+        let mut request_headers = [EMPTY_HEADER; 2];
+        let request = parse_request("GET /docs/index.html?x HTTP/1.1\r\nHost: www.Example.com:443\r\n\r\n", &mut request_headers).unwrap();
+        let mut jar = CookieJar::new();
+        // 2021-06-09 10:18:14
+        let now = 1_623_233_894;
+        jar.store_response(&request, &response, "https", now - 10);
+        assert_eq!(jar.len(), 4);
+
+        let mut buf = String::new();
+
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut next = parse_request("GET /docs/ HTTP/1.1\r\nHost: www.example.com\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(cookies(&jar, &mut next, "https", &mut buf, now - 5), Some("b=2; c=3; d=4; a=1"));
+        let mut buf = String::new();
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut next = parse_request("GET /docs/ HTTP/1.1\r\nHost: www.example.com\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(cookies(&jar, &mut next, "https", &mut buf, now), Some("c=3; d=4; a=1"));
+        let mut buf = String::new();
+        let mut headers = [EMPTY_HEADER; 1];
+        let mut next = parse_request("GET http://sub.example.com/ HTTP/1.1\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(cookies(&jar, &mut next, "http", &mut buf, now), Some("a=1"));
+        let mut buf = String::new();
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut next = parse_request("GET / HTTP/1.1\r\nHost: other.com\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(cookies(&jar, &mut next, "http", &mut buf, now), None);
+
+        // the header buffer is full
+        let mut headers = [EMPTY_HEADER; 1];
+        let mut next = parse_request("GET / HTTP/1.1\r\nHost: www.example.com\r\n\r\n", &mut headers).unwrap();
+        let result = jar.add_cookie_header(&mut next, "https", &mut String::new(), now);
+        assert_eq!(result, Err(CookieJarError::HeaderBufferFull));
+    }
+
+    #[test]
+    fn public_suffixes_are_rejected() {
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("GET / HTTP/1.1\r\nHost: www.example.co.uk\r\n\r\n", &mut headers).unwrap();
+        let mut jar = CookieJar::with_public_suffixes(|domain| domain == "co.uk");
+        for cookie in ["a=1; Domain=uk", "a=1; Domain=co.uk", "a=1; Domain=other.co.uk"] {
+            assert!(!jar.store(&SetCookie::parse(cookie).unwrap(), &request, "https", 0), "{cookie}");
+        }
+        assert!(jar.store(&SetCookie::parse("a=1; Domain=example.co.uk").unwrap(), &request, "https", 0));
+
+        let cookie = SetCookie::parse("a=1; Domain=com").unwrap();
+        let mut jar = CookieJar::new();
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("GET / HTTP/1.1\r\nHost: example.com\r\n\r\n", &mut headers).unwrap();
+        assert!(!jar.store(&cookie, &request, "https", 0));
+        // the host itself can still set it, but only for itself
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("GET / HTTP/1.1\r\nHost: com\r\n\r\n", &mut headers).unwrap();
+        assert!(jar.store(&cookie, &request, "https", 0));
+        let mut buf = String::new();
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut next = parse_request("GET / HTTP/1.1\r\nHost: example.com\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(cookies(&jar, &mut next, "https", &mut buf, 0), None);
+    }
+}
@@ -0,0 +1,233 @@
+/// a point in time as sent in `Date`, `Expires`, `Last-Modified`, ... (rfc 9110 section 5.6.7)
+///
+/// stored as whole seconds since the unix epoch, between 1970 and the end of 9999
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HttpDate(u64);
+
+#[derive(Debug)]
+pub struct InvalidHttpDate;
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const LONG_DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+/// 9999-12-31 23:59:59
+const MAX_SECONDS: u64 = 253_402_300_799;
+
+impl HttpDate {
+    pub const UNIX_EPOCH: HttpDate = HttpDate(0);
+
+    /// parse any of the three accepted formats, the preferred imf-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`)
+    /// and the obsolete rfc 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime (`Sun Nov  6 08:49:37 1994`)
+    ///
+    /// two digit rfc 850 years are read as 1970 through 2069
+    pub fn parse(input: &str) -> Result<Self, InvalidHttpDate> {
+        if !input.is_ascii() { return Err(InvalidHttpDate); }
+        let (weekday, rest) = match input.split_once(", ") {
+            Some((weekday, rest)) => (weekday, rest),
+            None => return parse_asctime(input),
+        };
+        let (day, month, year, time) = if DAYS.contains(&weekday) {
+            // `06 Nov 1994 08:49:37 GMT`
+            let mut parts = rest.strip_suffix(" GMT").ok_or(InvalidHttpDate)?.split(' ');
+            let day = parts.next().filter(|day| day.len() == 2).ok_or(InvalidHttpDate)?;
+            let (month, year, time) = (parts.next(), parts.next(), parts.next());
+            if parts.next().is_some() { return Err(InvalidHttpDate); }
+            let year = year.filter(|year| year.len() == 4).ok_or(InvalidHttpDate)?;
+            (day, month, digits(year)?, time)
+        } else if LONG_DAYS.contains(&weekday) {
+            // `06-Nov-94 08:49:37 GMT`
+            let (date, time) = rest.strip_suffix(" GMT").and_then(|rest| rest.split_once(' ')).ok_or(InvalidHttpDate)?;
+            let mut parts = date.split('-');
+            let day = parts.next().filter(|day| day.len() == 2).ok_or(InvalidHttpDate)?;
+            let (month, year) = (parts.next(), parts.next());
+            if parts.next().is_some() { return Err(InvalidHttpDate); }
+            let year = digits(year.filter(|year| year.len() == 2).ok_or(InvalidHttpDate)?)?;
+            (day, month, if year < 70 { 2000 + year } else { 1900 + year }, Some(time))
+        } else {
+            return Err(InvalidHttpDate);
+        };
+        from_parts(digits(day)?, month.ok_or(InvalidHttpDate)?, year, time.ok_or(InvalidHttpDate)?)
+    }
+
+    /// seconds past `9999-12-31 23:59:59` are clamped to it
+    pub fn from_unix(seconds: u64) -> Self { Self(seconds.min(MAX_SECONDS)) }
+    pub fn unix(&self) -> u64 { self.0 }
+
+    /// format as an imf-fixdate, which is always 29 bytes long
+    pub fn format<'b>(&self, buf: &'b mut [u8; 29]) -> &'b str {
+        let days = (self.0 / 86400) as i64;
+        let seconds = self.0 % 86400;
+        let (year, month, day) = civil_from_days(days);
+        // 1970-01-01 was a thursday
+        let weekday = ((days + 3) % 7) as usize;
+
+        buf[..3].copy_from_slice(DAYS[weekday].as_bytes());
+        buf[3..5].copy_from_slice(b", ");
+        write_digits(&mut buf[5..7], day);
+        buf[7] = b' ';
+        buf[8..11].copy_from_slice(MONTHS[month as usize - 1].as_bytes());
+        buf[11] = b' ';
+        write_digits(&mut buf[12..16], year);
+        buf[16] = b' ';
+        write_digits(&mut buf[17..19], (seconds / 3600) as u32);
+        buf[19] = b':';
+        write_digits(&mut buf[20..22], (seconds / 60 % 60) as u32);
+        buf[22] = b':';
+        write_digits(&mut buf[23..25], (seconds % 60) as u32);
+        buf[25..].copy_from_slice(b" GMT");
+        // only ascii was written
+        core::str::from_utf8(buf).unwrap()
+    }
+}
+
+impl TryFrom<&str> for HttpDate {
+    type Error = InvalidHttpDate;
+    fn try_from(value: &str) -> Result<Self, Self::Error> { Self::parse(value) }
+}
+
+impl From<HttpDate> for u64 {
+    fn from(value: HttpDate) -> Self { value.0 }
+}
+
+impl core::fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.format(&mut [0; 29]))
+    }
+}
+
+// `Nov  6 08:49:37 1994`, after the weekday
+fn parse_asctime(input: &str) -> Result<HttpDate, InvalidHttpDate> {
+    let (weekday, rest) = input.split_once(' ').ok_or(InvalidHttpDate)?;
+    if !DAYS.contains(&weekday) || rest.len() != 20 { return Err(InvalidHttpDate); }
+    let (month, rest) = rest.split_at(3);
+    let day = rest[1..3].trim_start_matches(' ');
+    if !rest.starts_with(' ') || rest.as_bytes()[3] != b' ' || rest.as_bytes()[12] != b' ' {
+        return Err(InvalidHttpDate);
+    }
+    from_parts(digits(day)?, month, digits(&rest[13..])?, &rest[4..12])
+}
+
+fn from_parts(day: u32, month: &str, year: u32, time: &str) -> Result<HttpDate, InvalidHttpDate> {
+    let month = MONTHS.iter().position(|name| *name == month).ok_or(InvalidHttpDate)? as u32 + 1;
+    let mut time = time.split(':').map(|part| if part.len() == 2 { digits(part) } else { Err(InvalidHttpDate) });
+    let (hour, minute, second) = match (time.next(), time.next(), time.next(), time.next()) {
+        (Some(hour), Some(minute), Some(second), None) => (hour?, minute?, second?),
+        _ => return Err(InvalidHttpDate),
+    };
+    // a leap second is folded into the following second
+    if year < 1970 || day == 0 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 60 {
+        return Err(InvalidHttpDate);
+    }
+    let days = days_from_civil(year, month, day) as u64;
+    Ok(HttpDate::from_unix(days * 86400 + (hour * 3600 + minute * 60 + second) as u64))
+}
+
+fn digits(input: &str) -> Result<u32, InvalidHttpDate> {
+    if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) { return Err(InvalidHttpDate); }
+    input.parse().map_err(|_| InvalidHttpDate)
+}
+
+fn write_digits(buf: &mut [u8], mut value: u32) {
+    for byte in buf.iter_mut().rev() {
+        *byte = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+}
+
+pub(crate) fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// days since 1970-01-01 for a date in the proleptic gregorian calendar
+pub(crate) fn days_from_civil(year: u32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// the (year, month, day) of a number of days since 1970-01-01
+pub(crate) fn civil_from_days(days: i64) -> (u32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = (year_of_era + era * 400 + (month <= 2) as i64) as u32;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+    use super::*;
+
+    const SUNDAY: HttpDate = HttpDate(784_111_777);
+
+    #[test]
+    fn accepted_formats() {
+        assert_eq!(HttpDate::parse("Sun, 06 Nov 1994 08:49:37 GMT").ok(), Some(SUNDAY));
+        assert_eq!(HttpDate::parse("Sunday, 06-Nov-94 08:49:37 GMT").ok(), Some(SUNDAY));
+        assert_eq!(HttpDate::parse("Sun Nov  6 08:49:37 1994").ok(), Some(SUNDAY));
+        assert_eq!(HttpDate::parse("Thu Nov 16 08:49:37 1994").map(u64::from).ok(), Some(SUNDAY.unix() + 10 * 86400));
+
+        // two digit years wrap at 70
+        assert_eq!(HttpDate::parse("Thursday, 01-Jan-70 00:00:00 GMT").ok(), Some(HttpDate::UNIX_EPOCH));
+        assert_eq!(HttpDate::parse("Tuesday, 29-Feb-00 00:00:00 GMT").map(u64::from).ok(), Some(951_782_400));
+        assert_eq!(HttpDate::parse("Sunday, 01-Jan-69 00:00:00 GMT").map(u64::from).ok(), Some(3_124_224_000));
+
+        // a leap second is the next second, the end of 9999 is the latest date
+        assert_eq!(HttpDate::parse("Wed, 31 Dec 2008 23:59:60 GMT").map(u64::from).ok(), Some(1_230_768_000));
+        assert_eq!(HttpDate::parse("Fri, 31 Dec 9999 23:59:59 GMT").map(u64::from).ok(), Some(MAX_SECONDS));
+    }
+
+    #[test]
+    fn invalid_dates() {
+        for invalid in [
+            "",
+            "Sun, 06 Nov 1994 08:49:37",
+            "Sun, 06 Nov 1994 08:49:37 UTC",
+            "Sun, 6 Nov 1994 08:49:37 GMT",
+            "Sun, 06 nov 1994 08:49:37 GMT",
+            "Sun, 06 Nov 94 08:49:37 GMT",
+            "Sun, 06 Nov 1994 8:49:37 GMT",
+            "Sun, 06 Nov 1994 08:49 GMT",
+            "Sun, 06 Nov 1994 24:00:00 GMT",
+            "Sun, 06  Nov 1994 08:49:37 GMT",
+            "Sunday, 06 Nov 1994 08:49:37 GMT",
+            "Sun, 06-Nov-94 08:49:37 GMT",
+            "Mon, 29 Feb 2100 00:00:00 GMT",
+            "Mon, 31 Dec 1969 23:59:59 GMT",
+            "Sun Nov 6 08:49:37 1994",
+            "Sun Nov  6 08:49:37 94",
+            "Sun Nov 06 08:49:37 1994 ",
+            "Sun, 06 Nov 1994 08:49:37 GMT\u{e9}",
+        ] {
+            assert!(HttpDate::parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(SUNDAY.to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(HttpDate::UNIX_EPOCH.to_string(), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(HttpDate::from_unix(951_782_400).to_string(), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(HttpDate::from_unix(u64::MAX).to_string(), "Fri, 31 Dec 9999 23:59:59 GMT");
+        for seconds in [0, 59, 86_399, 86_400, 951_868_799, 1_700_000_000, MAX_SECONDS] {
+            let date = HttpDate::from_unix(seconds);
+            assert_eq!(HttpDate::parse(&date.to_string()).ok(), Some(date));
+        }
+    }
+}
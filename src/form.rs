@@ -0,0 +1,216 @@
+use core::fmt::{ self, Display, Write };
+use crate::{ MediaType, Request, StandardHeaderName };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormError {
+    /// the body isn't `application/x-www-form-urlencoded`
+    UnsupportedMediaType,
+    /// the body, or a decoded key or value, isn't utf-8
+    InvalidUtf8,
+    /// the buffer is too small for the decoded key or value
+    BufferTooSmall,
+}
+
+/// a `key=value` pair of a query string or form body, as it was sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormPair<'i> {
+    pub key: &'i str,
+    pub value: &'i str,
+}
+
+impl<'i> FormPair<'i> {
+    /// the key with `+` and percent escapes decoded, into `buf` only if there are any
+    pub fn decode_key<'a>(&self, buf: &'a mut [u8]) -> Result<&'a str, FormError> where 'i: 'a { form_decode(self.key, buf) }
+
+    /// the value with `+` and percent escapes decoded, into `buf` only if there are any
+    pub fn decode_value<'a>(&self, buf: &'a mut [u8]) -> Result<&'a str, FormError> where 'i: 'a { form_decode(self.value, buf) }
+}
+
+/// the pairs of an `application/x-www-form-urlencoded` string, e.g. `a=1&b=two+words`
+///
+/// empty pairs are skipped, a pair without `=` has an empty value
+#[derive(Debug, Clone)]
+pub struct FormPairs<'i>(core::str::Split<'i, char>);
+
+impl<'i> FormPairs<'i> {
+    pub fn new(input: &'i str) -> Self { Self(input.split('&')) }
+}
+
+impl<'i> Iterator for FormPairs<'i> {
+    type Item = FormPair<'i>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let pair = self.0.by_ref().find(|pair| !pair.is_empty())?;
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        Some(FormPair { key, value })
+    }
+}
+
+/// the pairs of the query of the request target, empty if it has none
+pub fn query_pairs<'r, B>(request: &Request<'r, '_, B>) -> FormPairs<'r> {
+    let target = request.target();
+    let target = target.split_once('#').map_or(target, |(target, _)| target);
+    FormPairs::new(target.split_once('?').map_or("", |(_, query)| query))
+}
+
+/// the pairs of an `application/x-www-form-urlencoded` request body
+pub fn form_pairs<'a, B: AsRef<[u8]>>(request: &'a Request<'_, '_, B>) -> Result<FormPairs<'a>, FormError> {
+    let content_type = request.header(StandardHeaderName::ContentType).ok_or(FormError::UnsupportedMediaType)?;
+    let is_form = MediaType::parse(content_type.value())
+        .is_ok_and(|media_type| media_type.essence_eq(&MediaType::APPLICATION_WWW_FORM_URLENCODED));
+    if !is_form { return Err(FormError::UnsupportedMediaType); }
+    let body = core::str::from_utf8(request.body.as_ref()).map_err(|_| FormError::InvalidUtf8)?;
+    Ok(FormPairs::new(body))
+}
+
+/// decode `+` as a space and percent escapes, into `buf` only if there are any
+///
+/// a `%` that isn't followed by two hex digits is kept as it is
+pub fn form_decode<'a>(input: &'a str, buf: &'a mut [u8]) -> Result<&'a str, FormError> {
+    if !input.contains(['%', '+']) { return Ok(input); }
+    percent_decode(input, true, buf)
+}
+
+// decode percent escapes into `buf`, and `+` as a space if `plus` is set
+fn percent_decode<'b>(input: &str, plus: bool, buf: &'b mut [u8]) -> Result<&'b str, FormError> {
+    let mut len = 0;
+    for byte in percent_decoded(input, plus) {
+        *buf.get_mut(len).ok_or(FormError::BufferTooSmall)? = byte;
+        len += 1;
+    }
+    core::str::from_utf8(&buf[..len]).map_err(|_| FormError::InvalidUtf8)
+}
+
+// the bytes of `input` with percent escapes decoded, a `%` that isn't followed by two hex digits is kept
+pub(crate) fn percent_decoded(input: &str, plus: bool) -> impl Iterator<Item = u8> + '_ {
+    let input = input.as_bytes();
+    let mut i = 0;
+    core::iter::from_fn(move || {
+        let rest = input.get(i..).filter(|rest| !rest.is_empty())?;
+        let escaped = match rest {
+            [b'%', high, low, ..] => hex(*high).zip(hex(*low)).map(|(high, low)| high << 4 | low),
+            _ => None,
+        };
+        let (byte, consumed) = match (escaped, rest[0]) {
+            (Some(byte), _) => (byte, 3),
+            (None, b'+') if plus => (b' ', 1),
+            (None, byte) => (byte, 1),
+        };
+        i += consumed;
+        Some(byte)
+    })
+}
+
+fn hex(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// displays a key or value percent-encoded for a form body or query string, with spaces as `+`
+///
+/// only ascii alphanumerics and `*-._` are left as they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormEncoded<'a>(pub &'a str);
+
+impl Display for FormEncoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0.bytes() {
+            match byte {
+                b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => f.write_char(byte as char)?,
+                b' ' => f.write_char('+')?,
+                byte => write!(f, "%{byte:02X}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// displays pairs as an `application/x-www-form-urlencoded` string, e.g. `a=1&b=two+words`, for a form body or
+/// the query of a target
+#[derive(Debug, Clone, Copy)]
+pub struct FormSerializer<'a> {
+    pairs: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> FormSerializer<'a> {
+    pub fn new(pairs: &'a [(&'a str, &'a str)]) -> Self { Self { pairs } }
+}
+
+impl Display for FormSerializer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (key, value)) in self.pairs.iter().enumerate() {
+            if idx > 0 { f.write_char('&')?; }
+            write!(f, "{}={}", FormEncoded(key), FormEncoded(value))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::ToString, vec::Vec };
+    use super::*;
+    use crate::{ Header, HeaderName, parse_request };
+    use crate::header::EMPTY_HEADER;
+
+    #[test]
+    fn decoding() {
+        let mut buf = [0; 16];
+        assert_eq!(form_decode("plain", &mut []), Ok("plain"));
+        assert_eq!(form_decode("two+words%21", &mut buf), Ok("two words!"));
+        assert_eq!(form_decode("%e2%82%AC+%2B", &mut buf), Ok("€ +"));
+        // invalid escapes are kept
+        assert_eq!(form_decode("100%+%4g%", &mut buf), Ok("100% %4g%"));
+        assert_eq!(form_decode("%ff", &mut buf), Err(FormError::InvalidUtf8));
+        assert_eq!(form_decode("a+b", &mut [0; 2]), Err(FormError::BufferTooSmall));
+
+        let pair = FormPairs::new("a%20b=c+d").next().unwrap();
+        assert_eq!(pair, FormPair { key: "a%20b", value: "c+d" });
+        assert_eq!(pair.decode_key(&mut buf), Ok("a b"));
+        assert_eq!(pair.decode_value(&mut buf), Ok("c d"));
+    }
+
+    #[test]
+    fn query_pairs_stop_at_the_fragment() {
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("GET /a?x=1&&y&z=2=3#w=4 HTTP/1.1\r\n\r\n", &mut headers).unwrap();
+        let pairs: Vec<_> = query_pairs(&request).map(|pair| (pair.key, pair.value)).collect();
+        assert_eq!(pairs, [("x", "1"), ("y", ""), ("z", "2=3")]);
+
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("GET /a#?x=1 HTTP/1.1\r\n\r\n", &mut headers).unwrap();
+        assert_eq!(query_pairs(&request).count(), 0);
+    }
+
+    #[test]
+    fn form_pairs_need_a_form_content_type() {
+        let mut headers = [EMPTY_HEADER; 2];
+        let input = "POST / HTTP/1.1\r\nContent-Type: Application/X-WWW-Form-Urlencoded; charset=utf-8\r\n\r\na=1&b=2";
+        let request = parse_request(input, &mut headers).unwrap();
+        assert_eq!(form_pairs(&request).unwrap().map(|pair| pair.value).collect::<Vec<_>>(), ["1", "2"]);
+
+        let mut headers = [EMPTY_HEADER; 2];
+        let request = parse_request("POST / HTTP/1.1\r\nContent-Type: text/plain\r\n\r\na=1", &mut headers).unwrap();
+        assert_eq!(form_pairs(&request).err(), Some(FormError::UnsupportedMediaType));
+        let mut headers = [EMPTY_HEADER; 1];
+        let request = parse_request("POST / HTTP/1.1\r\n\r\na=1", &mut headers).unwrap();
+        assert_eq!(form_pairs(&request).err(), Some(FormError::UnsupportedMediaType));
+
+        let content_type = HeaderName::Standard(StandardHeaderName::ContentType);
+        let mut headers = [Header::new(content_type, "application/x-www-form-urlencoded")];
+        let request = Request::new(&b"a=\xff"[..], &mut headers);
+        assert_eq!(form_pairs(&request).err(), Some(FormError::InvalidUtf8));
+    }
+
+    #[test]
+    fn serialized_pairs_round_trip() {
+        let pairs = [("a b", "1+1=2"), ("€", "x&y"), ("*-._", ""), ("", "%")];
+        let serialized = FormSerializer::new(&pairs).to_string();
+        assert_eq!(serialized, "a+b=1%2B1%3D2&%E2%82%AC=x%26y&*-._=&=%25");
+        let mut buf = [0; 16];
+        for (pair, (key, value)) in FormPairs::new(&serialized).zip(pairs) {
+            assert_eq!(pair.decode_key(&mut buf), Ok(key));
+            assert_eq!(pair.decode_value(&mut buf), Ok(value));
+        }
+        assert_eq!(FormPairs::new(&serialized).count(), pairs.len());
+    }
+}
@@ -0,0 +1,310 @@
+use core::net::{ IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr };
+use crate::{ Header, Request, StandardHeaderName };
+use crate::header::list_values;
+use crate::syntax::{ self, Params };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardedError {
+    /// a parameter that occurs more than once in an element
+    DuplicateParameter,
+    /// a `host` or `proto` that isn't a valid host or scheme, or a parameter without a value
+    InvalidParameter,
+    /// a `for` or `by` that isn't a valid node
+    InvalidNode,
+}
+
+/// the name of a node (rfc 7239 section 6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeName<'f> {
+    Ip(IpAddr),
+    /// `unknown`, the proxy doesn't know or doesn't want to reveal the address
+    Unknown,
+    /// an obfuscated identifier, e.g. `_hidden`
+    Obfuscated(&'f str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodePort<'f> {
+    Port(u16),
+    /// an obfuscated port, e.g. `_8080`
+    Obfuscated(&'f str),
+}
+
+/// the client or proxy end of a hop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Node<'f> {
+    pub name: NodeName<'f>,
+    pub port: Option<NodePort<'f>>,
+}
+
+impl<'f> Node<'f> {
+    /// parse an unquoted node, e.g. `192.0.2.43:47011`, `[2001:db8:cafe::17]` or `_gazonk`
+    pub fn parse(input: &'f str) -> Result<Self, ForwardedError> {
+        let (name, port) = match input.strip_prefix('[') {
+            Some(rest) => {
+                let (ip, rest) = rest.split_once(']').ok_or(ForwardedError::InvalidNode)?;
+                let ip = ip.parse::<Ipv6Addr>().map_err(|_| ForwardedError::InvalidNode)?;
+                let port = match rest {
+                    "" => None,
+                    rest => Some(rest.strip_prefix(':').ok_or(ForwardedError::InvalidNode)?),
+                };
+                (NodeName::Ip(IpAddr::V6(ip)), port)
+            }
+            None => {
+                let (name, port) = input.split_once(':').map_or((input, None), |(name, port)| (name, Some(port)));
+                let name = match name {
+                    name if name.eq_ignore_ascii_case("unknown") => NodeName::Unknown,
+                    name if is_obfuscated(name) => NodeName::Obfuscated(name),
+                    name => NodeName::Ip(IpAddr::V4(name.parse::<Ipv4Addr>().map_err(|_| ForwardedError::InvalidNode)?)),
+                };
+                (name, port)
+            }
+        };
+        let port = match port {
+            None => None,
+            Some(port) if is_obfuscated(port) => Some(NodePort::Obfuscated(port)),
+            Some(port) if (1..=5).contains(&port.len()) && port.bytes().all(|b| b.is_ascii_digit()) => {
+                Some(NodePort::Port(port.parse().map_err(|_| ForwardedError::InvalidNode)?))
+            }
+            Some(_) => return Err(ForwardedError::InvalidNode),
+        };
+        Ok(Self { name, port })
+    }
+
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self.name {
+            NodeName::Ip(ip) => Some(ip),
+            _ => None,
+        }
+    }
+
+    /// the address, if both the ip and the port are known
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match (self.name, self.port) {
+            (NodeName::Ip(ip), Some(NodePort::Port(port))) => Some(SocketAddr::new(ip, port)),
+            _ => None,
+        }
+    }
+}
+
+// `obfnode` and `obfport`
+fn is_obfuscated(input: &str) -> bool {
+    input.len() > 1 && input.starts_with('_')
+        && input.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+/// one hop of a `Forwarded` header (rfc 7239 section 4), with its values unquoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ForwardedElement<'f> {
+    /// the interface the request came in on at the proxy
+    pub by: Option<Node<'f>>,
+    /// the node that made the request to the proxy
+    pub for_: Option<Node<'f>>,
+    /// the `Host` the proxy received
+    pub host: Option<&'f str>,
+    /// the scheme the proxy received the request with, e.g. `https`
+    pub proto: Option<&'f str>,
+}
+
+impl<'f> ForwardedElement<'f> {
+    /// parse one element of a `Forwarded` list, e.g. `for=192.0.2.60;proto=http;by=203.0.113.43`
+    ///
+    /// unknown parameters are ignored
+    pub fn parse(element: &'f str) -> Result<Self, ForwardedError> {
+        let mut parsed = Self::default();
+        for pair in syntax::Split::new(element, b';') {
+            if !pair.contains('=') { return Err(ForwardedError::InvalidParameter); }
+            let Some((name, value)) = Params::new(pair).next() else { return Err(ForwardedError::InvalidParameter) };
+            match name {
+                name if name.eq_ignore_ascii_case("by") => set(&mut parsed.by, Node::parse(value)?)?,
+                name if name.eq_ignore_ascii_case("for") => set(&mut parsed.for_, Node::parse(value)?)?,
+                name if name.eq_ignore_ascii_case("host") => {
+                    if value.is_empty() || value.bytes().any(|b| b.is_ascii_whitespace() || b == b'/') {
+                        return Err(ForwardedError::InvalidParameter);
+                    }
+                    set(&mut parsed.host, value)?;
+                }
+                name if name.eq_ignore_ascii_case("proto") => {
+                    if !is_scheme(value) { return Err(ForwardedError::InvalidParameter); }
+                    set(&mut parsed.proto, value)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn set<T>(field: &mut Option<T>, value: T) -> Result<(), ForwardedError> {
+    match field.replace(value) {
+        Some(_) => Err(ForwardedError::DuplicateParameter),
+        None => Ok(()),
+    }
+}
+
+// `scheme` from rfc 3986 section 3.1
+fn is_scheme(input: &str) -> bool {
+    input.as_bytes().first().is_some_and(u8::is_ascii_alphabetic)
+        && input.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+}
+
+/// the elements of all `Forwarded` headers in order, from the hop closest to the client to the closest proxy
+pub fn forwarded_elements<'a, 'h>(
+    headers: &'a [Header<'h>],
+) -> impl Iterator<Item = Result<ForwardedElement<'h>, ForwardedError>> + 'a {
+    list_values(headers, StandardHeaderName::Forwarded).map(ForwardedElement::parse)
+}
+
+/// the nodes of all `X-Forwarded-For` headers in order, from the client to the closest proxy
+///
+/// besides nodes as in `Forwarded`, unbracketed ipv6 addresses are accepted
+pub fn x_forwarded_for<'a, 'h>(headers: &'a [Header<'h>]) -> impl Iterator<Item = Result<Node<'h>, ForwardedError>> + 'a {
+    list_values(headers, StandardHeaderName::XForwardedFor).map(|value| match value.parse::<Ipv6Addr>() {
+        Ok(ip) => Ok(Node { name: NodeName::Ip(IpAddr::V6(ip)), port: None }),
+        Err(_) => Node::parse(syntax::unquote(value)),
+    })
+}
+
+/// the client of a request as reported by trusted proxies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForwardedClient<'h> {
+    /// the client's address, the peer's own when it isn't a trusted proxy
+    pub ip: IpAddr,
+    /// the scheme the client used, `None` when the proxy that reported the client didn't report one
+    pub proto: Option<&'h str>,
+    /// the `Host` the client sent, `None` when the proxy that reported the client didn't report one
+    pub host: Option<&'h str>,
+}
+
+impl ForwardedClient<'_> {
+    /// whether the client used `https`
+    pub fn is_secure(&self) -> bool { self.proto.is_some_and(|proto| proto.eq_ignore_ascii_case("https")) }
+}
+
+/// determine the client of a request that came from `peer`, trusting only the forwarding headers added by the
+/// proxies in `trusted`
+///
+/// the hops are walked back from the peer while they are trusted, the first untrusted address is the client's.
+/// `Forwarded` is preferred over `X-Forwarded-For`, its `proto` and `host` are only taken from the element that
+/// identifies the client, as they describe the request that proxy received. `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` are taken from their last value, as set by the closest proxy. a hop without an ip address, like `unknown` or an obfuscated
+/// node, ends the walk at the last known address
+pub fn resolve_client<'h, B>(
+    request: &Request<'h, '_, B>,
+    peer: IpAddr,
+    trusted: &[IpAddr],
+) -> Result<ForwardedClient<'h>, ForwardedError> {
+    let mut client = ForwardedClient { ip: peer, proto: None, host: None };
+    if !trusted.contains(&peer) { return Ok(client); }
+    let headers = request.headers();
+
+    // the lists aren't double ended, so each hop is found again from the front
+    if request.header(StandardHeaderName::Forwarded).is_some() {
+        let hops = forwarded_elements(headers).count();
+        for hop in (0..hops).rev() {
+            let element = forwarded_elements(headers).nth(hop).ok_or(ForwardedError::InvalidParameter)??;
+            let Some(ip) = element.for_.and_then(|node| node.ip()) else { break };
+            client = ForwardedClient { ip, proto: element.proto, host: element.host };
+            if !trusted.contains(&ip) { break; }
+        }
+        return Ok(client);
+    }
+
+    let hops = x_forwarded_for(headers).count();
+    for hop in (0..hops).rev() {
+        let node = x_forwarded_for(headers).nth(hop).ok_or(ForwardedError::InvalidNode)??;
+        let Some(ip) = node.ip() else { break };
+        client.ip = ip;
+        if !trusted.contains(&ip) { break; }
+    }
+    client.proto = list_values(headers, StandardHeaderName::XForwardedProto).last().filter(|proto| is_scheme(proto));
+    client.host = list_values(headers, StandardHeaderName::XForwardedHost).last();
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use super::*;
+    use crate::parse_request;
+
+    const PROXY: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn lowercase_forwarded_is_used() {
+        let mut headers = [crate::header::EMPTY_HEADER; 4];
+        let input = "GET / HTTP/1.1\r\nhost: example.com\r\nforwarded: for=192.0.2.60;proto=https;host=example.org\r\n\r\n";
+        let request = parse_request(input, &mut headers).unwrap();
+        let client = resolve_client(&request, PROXY, &[PROXY]).unwrap();
+        assert_eq!(client, ForwardedClient {
+            ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60)),
+            proto: Some("https"),
+            host: Some("example.org"),
+        });
+    }
+
+    #[test]
+    fn lowercase_x_forwarded_headers_are_used() {
+        let mut headers = [crate::header::EMPTY_HEADER; 4];
+        let input = "GET / HTTP/1.1\r\nx-forwarded-for: 2001:db8::1, 10.0.0.2\r\nx-forwarded-proto: https\r\n\r\n";
+        let request = parse_request(input, &mut headers).unwrap();
+        let trusted = [PROXY, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))];
+        let client = resolve_client(&request, PROXY, &trusted).unwrap();
+        assert_eq!(client.ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert!(client.is_secure());
+        // an untrusted peer's headers are ignored
+        let client = resolve_client(&request, IpAddr::V4(Ipv4Addr::LOCALHOST), &trusted).unwrap();
+        assert_eq!(client, ForwardedClient { ip: IpAddr::V4(Ipv4Addr::LOCALHOST), proto: None, host: None });
+    }
+
+    fn resolve(forwarded: &str, trusted: &[IpAddr]) -> Result<ForwardedClient<'static>, ForwardedError> {
+        let mut headers = [crate::header::EMPTY_HEADER; 4];
+        let input = std::format!("GET / HTTP/1.1\r\nHost: example.com\r\nForwarded: {forwarded}\r\n\r\n").leak();
+        let request = parse_request(input, &mut headers).unwrap();
+        resolve_client(&request, PROXY, trusted)
+    }
+
+    #[test]
+    fn quoted_ipv6_nodes() {
+        let client = resolve("for=\"[2001:db8::1]:4711\";proto=https", &[PROXY]).unwrap();
+        assert_eq!(client.ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(client.proto, Some("https"));
+        let element = ForwardedElement::parse("For=\"[2001:db8::1]:4711\"").unwrap();
+        assert_eq!(element.for_.and_then(|node| node.socket_addr()), "[2001:db8::1]:4711".parse().ok());
+    }
+
+    #[test]
+    fn trusted_chains_are_walked() {
+        let trusted = [PROXY, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3))];
+        let forwarded = "for=192.0.2.1;host=spoofed.example, for=192.0.2.60;proto=https;host=example.org, \
+            for=10.0.0.3;proto=http, for=10.0.0.2;host=internal";
+        let client = resolve(forwarded, &trusted).unwrap();
+        assert_eq!(client, ForwardedClient {
+            ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60)),
+            proto: Some("https"),
+            host: Some("example.org"),
+        });
+
+        // the scheme and host of the proxies in between aren't the client's
+        let client = resolve("for=192.0.2.60, for=10.0.0.2;proto=https;host=internal", &trusted).unwrap();
+        assert_eq!(client, ForwardedClient { ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60)), proto: None, host: None });
+    }
+
+    #[test]
+    fn unknown_nodes_end_the_walk() {
+        let trusted = [PROXY, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))];
+        let client = resolve("for=192.0.2.60, for=_hidden;proto=http, for=10.0.0.2;proto=https", &trusted).unwrap();
+        assert_eq!(client, ForwardedClient { ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), proto: Some("https"), host: None });
+        let client = resolve("for=192.0.2.60, for=unknown", &trusted).unwrap();
+        assert_eq!(client, ForwardedClient { ip: PROXY, proto: None, host: None });
+    }
+
+    #[test]
+    fn malformed_elements_are_errors() {
+        assert_eq!(resolve("for=192.0.2.60, for=10.0.0.2;proto", &[PROXY]), Err(ForwardedError::InvalidParameter));
+        assert_eq!(resolve("for=\"[2001:db8::1\"", &[PROXY]), Err(ForwardedError::InvalidNode));
+        assert_eq!(resolve("for=192.0.2.60;for=192.0.2.61", &[PROXY]), Err(ForwardedError::DuplicateParameter));
+        // elements behind an untrusted hop aren't looked at
+        assert!(resolve("for=;, for=192.0.2.60", &[PROXY]).is_ok());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::syntax;
+
 pub const EMPTY_HEADER: Header<'_> = Header { name: HeaderName::Custom(""), value: "" };
 
 #[derive(Debug)]
@@ -14,25 +16,72 @@ impl<'h> Header<'h> {
     pub fn new(name: HeaderName<'h>, value: &'h str) -> Self {
         Self { name, value }
     }
+
+    pub fn name(&self) -> &HeaderName<'h> { &self.name }
+    pub fn value(&self) -> &'h str { self.value }
+
+    /// check whether this is the given standard header
+    pub fn is(&self, name: StandardHeaderName) -> bool {
+        self.name == HeaderName::Standard(name)
+    }
 }
 
-#[derive(Debug)]
+/// the values of every (possibly repeated) list header with the given name
+pub(crate) fn list_values<'a, 'h>(
+    headers: &'a [Header<'h>],
+    name: StandardHeaderName,
+) -> impl Iterator<Item = &'h str> + 'a {
+    headers.iter().filter(move |header| header.is(name)).flat_map(|header| syntax::list(header.value()))
+}
+
+/// whether any of the (possibly repeated) list headers contains the case-insensitive token
+pub(crate) fn has_token(headers: &[Header<'_>], name: StandardHeaderName, token: &str) -> bool {
+    list_values(headers, name).any(|value| value.eq_ignore_ascii_case(token))
+}
+
+/// the number of unused (empty) slots
+pub(crate) fn unused_slots(headers: &[Header<'_>]) -> usize {
+    headers.iter().filter(|header| header.name().as_str().is_empty()).count()
+}
+
+/// put a header in the first unused (empty) slot, `false` if there is none
+pub(crate) fn push_header<'h>(headers: &mut [Header<'h>], name: StandardHeaderName, value: &'h str) -> bool {
+    let Some(slot) = headers.iter_mut().find(|header| header.name().as_str().is_empty()) else { return false };
+    *slot = Header::new(HeaderName::Standard(name), value);
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HeaderName<'h> {
     Standard(StandardHeaderName),
     Custom(&'h str)
 }
 
+impl<'h> HeaderName<'h> {
+    pub fn as_str(&self) -> &'h str {
+        match self {
+            Self::Standard(name) => name.as_str(),
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+/// header names are case-insensitive, `host` and `Host` are both `Standard(Host)`
 impl<'h> From<&'h str> for HeaderName<'h> {
     fn from(value: &'h str) -> Self {
         let header: Result<StandardHeaderName, InvalidStandardHeaderName> = value.try_into();
+        // the exact spelling is the common case, the lookup ignoring case only runs for the rest
         match header {
             Ok(header) => Self::Standard(header),
-            Err(header) => Self::Custom(header.0)
+            Err(header) => match StandardHeaderName::from_ignore_case(header.0) {
+                Some(header) => Self::Standard(header),
+                None => Self::Custom(header.0),
+            },
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StandardHeaderName {
     Accept,
     AcceptCharset,
@@ -78,6 +127,7 @@ pub enum StandardHeaderName {
     Forwarded,
     From,
     Host,
+    Http2Settings,
     IfMatch,
     IfModifiedSince,
     IfNoneMatch,
@@ -103,6 +153,10 @@ pub enum StandardHeaderName {
     SecFetchUser,
     SecPurpose,
     SecWebsocketAccept,
+    SecWebsocketExtensions,
+    SecWebsocketKey,
+    SecWebsocketProtocol,
+    SecWebsocketVersion,
     Server,
     ServerTiming,
     ServiceWorkerNavigationPreload,
@@ -120,10 +174,144 @@ pub enum StandardHeaderName {
     Via,
     WWWAuthenticate,
     XContentTypeOptions,
+    XForwardedFor,
+    XForwardedHost,
+    XForwardedProto,
     XFrameOptions,
     XXssProtection,
 }
 
+impl StandardHeaderName {
+    const ALL: [Self; 96] = [
+        Self::Accept, Self::AcceptCharset, Self::AcceptEncoding, Self::AcceptLanguage, Self::AcceptPatch,
+        Self::AcceptPost, Self::AcceptRanges, Self::AccessControlAllowCredentials, Self::AccessControlAllowHeaders,
+        Self::AccessControlAllowMethods, Self::AccessControlAllowOrigin, Self::AccessControlExposeHeaders,
+        Self::AccessControlMaxAge, Self::AccessControlRequestHeaders, Self::AccessControlRequestMethod, Self::Age,
+        Self::Allow, Self::AltSvc, Self::AltUsed, Self::Authoritzation, Self::CacheControl, Self::ClearSiteData,
+        Self::Connection, Self::ContentDisposition, Self::ContentEncoding, Self::ContentLanguage, Self::ContentLength,
+        Self::ContentLocation, Self::ContentRange, Self::ContentSecurityPolicy, Self::ContentSecurityPolicyReportOnly,
+        Self::ContentType, Self::Cookie, Self::CrossOriginEmbedderPolicy, Self::CrossOriginOpenerPolicy,
+        Self::CrossOriginResourcePolicy, Self::Date, Self::DeviceMemory, Self::Etag, Self::Expect, Self::Expires,
+        Self::Forwarded, Self::From, Self::Host, Self::Http2Settings, Self::IfMatch, Self::IfModifiedSince,
+        Self::IfNoneMatch, Self::IfRange, Self::IfUnmodifiedSince, Self::KeepAlive, Self::LastModified, Self::Link,
+        Self::Location, Self::MaxForwards, Self::Origin, Self::PermissionsPolicy, Self::ProxyAuthenticate,
+        Self::ProxyAuthorization, Self::Range, Self::Referer, Self::RefererPolicy, Self::ReportingEndpoints,
+        Self::RetryAfter, Self::SecFetchDest, Self::SecFetchMode, Self::SecFetchSite, Self::SecFetchUser,
+        Self::SecPurpose, Self::SecWebsocketAccept, Self::SecWebsocketExtensions, Self::SecWebsocketKey,
+        Self::SecWebsocketProtocol, Self::SecWebsocketVersion, Self::Server, Self::ServerTiming,
+        Self::ServiceWorkerNavigationPreload, Self::SetCookie, Self::SourceMap, Self::StrictTransportSecurity, Self::TE,
+        Self::TimingAllowOrigin, Self::Trailer, Self::TransferEncoding, Self::Upgrade, Self::UpgradeInsecureRequests,
+        Self::UserAgent, Self::Vary, Self::Via, Self::WWWAuthenticate, Self::XContentTypeOptions, Self::XForwardedFor,
+        Self::XForwardedHost, Self::XForwardedProto, Self::XFrameOptions, Self::XXssProtection,
+    ];
+
+    /// the standard header with this name in any case, e.g. the lowercase names of http/2
+    pub fn from_ignore_case(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|header| header.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accept => "Accept",
+            Self::AcceptCharset => "Accept-Charset",
+            Self::AcceptEncoding => "Accept-Encoding",
+            Self::AcceptLanguage => "Accept-Language",
+            Self::AcceptPatch => "Accept-Patch",
+            Self::AcceptPost => "Accept-Post",
+            Self::AcceptRanges => "Accept-Ranges",
+            Self::AccessControlAllowCredentials => "Access-Control-Allow-Credentials",
+            Self::AccessControlAllowHeaders => "Access-Control-Allow-Headers",
+            Self::AccessControlAllowMethods => "Access-Control-Allow-Methods",
+            Self::AccessControlAllowOrigin => "Access-Control-Allow-Origin",
+            Self::AccessControlExposeHeaders => "Access-Control-Expose-Headers",
+            Self::AccessControlMaxAge => "Access-Control-Max-Age",
+            Self::AccessControlRequestHeaders => "Access-Control-Request-Headers",
+            Self::AccessControlRequestMethod => "Access-Control-Request-Method",
+            Self::Age => "Age",
+            Self::Allow => "Allow",
+            Self::AltSvc => "Alt-Svc",
+            Self::AltUsed => "Alt-Used",
+            Self::Authoritzation => "Authorization",
+            Self::CacheControl => "Cache-Control",
+            Self::ClearSiteData => "Clear-Site-Data",
+            Self::Connection => "Connection",
+            Self::ContentDisposition => "Content-Disposition",
+            Self::ContentEncoding => "Content-Encoding",
+            Self::ContentLanguage => "Content-Language",
+            Self::ContentLength => "Content-Length",
+            Self::ContentLocation => "Content-Location",
+            Self::ContentRange => "Content-Range",
+            Self::ContentSecurityPolicy => "Content-Security-Policy",
+            Self::ContentSecurityPolicyReportOnly => "Content-Security-Policy-Report-Only",
+            Self::ContentType => "Content-Type",
+            Self::Cookie => "Cookie",
+            Self::CrossOriginEmbedderPolicy => "Cross-Origin-Embedder-Policy",
+            Self::CrossOriginOpenerPolicy => "Cross-Origin-Opener-Policy",
+            Self::CrossOriginResourcePolicy => "Cross-Origin-Resource-Policy",
+            Self::Date => "Date",
+            Self::DeviceMemory => "Device-Memory",
+            Self::Etag => "ETag",
+            Self::Expect => "Expect",
+            Self::Expires => "Expires",
+            Self::Forwarded => "Forwarded",
+            Self::From => "From",
+            Self::Host => "Host",
+            Self::Http2Settings => "HTTP2-Settings",
+            Self::IfMatch => "If-Match",
+            Self::IfModifiedSince => "If-Modified-Since",
+            Self::IfNoneMatch => "If-None-Match",
+            Self::IfRange => "If-Range",
+            Self::IfUnmodifiedSince => "If-Unmodified-Since",
+            Self::KeepAlive => "Keep-Alive",
+            Self::LastModified => "Last-Modified",
+            Self::Link => "Link",
+            Self::Location => "Location",
+            Self::MaxForwards => "Max-Forwards",
+            Self::Origin => "Origin",
+            Self::PermissionsPolicy => "Permissions-Policy",
+            Self::ProxyAuthenticate => "Proxy-Authenticate",
+            Self::ProxyAuthorization => "Proxy-Authorization",
+            Self::Range => "Range",
+            Self::Referer => "Referer",
+            Self::RefererPolicy => "Referer-Policy",
+            Self::ReportingEndpoints => "Reporting-Endpoints",
+            Self::RetryAfter => "Retry-After",
+            Self::SecFetchDest => "Sec-Fetch-Dest",
+            Self::SecFetchMode => "Sec-Fetch-Mode",
+            Self::SecFetchSite => "Sec-Fetch-Site",
+            Self::SecFetchUser => "Sec-Fetch-User",
+            Self::SecPurpose => "Sec-Purpose",
+            Self::SecWebsocketAccept => "Sec-WebSocket-Accept",
+            Self::SecWebsocketExtensions => "Sec-WebSocket-Extensions",
+            Self::SecWebsocketKey => "Sec-WebSocket-Key",
+            Self::SecWebsocketProtocol => "Sec-WebSocket-Protocol",
+            Self::SecWebsocketVersion => "Sec-WebSocket-Version",
+            Self::Server => "Server",
+            Self::ServerTiming => "Server-Timing",
+            Self::ServiceWorkerNavigationPreload => "Service-Worker-Navigation-Preload",
+            Self::SetCookie => "Set-Cookie",
+            Self::SourceMap => "SourceMap",
+            Self::StrictTransportSecurity => "Strict-Transport-Security",
+            Self::TE => "TE",
+            Self::TimingAllowOrigin => "Timing-Allow-Origin",
+            Self::Trailer => "Trailer",
+            Self::TransferEncoding => "Transfer-Encoding",
+            Self::Upgrade => "Upgrade",
+            Self::UpgradeInsecureRequests => "Upgrade-Insecure-Requests",
+            Self::UserAgent => "User-Agent",
+            Self::Vary => "Vary",
+            Self::Via => "Via",
+            Self::WWWAuthenticate => "WWW-Authenticate",
+            Self::XContentTypeOptions => "X-Content-Type-Options",
+            Self::XForwardedFor => "X-Forwarded-For",
+            Self::XForwardedHost => "X-Forwarded-Host",
+            Self::XForwardedProto => "X-Forwarded-Proto",
+            Self::XFrameOptions => "X-Frame-Options",
+            Self::XXssProtection => "X-XSS-Protection",
+        }
+    }
+}
+
 pub struct InvalidStandardHeaderName<'i>(&'i str); // ??: should i include the header name in the error type
 
 impl<'e> TryFrom<&'e str> for StandardHeaderName {
@@ -141,6 +329,7 @@ impl<'e> TryFrom<&'e str> for StandardHeaderName {
             "Access-Control-Allow-Headers" => Ok(Self::AccessControlAllowHeaders),
             "Access-Control-Allow-Methods" => Ok(Self::AccessControlAllowMethods),
             "Access-Control-Allow-Origin" => Ok(Self::AccessControlAllowOrigin),
+            "Access-Control-Expose-Headers" => Ok(Self::AccessControlExposeHeaders),
             "Access-Control-Max-Age" => Ok(Self::AccessControlMaxAge),
             "Access-Control-Request-Headers" => Ok(Self::AccessControlRequestHeaders),
             "Access-Control-Request-Method" => Ok(Self::AccessControlRequestMethod),
@@ -167,12 +356,13 @@ impl<'e> TryFrom<&'e str> for StandardHeaderName {
             "Cross-Origin-Resource-Policy" => Ok(Self::CrossOriginResourcePolicy),
             "Date" => Ok(Self::Date),
             "Device-Memory" => Ok(Self::DeviceMemory),
-            "Etag" => Ok(Self::Etag),
+            "ETag" | "Etag" => Ok(Self::Etag),
             "Expect" => Ok(Self::Expect),
             "Expires" => Ok(Self::Expires),
             "Forwarded" => Ok(Self::Forwarded),
             "From" => Ok(Self::From),
             "Host" => Ok(Self::Host),
+            "HTTP2-Settings" | "Http2-Settings" => Ok(Self::Http2Settings),
             "If-Match" => Ok(Self::IfMatch),
             "If-Modified-Since" => Ok(Self::IfModifiedSince),
             "If-None-Match" => Ok(Self::IfNoneMatch),
@@ -197,7 +387,11 @@ impl<'e> TryFrom<&'e str> for StandardHeaderName {
             "Sec-Fetch-Site" => Ok(Self::SecFetchSite),
             "Sec-Fetch-User" => Ok(Self::SecFetchUser),
             "Sec-Purpose" => Ok(Self::SecPurpose),
-            "Sec-Websocket-Accept" => Ok(Self::SecWebsocketAccept),
+            "Sec-WebSocket-Accept" | "Sec-Websocket-Accept" => Ok(Self::SecWebsocketAccept),
+            "Sec-WebSocket-Extensions" | "Sec-Websocket-Extensions" => Ok(Self::SecWebsocketExtensions),
+            "Sec-WebSocket-Key" | "Sec-Websocket-Key" => Ok(Self::SecWebsocketKey),
+            "Sec-WebSocket-Protocol" | "Sec-Websocket-Protocol" => Ok(Self::SecWebsocketProtocol),
+            "Sec-WebSocket-Version" | "Sec-Websocket-Version" => Ok(Self::SecWebsocketVersion),
             "Server" => Ok(Self::Server),
             "Server-Timing" => Ok(Self::ServerTiming),
             "Service-Worker-Navigation-Preload" => Ok(StandardHeaderName::ServiceWorkerNavigationPreload),
@@ -215,6 +409,9 @@ impl<'e> TryFrom<&'e str> for StandardHeaderName {
             "Via" => Ok(Self::Via),
             "WWW-Authenticate" => Ok(Self::WWWAuthenticate),
             "X-Content-Type-Options" => Ok(Self::XContentTypeOptions),
+            "X-Forwarded-For" => Ok(Self::XForwardedFor),
+            "X-Forwarded-Host" => Ok(Self::XForwardedHost),
+            "X-Forwarded-Proto" => Ok(Self::XForwardedProto),
             "X-Frame-Options" => Ok(Self::XFrameOptions),
             "X-XSS-Protection" => Ok(Self::XXssProtection),
             h => Err(InvalidStandardHeaderName(h)),
@@ -0,0 +1,17 @@
+mod frame;
+pub use frame::{
+    FRAME_HEADER_LEN, MAX_WINDOW_SIZE, DEFAULT_MAX_FRAME_SIZE, MAX_FRAME_SIZE, PREFACE,
+    ErrorCode, Http2Error, FrameType, Flags, FrameHeader, Priority, Setting, SettingsIter, Settings, Frame,
+    PrefaceMatch, write_settings, match_preface,
+};
+mod huffman;
+pub(crate) mod hpack;
+pub use hpack::{ DEFAULT_TABLE_SIZE, HpackError, HpackDecoder, HpackEncoder };
+mod connection;
+pub use connection::{ StreamState, SendError, Connection };
+mod pseudo;
+pub use pseudo::{ MalformedError, RequestPseudoHeaders, ResponsePseudoHeaders, check_headers };
+mod upgrade;
+pub use upgrade::{ UpgradeError, H2cSettings, H2cUpgrade, upgrade_h2c_request, verify_h2c_response };
+mod translate;
+pub use translate::{ TranslateError, request_to_http1, response_to_http1, request_to_http2, response_to_http2 };
@@ -0,0 +1,604 @@
+use crate::Role;
+use super::frame::{ ErrorCode, Frame, Http2Error, MAX_WINDOW_SIZE, Setting, Settings };
+
+const MAX_STREAM_ID: u32 = (1 << 31) - 1;
+// how many `SETTINGS` can be waiting for an acknowledgment
+const MAX_PENDING_SETTINGS: usize = 4;
+
+/// the lifecycle of a stream (rfc 9113 section 5.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    Idle,
+    /// promised by this endpoint with a `PUSH_PROMISE`
+    ReservedLocal,
+    /// promised by the peer with a `PUSH_PROMISE`
+    ReservedRemote,
+    Open,
+    /// this endpoint sent `END_STREAM`
+    HalfClosedLocal,
+    /// the peer sent `END_STREAM`
+    HalfClosedRemote,
+    Closed,
+}
+
+/// a frame this endpoint can't send in the connection's current state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendError {
+    /// the peer's `SETTINGS_MAX_CONCURRENT_STREAMS` or the connection's stream capacity is reached
+    TooManyStreams,
+    /// the stream can't carry the frame in its state
+    InvalidState,
+    /// the data is more than the flow control windows allow, or a window update would overflow the window
+    FlowControl,
+    /// the peer disabled server push
+    PushDisabled,
+    /// the peer sent a `GOAWAY` or the stream ids ran out, so no new streams can be opened
+    GoingAway,
+    /// too many `SETTINGS` haven't been acknowledged by the peer yet
+    SettingsPending,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Stream {
+    id: u32,
+    state: StreamState,
+    send_window: i64,
+    recv_window: i64,
+}
+
+/// the state of an http/2 connection, without any io
+///
+/// every frame received after the connection preface is passed to [`recv`](Self::recv), starting with the peer's
+/// `SETTINGS`, and every frame sent is first checked with the matching `send_*` method. up to `S` streams can be
+/// active at once, this limit should be advertised as `SETTINGS_MAX_CONCURRENT_STREAMS`
+///
+/// header blocks have to be decoded even when their frame is rejected with a stream error, so the hpack state
+/// stays in step with the peer
+#[derive(Debug, Clone)]
+pub struct Connection<const S: usize = 100> {
+    role: Role,
+    local: Settings,
+    // the unacknowledged `SETTINGS` in the order they were sent, a ring starting at `pending_start`
+    pending_local: [Settings; MAX_PENDING_SETTINGS],
+    pending_start: usize,
+    pending_len: usize,
+    remote: Settings,
+    streams: [Option<Stream>; S],
+    next_local_id: u32,
+    last_remote_id: u32,
+    send_window: i64,
+    recv_window: i64,
+    // the stream whose header block is being continued
+    continuation: Option<u32>,
+    // the first frame from the peer has to be its `SETTINGS`
+    settings_received: bool,
+    goaway_sent: bool,
+    goaway_received: Option<u32>,
+}
+
+impl<const S: usize> Connection<S> {
+    pub fn new(role: Role) -> Self {
+        let initial_window = Settings::default().initial_window_size as i64;
+        Self {
+            role,
+            local: Settings::default(),
+            pending_local: [Settings::default(); MAX_PENDING_SETTINGS],
+            pending_start: 0,
+            pending_len: 0,
+            remote: Settings::default(),
+            streams: [None; S],
+            next_local_id: if role == Role::Client { 1 } else { 2 },
+            last_remote_id: 0,
+            send_window: initial_window,
+            recv_window: initial_window,
+            continuation: None,
+            settings_received: false,
+            goaway_sent: false,
+            goaway_received: None,
+        }
+    }
+
+    /// a connection upgraded from http/1.1 (rfc 7540 section 3.2), where the upgraded request is stream 1,
+    /// `settings` are the client's `HTTP2-Settings`, acknowledged by the `101 Switching Protocols`
+    pub fn upgraded(role: Role, settings: Settings) -> Self {
+        let mut connection = Self::new(role);
+        let state = match role {
+            Role::Client => {
+                connection.local = settings;
+                connection.next_local_id = 3;
+                StreamState::HalfClosedLocal
+            }
+            Role::Server => {
+                connection.remote = settings;
+                connection.last_remote_id = 1;
+                StreamState::HalfClosedRemote
+            }
+        };
+        let stream = connection.new_stream(1, state);
+        connection.insert(stream);
+        connection
+    }
+
+    pub fn role(&self) -> Role { self.role }
+    /// this endpoint's settings, once acknowledged by the peer
+    pub fn local_settings(&self) -> &Settings { &self.local }
+    pub fn remote_settings(&self) -> &Settings { &self.remote }
+
+    pub fn stream_state(&self, stream_id: u32) -> StreamState {
+        if let Some(stream) = self.stream(stream_id) { return stream.state; }
+        let last_id = if self.is_local(stream_id) { self.next_local_id.saturating_sub(2) } else { self.last_remote_id };
+        if stream_id <= last_id { StreamState::Closed } else { StreamState::Idle }
+    }
+
+    /// the number of open or half-closed streams
+    pub fn active_streams(&self) -> usize { self.count_active(|_| true) }
+
+    /// how much data the flow control windows allow to be sent on the stream right now
+    pub fn send_capacity(&self, stream_id: u32) -> u32 {
+        let stream_window = self.stream(stream_id).map_or(0, |stream| stream.send_window);
+        self.send_window.min(stream_window).max(0) as u32
+    }
+
+    /// how much more data the peer may send on the stream, `stream_id` 0 for the whole connection
+    pub fn recv_window(&self, stream_id: u32) -> i64 {
+        match stream_id {
+            0 => self.recv_window,
+            stream_id => self.stream(stream_id).map_or(0, |stream| stream.recv_window),
+        }
+    }
+
+    /// the last stream id of a `GOAWAY` received from the peer
+    pub fn goaway_received(&self) -> Option<u32> { self.goaway_received }
+
+    /// whether a `GOAWAY` was sent or received and every stream has finished, so the connection can be closed
+    pub fn is_finished(&self) -> bool {
+        (self.goaway_sent || self.goaway_received.is_some()) && self.streams.iter().all(Option::is_none)
+    }
+
+    /// check a received frame against the connection state and apply it
+    ///
+    /// a changed `SETTINGS_HEADER_TABLE_SIZE` has to be passed on to the hpack encoder, a `SETTINGS` frame that
+    /// isn't an acknowledgement has to be acknowledged and a `PING` answered
+    pub fn recv(&mut self, frame: &Frame<'_>) -> Result<(), Http2Error> {
+        if !self.settings_received {
+            if !matches!(frame, Frame::Settings { ack: false, .. }) {
+                return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+            }
+            self.settings_received = true;
+        }
+        // a header block has to be continued without any other frame in between
+        if let Some(expected) = self.continuation {
+            return match *frame {
+                Frame::Continuation { stream_id, end_headers, .. } if stream_id == expected => {
+                    if end_headers { self.continuation = None; }
+                    Ok(())
+                }
+                _ => Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+            };
+        }
+
+        match *frame {
+            Frame::Data { stream_id, end_stream, .. } => {
+                let len = frame.flow_controlled_len() as i64;
+                // the connection window is used up even by data that is rejected
+                if len > self.recv_window { return Err(Http2Error::Connection(ErrorCode::FLOW_CONTROL_ERROR)); }
+                self.recv_window -= len;
+                let state = self.stream_state(stream_id);
+                let stream = match self.stream_mut(stream_id) {
+                    Some(stream) if matches!(stream.state, StreamState::Open | StreamState::HalfClosedLocal) => stream,
+                    _ if state == StreamState::Idle => return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+                    _ => return Err(Http2Error::Stream(stream_id, ErrorCode::STREAM_CLOSED)),
+                };
+                if len > stream.recv_window { return Err(Http2Error::Stream(stream_id, ErrorCode::FLOW_CONTROL_ERROR)); }
+                stream.recv_window -= len;
+                if end_stream { self.close_remote(stream_id); }
+                Ok(())
+            }
+            Frame::Headers { stream_id, end_stream, end_headers, .. } => {
+                if !end_headers { self.continuation = Some(stream_id); }
+                self.recv_headers(stream_id, end_stream)
+            }
+            Frame::Priority { .. } | Frame::Ping { .. } | Frame::Unknown { .. } => Ok(()),
+            Frame::RstStream { stream_id, .. } => {
+                if self.stream_state(stream_id) == StreamState::Idle {
+                    return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                }
+                self.remove(stream_id);
+                Ok(())
+            }
+            Frame::Settings { ack: false, settings } => settings.into_iter().try_for_each(|setting| self.apply_remote(setting)),
+            Frame::Settings { ack: true, .. } => {
+                // each acknowledgment is for the oldest `SETTINGS` that hasn't been acknowledged yet
+                if self.pending_len > 0 {
+                    let settings = self.pending_local[self.pending_start];
+                    self.pending_start = (self.pending_start + 1) % MAX_PENDING_SETTINGS;
+                    self.pending_len -= 1;
+                    self.apply_local(settings);
+                }
+                Ok(())
+            }
+            Frame::PushPromise { stream_id, end_headers, promised_stream_id, .. } => {
+                if self.role == Role::Server || !self.local.enable_push {
+                    return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                }
+                if !end_headers { self.continuation = Some(stream_id); }
+                let associated = self.stream_state(stream_id);
+                if !matches!(associated, StreamState::Open | StreamState::HalfClosedLocal) {
+                    return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                }
+                if self.is_local(promised_stream_id) || promised_stream_id <= self.last_remote_id {
+                    return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                }
+                self.last_remote_id = promised_stream_id;
+                let stream = self.new_stream(promised_stream_id, StreamState::ReservedRemote);
+                if !self.insert(stream) { return Err(Http2Error::Stream(promised_stream_id, ErrorCode::REFUSED_STREAM)); }
+                Ok(())
+            }
+            Frame::Goaway { last_stream_id, .. } => {
+                let last_stream_id = self.goaway_received.map_or(last_stream_id, |last| last.min(last_stream_id));
+                self.goaway_received = Some(last_stream_id);
+                // streams the peer never processed can be retried on a new connection
+                for slot in &mut self.streams {
+                    let unprocessed = slot.is_some_and(|stream| {
+                        stream.id > last_stream_id && (stream.id % 2 == 1) == (self.role == Role::Client)
+                    });
+                    if unprocessed { *slot = None; }
+                }
+                Ok(())
+            }
+            Frame::WindowUpdate { stream_id: 0, increment } => {
+                self.send_window += increment as i64;
+                if self.send_window > MAX_WINDOW_SIZE as i64 { return Err(Http2Error::Connection(ErrorCode::FLOW_CONTROL_ERROR)); }
+                Ok(())
+            }
+            Frame::WindowUpdate { stream_id, increment } => {
+                if self.stream_state(stream_id) == StreamState::Idle {
+                    return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+                }
+                // updates can still arrive shortly after a stream closed
+                let Some(stream) = self.stream_mut(stream_id) else { return Ok(()) };
+                stream.send_window += increment as i64;
+                if stream.send_window > MAX_WINDOW_SIZE as i64 {
+                    return Err(Http2Error::Stream(stream_id, ErrorCode::FLOW_CONTROL_ERROR));
+                }
+                Ok(())
+            }
+            Frame::Continuation { .. } => Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+        }
+    }
+
+    fn recv_headers(&mut self, stream_id: u32, end_stream: bool) -> Result<(), Http2Error> {
+        match self.stream_state(stream_id) {
+            StreamState::Idle if !self.is_local(stream_id) && self.role == Role::Server => {
+                self.last_remote_id = stream_id;
+                let max_streams = self.local.max_concurrent_streams.map_or(S, |max| (max as usize).min(S));
+                let active = self.count_active(|stream| !self.is_local(stream.id));
+                if self.goaway_sent || active >= max_streams {
+                    return Err(Http2Error::Stream(stream_id, ErrorCode::REFUSED_STREAM));
+                }
+                let state = if end_stream { StreamState::HalfClosedRemote } else { StreamState::Open };
+                let stream = self.new_stream(stream_id, state);
+                if !self.insert(stream) { return Err(Http2Error::Stream(stream_id, ErrorCode::REFUSED_STREAM)); }
+            }
+            StreamState::Idle | StreamState::ReservedLocal => {
+                return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+            }
+            // a response can be preceded by informational responses and followed by trailers
+            StreamState::Open | StreamState::HalfClosedLocal => if end_stream { self.close_remote(stream_id); },
+            StreamState::ReservedRemote => {
+                self.set_state(stream_id, StreamState::HalfClosedLocal);
+                if end_stream { self.close_remote(stream_id); }
+            }
+            StreamState::HalfClosedRemote | StreamState::Closed => {
+                return Err(Http2Error::Stream(stream_id, ErrorCode::STREAM_CLOSED));
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_remote(&mut self, setting: Setting) -> Result<(), Http2Error> {
+        if let Setting::InitialWindowSize(size) = setting {
+            // the change applies to every stream's window, which can leave them negative
+            let delta = size as i64 - self.remote.initial_window_size as i64;
+            for stream in self.streams.iter_mut().flatten() {
+                stream.send_window += delta;
+                if stream.send_window > MAX_WINDOW_SIZE as i64 {
+                    return Err(Http2Error::Connection(ErrorCode::FLOW_CONTROL_ERROR));
+                }
+            }
+        }
+        self.remote.apply(setting);
+        Ok(())
+    }
+
+    fn apply_local(&mut self, settings: Settings) {
+        let delta = settings.initial_window_size as i64 - self.local.initial_window_size as i64;
+        for stream in self.streams.iter_mut().flatten() {
+            stream.recv_window += delta;
+        }
+        self.local = settings;
+    }
+
+    /// open a new stream by sending its `HEADERS`, returns the stream's id
+    pub fn open_stream(&mut self, end_stream: bool) -> Result<u32, SendError> {
+        if self.role == Role::Server { return Err(SendError::InvalidState); }
+        if self.goaway_received.is_some() || self.next_local_id > MAX_STREAM_ID { return Err(SendError::GoingAway); }
+        let max_streams = self.remote.max_concurrent_streams.map_or(usize::MAX, |max| max as usize);
+        if self.count_active(|stream| self.is_local(stream.id)) >= max_streams { return Err(SendError::TooManyStreams); }
+
+        let stream_id = self.next_local_id;
+        let state = if end_stream { StreamState::HalfClosedLocal } else { StreamState::Open };
+        let stream = self.new_stream(stream_id, state);
+        if !self.insert(stream) { return Err(SendError::TooManyStreams); }
+        self.next_local_id += 2;
+        Ok(stream_id)
+    }
+
+    /// send `HEADERS` on an existing stream, e.g. a response or trailers
+    pub fn send_headers(&mut self, stream_id: u32, end_stream: bool) -> Result<(), SendError> {
+        match self.stream_state(stream_id) {
+            StreamState::Open | StreamState::HalfClosedRemote => {}
+            StreamState::ReservedLocal => self.set_state(stream_id, StreamState::HalfClosedRemote),
+            _ => return Err(SendError::InvalidState),
+        }
+        if end_stream { self.close_local(stream_id); }
+        Ok(())
+    }
+
+    /// promise a pushed response on the stream of a request, returns the promised stream's id
+    pub fn send_push_promise(&mut self, stream_id: u32) -> Result<u32, SendError> {
+        if self.role == Role::Client { return Err(SendError::InvalidState); }
+        if !self.remote.enable_push { return Err(SendError::PushDisabled); }
+        if !matches!(self.stream_state(stream_id), StreamState::Open | StreamState::HalfClosedRemote) {
+            return Err(SendError::InvalidState);
+        }
+        if self.goaway_received.is_some() || self.next_local_id > MAX_STREAM_ID { return Err(SendError::GoingAway); }
+
+        let promised_stream_id = self.next_local_id;
+        let stream = self.new_stream(promised_stream_id, StreamState::ReservedLocal);
+        if !self.insert(stream) { return Err(SendError::TooManyStreams); }
+        self.next_local_id += 2;
+        Ok(promised_stream_id)
+    }
+
+    /// send `DATA` counting `len` bytes against flow control, see [`send_capacity`](Self::send_capacity)
+    pub fn send_data(&mut self, stream_id: u32, len: u32, end_stream: bool) -> Result<(), SendError> {
+        if !matches!(self.stream_state(stream_id), StreamState::Open | StreamState::HalfClosedRemote) {
+            return Err(SendError::InvalidState);
+        }
+        if len > self.send_capacity(stream_id) { return Err(SendError::FlowControl); }
+        self.send_window -= len as i64;
+        if let Some(stream) = self.stream_mut(stream_id) { stream.send_window -= len as i64; }
+        if end_stream { self.close_local(stream_id); }
+        Ok(())
+    }
+
+    /// send a `WINDOW_UPDATE`, `stream_id` 0 for the whole connection
+    pub fn send_window_update(&mut self, stream_id: u32, increment: u32) -> Result<(), SendError> {
+        let window = match stream_id {
+            0 => &mut self.recv_window,
+            stream_id => &mut self.stream_mut(stream_id).ok_or(SendError::InvalidState)?.recv_window,
+        };
+        if increment == 0 || *window + increment as i64 > MAX_WINDOW_SIZE as i64 { return Err(SendError::FlowControl); }
+        *window += increment as i64;
+        Ok(())
+    }
+
+    /// send a `RST_STREAM`, which closes the stream right away
+    pub fn send_reset(&mut self, stream_id: u32) { self.remove(stream_id); }
+
+    /// send `SETTINGS`, which take effect once the peer acknowledges them
+    ///
+    /// up to 4 can be waiting for their acknowledgments, which arrive in the order they were sent
+    pub fn send_settings(&mut self, settings: Settings) -> Result<(), SendError> {
+        if self.pending_len == MAX_PENDING_SETTINGS { return Err(SendError::SettingsPending); }
+        self.pending_local[(self.pending_start + self.pending_len) % MAX_PENDING_SETTINGS] = settings;
+        self.pending_len += 1;
+        Ok(())
+    }
+
+    /// send a `GOAWAY`, returns its last stream id, the streams up to it are still finished but new ones are
+    /// refused
+    pub fn send_goaway(&mut self) -> u32 {
+        self.goaway_sent = true;
+        self.last_remote_id
+    }
+
+    fn is_local(&self, stream_id: u32) -> bool { (stream_id % 2 == 1) == (self.role == Role::Client) }
+
+    fn new_stream(&self, id: u32, state: StreamState) -> Stream {
+        Stream {
+            id,
+            state,
+            send_window: self.remote.initial_window_size as i64,
+            recv_window: self.local.initial_window_size as i64,
+        }
+    }
+
+    fn stream(&self, stream_id: u32) -> Option<&Stream> {
+        self.streams.iter().flatten().find(|stream| stream.id == stream_id)
+    }
+
+    fn stream_mut(&mut self, stream_id: u32) -> Option<&mut Stream> {
+        self.streams.iter_mut().flatten().find(|stream| stream.id == stream_id)
+    }
+
+    fn count_active(&self, filter: impl Fn(&Stream) -> bool) -> usize {
+        self.streams.iter().flatten()
+            .filter(|stream| !matches!(stream.state, StreamState::ReservedLocal | StreamState::ReservedRemote))
+            .filter(|stream| filter(stream))
+            .count()
+    }
+
+    fn insert(&mut self, stream: Stream) -> bool {
+        let Some(slot) = self.streams.iter_mut().find(|slot| slot.is_none()) else { return false };
+        *slot = Some(stream);
+        true
+    }
+
+    fn remove(&mut self, stream_id: u32) {
+        if let Some(slot) = self.streams.iter_mut().find(|slot| slot.is_some_and(|stream| stream.id == stream_id)) {
+            *slot = None;
+        }
+    }
+
+    fn set_state(&mut self, stream_id: u32, state: StreamState) {
+        if let Some(stream) = self.stream_mut(stream_id) { stream.state = state; }
+    }
+
+    fn close_local(&mut self, stream_id: u32) {
+        match self.stream_state(stream_id) {
+            StreamState::Open => self.set_state(stream_id, StreamState::HalfClosedLocal),
+            _ => self.remove(stream_id),
+        }
+    }
+
+    fn close_remote(&mut self, stream_id: u32) {
+        match self.stream_state(stream_id) {
+            StreamState::Open => self.set_state(stream_id, StreamState::HalfClosedRemote),
+            _ => self.remove(stream_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::frame::DEFAULT_MAX_FRAME_SIZE;
+
+    fn settings(max_frame_size: u32) -> Settings {
+        Settings { max_frame_size, ..Settings::default() }
+    }
+
+    fn recv<const S: usize>(connection: &mut Connection<S>, frame: &[u8]) -> Result<(), Http2Error> {
+        let (frame, _) = Frame::parse(frame, DEFAULT_MAX_FRAME_SIZE).unwrap();
+        connection.recv(&frame)
+    }
+
+    // a connection that received the peer's (empty) `SETTINGS`
+    fn connection<const S: usize>(role: Role) -> Connection<S> {
+        let mut connection = Connection::new(role);
+        recv(&mut connection, b"\0\0\0\x04\0\0\0\0\0").unwrap();
+        connection
+    }
+
+    // receive a frame with a payload of up to 8 bytes
+    fn recv_frame<const S: usize>(
+        connection: &mut Connection<S>,
+        ty: u8,
+        flags: u8,
+        stream_id: u32,
+        payload: &[u8],
+    ) -> Result<(), Http2Error> {
+        let mut frame = [0; 17];
+        frame[2] = payload.len() as u8;
+        frame[3] = ty;
+        frame[4] = flags;
+        frame[5..9].copy_from_slice(&stream_id.to_be_bytes());
+        frame[9..9 + payload.len()].copy_from_slice(payload);
+        recv(connection, &frame[..9 + payload.len()])
+    }
+
+    const DATA: u8 = 0x0;
+    const HEADERS: u8 = 0x1;
+    const RST_STREAM: u8 = 0x3;
+    const PUSH_PROMISE: u8 = 0x5;
+    const PING: u8 = 0x6;
+    const END_STREAM: u8 = 0x1;
+    const END_HEADERS: u8 = 0x4;
+
+    fn ack<const S: usize>(connection: &mut Connection<S>) {
+        recv(connection, b"\0\0\0\x04\x01\0\0\0\0").unwrap();
+    }
+
+    #[test]
+    fn first_frame_must_be_settings() {
+        for frame in [&b"\0\0\x08\x06\0\0\0\0\0\0\0\0\0\0\0\0\0"[..], b"\0\0\0\x04\x01\0\0\0\0"] {
+            let mut connection = Connection::<4>::new(Role::Server);
+            assert_eq!(recv(&mut connection, frame), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        }
+        // also on a connection upgraded from http/1.1
+        let mut connection = Connection::<4>::upgraded(Role::Server, Settings::default());
+        assert_eq!(recv_frame(&mut connection, PING, 0, 0, &[0; 8]), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        let mut connection = Connection::<4>::upgraded(Role::Client, Settings::default());
+        recv(&mut connection, b"\0\0\0\x04\0\0\0\0\0").unwrap();
+        recv_frame(&mut connection, PING, 0, 0, &[0; 8]).unwrap();
+    }
+
+    #[test]
+    fn settings_take_effect_in_the_order_they_are_acknowledged() {
+        let mut connection = connection::<4>(Role::Server);
+        for size in 1..=MAX_PENDING_SETTINGS as u32 {
+            connection.send_settings(settings(16_384 + size)).unwrap();
+        }
+        assert_eq!(connection.send_settings(settings(16_384)), Err(SendError::SettingsPending));
+        assert_eq!(connection.local_settings(), &Settings::default());
+
+        ack(&mut connection);
+        assert_eq!(connection.local_settings().max_frame_size, 16_385);
+        connection.send_settings(settings(20_000)).unwrap();
+        for size in [16_386, 16_387, 16_388, 20_000] {
+            ack(&mut connection);
+            assert_eq!(connection.local_settings().max_frame_size, size);
+        }
+        // an acknowledgment of nothing changes nothing
+        ack(&mut connection);
+        assert_eq!(connection.local_settings().max_frame_size, 20_000);
+    }
+
+    #[test]
+    fn server_stream_states() {
+        let mut connection = connection::<4>(Role::Server);
+        recv_frame(&mut connection, HEADERS, END_HEADERS, 1, &[]).unwrap();
+        assert_eq!(connection.stream_state(1), StreamState::Open);
+        recv_frame(&mut connection, DATA, END_STREAM, 1, &[]).unwrap();
+        assert_eq!(connection.stream_state(1), StreamState::HalfClosedRemote);
+        assert_eq!(recv_frame(&mut connection, DATA, 0, 1, &[]), Err(Http2Error::Stream(1, ErrorCode::STREAM_CLOSED)));
+        connection.send_headers(1, false).unwrap();
+        connection.send_data(1, 0, true).unwrap();
+        assert_eq!(connection.stream_state(1), StreamState::Closed);
+        assert_eq!(connection.send_data(1, 0, false), Err(SendError::InvalidState));
+
+        recv_frame(&mut connection, HEADERS, END_HEADERS | END_STREAM, 3, &[]).unwrap();
+        assert_eq!(connection.stream_state(3), StreamState::HalfClosedRemote);
+        let promised = connection.send_push_promise(3).unwrap();
+        assert_eq!((promised, connection.stream_state(promised)), (2, StreamState::ReservedLocal));
+        assert_eq!(connection.send_data(promised, 0, false), Err(SendError::InvalidState));
+        connection.send_headers(promised, false).unwrap();
+        assert_eq!(connection.stream_state(promised), StreamState::HalfClosedRemote);
+        connection.send_data(promised, 0, true).unwrap();
+        assert_eq!(connection.stream_state(promised), StreamState::Closed);
+        recv_frame(&mut connection, RST_STREAM, 0, 3, &[0, 0, 0, 0x8]).unwrap();
+        assert_eq!(connection.stream_state(3), StreamState::Closed);
+
+        // frames on streams that were never opened are connection errors
+        assert_eq!(recv_frame(&mut connection, DATA, 0, 7, &[]), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        assert_eq!(recv_frame(&mut connection, HEADERS, END_HEADERS, 4, &[]), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+
+    #[test]
+    fn client_stream_states() {
+        let mut connection = connection::<4>(Role::Client);
+        let stream_id = connection.open_stream(false).unwrap();
+        assert_eq!((stream_id, connection.stream_state(stream_id)), (1, StreamState::Open));
+        connection.send_data(stream_id, 0, true).unwrap();
+        assert_eq!(connection.stream_state(stream_id), StreamState::HalfClosedLocal);
+        // an informational response, then the final one
+        recv_frame(&mut connection, HEADERS, END_HEADERS, stream_id, &[]).unwrap();
+        assert_eq!(connection.stream_state(stream_id), StreamState::HalfClosedLocal);
+        recv_frame(&mut connection, HEADERS, END_HEADERS | END_STREAM, stream_id, &[]).unwrap();
+        assert_eq!(connection.stream_state(stream_id), StreamState::Closed);
+
+        let stream_id = connection.open_stream(true).unwrap();
+        assert_eq!((stream_id, connection.stream_state(stream_id)), (3, StreamState::HalfClosedLocal));
+        recv_frame(&mut connection, PUSH_PROMISE, END_HEADERS, stream_id, &[0, 0, 0, 2]).unwrap();
+        assert_eq!(connection.stream_state(2), StreamState::ReservedRemote);
+        recv_frame(&mut connection, HEADERS, END_HEADERS, 2, &[]).unwrap();
+        assert_eq!(connection.stream_state(2), StreamState::HalfClosedLocal);
+        recv_frame(&mut connection, DATA, END_STREAM, 2, &[]).unwrap();
+        assert_eq!(connection.stream_state(2), StreamState::Closed);
+        // a promise has to use a new stream id of the server
+        assert_eq!(recv_frame(&mut connection, PUSH_PROMISE, END_HEADERS, stream_id, &[0, 0, 0, 2]),
+            Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+    }
+}
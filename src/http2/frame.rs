@@ -0,0 +1,654 @@
+/// the length of a frame header
+pub const FRAME_HEADER_LEN: usize = 9;
+
+/// an error code of `RST_STREAM` and `GOAWAY` frames (rfc 9113 section 7)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode(pub u32);
+
+impl ErrorCode {
+    pub const NO_ERROR: Self = Self(0x0);
+    pub const PROTOCOL_ERROR: Self = Self(0x1);
+    pub const INTERNAL_ERROR: Self = Self(0x2);
+    pub const FLOW_CONTROL_ERROR: Self = Self(0x3);
+    pub const SETTINGS_TIMEOUT: Self = Self(0x4);
+    pub const STREAM_CLOSED: Self = Self(0x5);
+    pub const FRAME_SIZE_ERROR: Self = Self(0x6);
+    pub const REFUSED_STREAM: Self = Self(0x7);
+    pub const CANCEL: Self = Self(0x8);
+    pub const COMPRESSION_ERROR: Self = Self(0x9);
+    pub const CONNECT_ERROR: Self = Self(0xa);
+    pub const ENHANCE_YOUR_CALM: Self = Self(0xb);
+    pub const INADEQUATE_SECURITY: Self = Self(0xc);
+    pub const HTTP_1_1_REQUIRED: Self = Self(0xd);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Http2Error {
+    /// more input is needed, at least this many bytes in total
+    Incomplete(usize),
+    /// an error that ends the whole connection with a `GOAWAY` (rfc 9113 section 5.4.1)
+    Connection(ErrorCode),
+    /// an error that resets a single stream with a `RST_STREAM` (rfc 9113 section 5.4.2)
+    Stream(u32, ErrorCode),
+    /// the output buffer is too small
+    BufferTooSmall,
+    /// a frame to write with a payload over 2^24 - 1 bytes, the most a frame header can describe
+    FrameTooLarge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Headers,
+    Priority,
+    RstStream,
+    Settings,
+    PushPromise,
+    Ping,
+    Goaway,
+    WindowUpdate,
+    Continuation,
+    /// frames of unknown types are ignored
+    Unknown(u8),
+}
+
+impl FrameType {
+    pub fn from_u8(ty: u8) -> Self {
+        match ty {
+            0x0 => Self::Data,
+            0x1 => Self::Headers,
+            0x2 => Self::Priority,
+            0x3 => Self::RstStream,
+            0x4 => Self::Settings,
+            0x5 => Self::PushPromise,
+            0x6 => Self::Ping,
+            0x7 => Self::Goaway,
+            0x8 => Self::WindowUpdate,
+            0x9 => Self::Continuation,
+            ty => Self::Unknown(ty),
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Data => 0x0,
+            Self::Headers => 0x1,
+            Self::Priority => 0x2,
+            Self::RstStream => 0x3,
+            Self::Settings => 0x4,
+            Self::PushPromise => 0x5,
+            Self::Ping => 0x6,
+            Self::Goaway => 0x7,
+            Self::WindowUpdate => 0x8,
+            Self::Continuation => 0x9,
+            Self::Unknown(ty) => *ty,
+        }
+    }
+}
+
+/// the flags of a frame, which flags are defined depends on the frame type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags(pub u8);
+
+impl Flags {
+    pub const END_STREAM: Self = Self(0x1);
+    pub const ACK: Self = Self(0x1);
+    pub const END_HEADERS: Self = Self(0x4);
+    pub const PADDED: Self = Self(0x8);
+    pub const PRIORITY: Self = Self(0x20);
+
+    pub fn contains(&self, flags: Flags) -> bool { self.0 & flags.0 == flags.0 }
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self { Self(self.0 | rhs.0) }
+}
+
+/// the 9 byte header preceding every frame (rfc 9113 section 4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    length: u32,
+    ty: FrameType,
+    flags: Flags,
+    stream_id: u32,
+}
+
+impl FrameHeader {
+    /// the length is the length of the payload, at most 2^24 - 1, higher bits are dropped
+    pub fn new(length: u32, ty: FrameType, flags: Flags, stream_id: u32) -> Self {
+        Self { length: length & 0xff_ffff, ty, flags, stream_id: stream_id & 0x7fff_ffff }
+    }
+
+    pub fn parse(input: &[u8]) -> Result<Self, Http2Error> {
+        let header = input.get(..FRAME_HEADER_LEN).ok_or(Http2Error::Incomplete(FRAME_HEADER_LEN))?;
+        Ok(Self {
+            length: u32::from_be_bytes([0, header[0], header[1], header[2]]),
+            ty: FrameType::from_u8(header[3]),
+            flags: Flags(header[4]),
+            // the reserved bit is ignored
+            stream_id: u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7fff_ffff,
+        })
+    }
+
+    pub fn length(&self) -> u32 { self.length }
+    pub fn ty(&self) -> FrameType { self.ty }
+    pub fn flags(&self) -> Flags { self.flags }
+    pub fn stream_id(&self) -> u32 { self.stream_id }
+
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, Http2Error> {
+        let out = out.get_mut(..FRAME_HEADER_LEN).ok_or(Http2Error::BufferTooSmall)?;
+        out[..3].copy_from_slice(&self.length.to_be_bytes()[1..]);
+        out[3] = self.ty.as_u8();
+        out[4] = self.flags.0;
+        out[5..].copy_from_slice(&self.stream_id.to_be_bytes());
+        Ok(FRAME_HEADER_LEN)
+    }
+}
+
+/// the priority fields of `HEADERS` and `PRIORITY` frames, deprecated but still parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority {
+    pub exclusive: bool,
+    pub dependency: u32,
+    /// the weight minus one, as it is sent
+    pub weight: u8,
+}
+
+impl Priority {
+    fn parse(input: &[u8]) -> Self {
+        let dependency = u32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+        Self { exclusive: dependency >> 31 != 0, dependency: dependency & 0x7fff_ffff, weight: input[4] }
+    }
+
+    fn bytes(&self) -> [u8; 5] {
+        let dependency = (self.exclusive as u32) << 31 | self.dependency & 0x7fff_ffff;
+        let [a, b, c, d] = dependency.to_be_bytes();
+        [a, b, c, d, self.weight]
+    }
+}
+
+/// a parameter of a `SETTINGS` frame (rfc 9113 section 6.5.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    HeaderTableSize(u32),
+    EnablePush(bool),
+    MaxConcurrentStreams(u32),
+    InitialWindowSize(u32),
+    MaxFrameSize(u32),
+    MaxHeaderListSize(u32),
+    /// unknown settings are ignored
+    Unknown(u16, u32),
+}
+
+impl Setting {
+    fn parse(input: &[u8]) -> Result<Self, Http2Error> {
+        let id = u16::from_be_bytes([input[0], input[1]]);
+        let value = u32::from_be_bytes([input[2], input[3], input[4], input[5]]);
+        Ok(match id {
+            0x1 => Self::HeaderTableSize(value),
+            0x2 if value > 1 => return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)),
+            0x2 => Self::EnablePush(value == 1),
+            0x3 => Self::MaxConcurrentStreams(value),
+            0x4 if value > MAX_WINDOW_SIZE => return Err(Http2Error::Connection(ErrorCode::FLOW_CONTROL_ERROR)),
+            0x4 => Self::InitialWindowSize(value),
+            0x5 if !(DEFAULT_MAX_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&value) => {
+                return Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR));
+            }
+            0x5 => Self::MaxFrameSize(value),
+            0x6 => Self::MaxHeaderListSize(value),
+            id => Self::Unknown(id, value),
+        })
+    }
+
+    pub(crate) fn bytes(&self) -> [u8; 6] {
+        let (id, value): (u16, u32) = match *self {
+            Self::HeaderTableSize(value) => (0x1, value),
+            Self::EnablePush(value) => (0x2, value as u32),
+            Self::MaxConcurrentStreams(value) => (0x3, value),
+            Self::InitialWindowSize(value) => (0x4, value),
+            Self::MaxFrameSize(value) => (0x5, value),
+            Self::MaxHeaderListSize(value) => (0x6, value),
+            Self::Unknown(id, value) => (id, value),
+        };
+        let ([a, b], [c, d, e, f]) = (id.to_be_bytes(), value.to_be_bytes());
+        [a, b, c, d, e, f]
+    }
+}
+
+/// the largest flow control window
+pub const MAX_WINDOW_SIZE: u32 = (1 << 31) - 1;
+/// the initial `SETTINGS_MAX_FRAME_SIZE`, and the smallest allowed
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 1 << 14;
+/// the largest allowed `SETTINGS_MAX_FRAME_SIZE`
+pub const MAX_FRAME_SIZE: u32 = (1 << 24) - 1;
+
+/// the (already validated) parameters of a `SETTINGS` frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingsIter<'f>(&'f [u8]);
+
+impl Iterator for SettingsIter<'_> {
+    type Item = Setting;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (setting, rest) = self.0.split_first_chunk::<6>()?;
+        self.0 = rest;
+        Setting::parse(setting).ok()
+    }
+}
+
+/// the settings of one endpoint, with the defaults from rfc 9113 section 6.5.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub header_table_size: u32,
+    pub enable_push: bool,
+    /// unlimited by default
+    pub max_concurrent_streams: Option<u32>,
+    pub initial_window_size: u32,
+    pub max_frame_size: u32,
+    /// unlimited by default
+    pub max_header_list_size: Option<u32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            header_table_size: 4096,
+            enable_push: true,
+            max_concurrent_streams: None,
+            initial_window_size: 65_535,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_header_list_size: None,
+        }
+    }
+}
+
+impl Settings {
+    pub fn apply(&mut self, setting: Setting) {
+        match setting {
+            Setting::HeaderTableSize(value) => self.header_table_size = value,
+            Setting::EnablePush(value) => self.enable_push = value,
+            Setting::MaxConcurrentStreams(value) => self.max_concurrent_streams = Some(value),
+            Setting::InitialWindowSize(value) => self.initial_window_size = value,
+            Setting::MaxFrameSize(value) => self.max_frame_size = value,
+            Setting::MaxHeaderListSize(value) => self.max_header_list_size = Some(value),
+            Setting::Unknown(..) => {}
+        }
+    }
+}
+
+/// a frame, borrowing its payload from the input
+///
+/// `pad_len` is the length of the padding, `None` for frames without the `PADDED` flag. padding counts towards flow
+/// control, see [`flow_controlled_len`](Self::flow_controlled_len)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame<'f> {
+    Data { stream_id: u32, end_stream: bool, data: &'f [u8], pad_len: Option<u8> },
+    Headers {
+        stream_id: u32,
+        end_stream: bool,
+        end_headers: bool,
+        priority: Option<Priority>,
+        fragment: &'f [u8],
+        pad_len: Option<u8>,
+    },
+    Priority { stream_id: u32, priority: Priority },
+    RstStream { stream_id: u32, error_code: ErrorCode },
+    Settings { ack: bool, settings: SettingsIter<'f> },
+    PushPromise { stream_id: u32, end_headers: bool, promised_stream_id: u32, fragment: &'f [u8], pad_len: Option<u8> },
+    Ping { ack: bool, data: [u8; 8] },
+    Goaway { last_stream_id: u32, error_code: ErrorCode, debug_data: &'f [u8] },
+    /// a stream id of 0 updates the connection's window
+    WindowUpdate { stream_id: u32, increment: u32 },
+    Continuation { stream_id: u32, end_headers: bool, fragment: &'f [u8] },
+    /// frames of unknown types are to be ignored
+    Unknown { header: FrameHeader, payload: &'f [u8] },
+}
+
+impl<'f> Frame<'f> {
+    /// parse a whole frame from the start of `input`, returns the frame and the number of bytes it took up
+    ///
+    /// `max_frame_size` is the `SETTINGS_MAX_FRAME_SIZE` this endpoint advertised
+    pub fn parse(input: &'f [u8], max_frame_size: u32) -> Result<(Self, usize), Http2Error> {
+        let header = FrameHeader::parse(input)?;
+        if header.length > max_frame_size { return Err(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR)); }
+        let len = FRAME_HEADER_LEN + header.length as usize;
+        let payload = input.get(FRAME_HEADER_LEN..len).ok_or(Http2Error::Incomplete(len))?;
+        Ok((Self::from_payload(header, payload)?, len))
+    }
+
+    /// check a frame's payload against its header
+    pub fn from_payload(header: FrameHeader, payload: &'f [u8]) -> Result<Self, Http2Error> {
+        let FrameHeader { ty, flags, stream_id, .. } = header;
+        let connection_error = |code| Err(Http2Error::Connection(code));
+        let requires_stream = matches!(ty, FrameType::Data | FrameType::Headers | FrameType::Priority
+            | FrameType::RstStream | FrameType::PushPromise | FrameType::Continuation);
+        let forbids_stream = matches!(ty, FrameType::Settings | FrameType::Ping | FrameType::Goaway);
+        if (requires_stream && stream_id == 0) || (forbids_stream && stream_id != 0) {
+            return connection_error(ErrorCode::PROTOCOL_ERROR);
+        }
+
+        Ok(match ty {
+            FrameType::Data => {
+                let (data, pad_len) = unpad(payload, flags)?;
+                Self::Data { stream_id, end_stream: flags.contains(Flags::END_STREAM), data, pad_len }
+            }
+            FrameType::Headers => {
+                let (mut fragment, pad_len) = unpad(payload, flags)?;
+                let mut priority = None;
+                if flags.contains(Flags::PRIORITY) {
+                    let (fields, rest) = fragment.split_first_chunk::<5>()
+                        .ok_or(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                    let fields = Priority::parse(fields);
+                    if fields.dependency == stream_id { return Err(Http2Error::Stream(stream_id, ErrorCode::PROTOCOL_ERROR)); }
+                    priority = Some(fields);
+                    fragment = rest;
+                }
+                Self::Headers {
+                    stream_id,
+                    end_stream: flags.contains(Flags::END_STREAM),
+                    end_headers: flags.contains(Flags::END_HEADERS),
+                    priority,
+                    fragment,
+                    pad_len,
+                }
+            }
+            FrameType::Priority => {
+                if payload.len() != 5 { return Err(Http2Error::Stream(stream_id, ErrorCode::FRAME_SIZE_ERROR)); }
+                let priority = Priority::parse(payload);
+                if priority.dependency == stream_id { return Err(Http2Error::Stream(stream_id, ErrorCode::PROTOCOL_ERROR)); }
+                Self::Priority { stream_id, priority }
+            }
+            FrameType::RstStream => {
+                let code = payload.try_into().map_err(|_| Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                Self::RstStream { stream_id, error_code: ErrorCode(u32::from_be_bytes(code)) }
+            }
+            FrameType::Settings => {
+                let ack = flags.contains(Flags::ACK);
+                if (ack && !payload.is_empty()) || !payload.len().is_multiple_of(6) {
+                    return connection_error(ErrorCode::FRAME_SIZE_ERROR);
+                }
+                for setting in payload.chunks_exact(6) {
+                    Setting::parse(setting)?;
+                }
+                Self::Settings { ack, settings: SettingsIter(payload) }
+            }
+            FrameType::PushPromise => {
+                let (payload, pad_len) = unpad(payload, flags)?;
+                let (promised, fragment) = payload.split_first_chunk::<4>()
+                    .ok_or(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                Self::PushPromise {
+                    stream_id,
+                    end_headers: flags.contains(Flags::END_HEADERS),
+                    promised_stream_id: u32::from_be_bytes(*promised) & 0x7fff_ffff,
+                    fragment,
+                    pad_len,
+                }
+            }
+            FrameType::Ping => {
+                let data = payload.try_into().map_err(|_| Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                Self::Ping { ack: flags.contains(Flags::ACK), data }
+            }
+            FrameType::Goaway => {
+                let (fields, debug_data) = payload.split_first_chunk::<8>()
+                    .ok_or(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                let [a, b, c, d, e, f, g, h] = *fields;
+                Self::Goaway {
+                    last_stream_id: u32::from_be_bytes([a, b, c, d]) & 0x7fff_ffff,
+                    error_code: ErrorCode(u32::from_be_bytes([e, f, g, h])),
+                    debug_data,
+                }
+            }
+            FrameType::WindowUpdate => {
+                let increment: [u8; 4] = payload.try_into().map_err(|_| Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+                let increment = u32::from_be_bytes(increment) & 0x7fff_ffff;
+                if increment == 0 {
+                    return Err(match stream_id {
+                        0 => Http2Error::Connection(ErrorCode::PROTOCOL_ERROR),
+                        _ => Http2Error::Stream(stream_id, ErrorCode::PROTOCOL_ERROR),
+                    });
+                }
+                Self::WindowUpdate { stream_id, increment }
+            }
+            FrameType::Continuation => {
+                Self::Continuation { stream_id, end_headers: flags.contains(Flags::END_HEADERS), fragment: payload }
+            }
+            FrameType::Unknown(_) => Self::Unknown { header, payload },
+        })
+    }
+
+    /// the stream the frame belongs to, 0 for the connection
+    pub fn stream_id(&self) -> u32 {
+        match *self {
+            Self::Data { stream_id, .. } | Self::Headers { stream_id, .. } | Self::Priority { stream_id, .. }
+                | Self::RstStream { stream_id, .. } | Self::PushPromise { stream_id, .. }
+                | Self::WindowUpdate { stream_id, .. } | Self::Continuation { stream_id, .. } => stream_id,
+            Self::Settings { .. } | Self::Ping { .. } | Self::Goaway { .. } => 0,
+            Self::Unknown { header, .. } => header.stream_id,
+        }
+    }
+
+    /// the bytes of a `DATA` frame counted against flow control windows, including any padding
+    pub fn flow_controlled_len(&self) -> u32 {
+        match *self {
+            Self::Data { data, pad_len, .. } => data.len() as u32 + pad_len.map_or(0, |pad_len| pad_len as u32 + 1),
+            _ => 0,
+        }
+    }
+
+    /// the header of the frame when written, the length is only valid for payloads within [`MAX_FRAME_SIZE`]
+    pub fn header(&self) -> FrameHeader {
+        let (ty, flags, stream_id, len) = self.header_fields();
+        FrameHeader::new(len as u32, ty, flags, stream_id)
+    }
+
+    // the type, flags, stream id and payload length, which may not fit in a frame header
+    fn header_fields(&self) -> (FrameType, Flags, u32, usize) {
+        let flag = |set: bool, flag: Flags| if set { flag } else { Flags::default() };
+        let padded = |pad_len: Option<u8>| flag(pad_len.is_some(), Flags::PADDED);
+        let padding = |pad_len: Option<u8>| pad_len.map_or(0, |pad_len| pad_len as usize + 1);
+        match *self {
+            Self::Data { stream_id, end_stream, data, pad_len } => {
+                (FrameType::Data, flag(end_stream, Flags::END_STREAM) | padded(pad_len), stream_id, data.len() + padding(pad_len))
+            }
+            Self::Headers { stream_id, end_stream, end_headers, priority, fragment, pad_len } => {
+                let flags = flag(end_stream, Flags::END_STREAM) | flag(end_headers, Flags::END_HEADERS)
+                    | flag(priority.is_some(), Flags::PRIORITY) | padded(pad_len);
+                let len = fragment.len() + padding(pad_len) + if priority.is_some() { 5 } else { 0 };
+                (FrameType::Headers, flags, stream_id, len)
+            }
+            Self::Priority { stream_id, .. } => (FrameType::Priority, Flags::default(), stream_id, 5),
+            Self::RstStream { stream_id, .. } => (FrameType::RstStream, Flags::default(), stream_id, 4),
+            Self::Settings { ack, settings } => (FrameType::Settings, flag(ack, Flags::ACK), 0, settings.0.len()),
+            Self::PushPromise { stream_id, end_headers, fragment, pad_len, .. } => {
+                let flags = flag(end_headers, Flags::END_HEADERS) | padded(pad_len);
+                (FrameType::PushPromise, flags, stream_id, 4 + fragment.len() + padding(pad_len))
+            }
+            Self::Ping { ack, .. } => (FrameType::Ping, flag(ack, Flags::ACK), 0, 8),
+            Self::Goaway { debug_data, .. } => (FrameType::Goaway, Flags::default(), 0, 8 + debug_data.len()),
+            Self::WindowUpdate { stream_id, .. } => (FrameType::WindowUpdate, Flags::default(), stream_id, 4),
+            Self::Continuation { stream_id, end_headers, fragment } => {
+                (FrameType::Continuation, flag(end_headers, Flags::END_HEADERS), stream_id, fragment.len())
+            }
+            Self::Unknown { header, payload } => (header.ty, header.flags, header.stream_id, payload.len()),
+        }
+    }
+
+    /// the length of the whole frame when written
+    pub fn encoded_len(&self) -> usize { FRAME_HEADER_LEN + self.header_fields().3 }
+
+    /// write the frame to the start of `out`, returns the frame's length
+    ///
+    /// the payload has to fit within the peer's `SETTINGS_MAX_FRAME_SIZE`, longer header blocks are split across
+    /// `CONTINUATION` frames. a payload over [`MAX_FRAME_SIZE`] can't be written at all
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, Http2Error> {
+        let len = self.encoded_len();
+        if len - FRAME_HEADER_LEN > MAX_FRAME_SIZE as usize { return Err(Http2Error::FrameTooLarge); }
+        let out = out.get_mut(..len).ok_or(Http2Error::BufferTooSmall)?;
+        self.header().write(out)?;
+        let mut payload = Writer { out: &mut out[FRAME_HEADER_LEN..], pos: 0 };
+        match *self {
+            Self::Data { data, pad_len, .. } => payload.padded(pad_len, &[data]),
+            Self::Headers { priority, fragment, pad_len, .. } => match priority {
+                Some(priority) => payload.padded(pad_len, &[&priority.bytes(), fragment]),
+                None => payload.padded(pad_len, &[fragment]),
+            },
+            Self::Priority { priority, .. } => payload.put(&priority.bytes()),
+            Self::RstStream { error_code, .. } => payload.put(&error_code.0.to_be_bytes()),
+            Self::Settings { settings, .. } => payload.put(settings.0),
+            Self::PushPromise { promised_stream_id, fragment, pad_len, .. } => {
+                payload.padded(pad_len, &[&(promised_stream_id & 0x7fff_ffff).to_be_bytes(), fragment]);
+            }
+            Self::Ping { data, .. } => payload.put(&data),
+            Self::Goaway { last_stream_id, error_code, debug_data } => {
+                payload.put(&(last_stream_id & 0x7fff_ffff).to_be_bytes());
+                payload.put(&error_code.0.to_be_bytes());
+                payload.put(debug_data);
+            }
+            Self::WindowUpdate { increment, .. } => payload.put(&(increment & 0x7fff_ffff).to_be_bytes()),
+            Self::Continuation { fragment, .. } => payload.put(fragment),
+            Self::Unknown { payload: unknown, .. } => payload.put(unknown),
+        }
+        Ok(len)
+    }
+}
+
+/// write a `SETTINGS` frame with the given parameters, returns the frame's length
+pub fn write_settings(settings: &[Setting], out: &mut [u8]) -> Result<usize, Http2Error> {
+    if settings.len() * 6 > MAX_FRAME_SIZE as usize { return Err(Http2Error::FrameTooLarge); }
+    let len = FRAME_HEADER_LEN + settings.len() * 6;
+    let out = out.get_mut(..len).ok_or(Http2Error::BufferTooSmall)?;
+    FrameHeader::new((settings.len() * 6) as u32, FrameType::Settings, Flags::default(), 0).write(out)?;
+    for (chunk, setting) in out[FRAME_HEADER_LEN..].chunks_exact_mut(6).zip(settings) {
+        chunk.copy_from_slice(&setting.bytes());
+    }
+    Ok(len)
+}
+
+/// the state of the client connection preface at the start of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefaceMatch {
+    /// the input starts with the whole preface
+    Matched,
+    /// the input is a prefix of the preface so far
+    Incomplete,
+    /// the input isn't an http/2 connection preface
+    Mismatch,
+}
+
+/// the client connection preface (rfc 9113 section 3.4), followed by a `SETTINGS` frame
+pub const PREFACE: &[u8; 24] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// check whether a connection starts with the client connection preface, so a server can tell a client with prior
+/// knowledge of http/2 from an http/1.1 request for [`parse_request`](crate::parse_request)
+pub fn match_preface(input: &[u8]) -> PrefaceMatch {
+    let len = input.len().min(PREFACE.len());
+    match (input[..len] == PREFACE[..len], len == PREFACE.len()) {
+        (false, _) => PrefaceMatch::Mismatch,
+        (true, true) => PrefaceMatch::Matched,
+        (true, false) => PrefaceMatch::Incomplete,
+    }
+}
+
+// split the padding off of a payload with the `PADDED` flag
+fn unpad(payload: &[u8], flags: Flags) -> Result<(&[u8], Option<u8>), Http2Error> {
+    if !flags.contains(Flags::PADDED) { return Ok((payload, None)); }
+    let (&pad_len, rest) = payload.split_first().ok_or(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR))?;
+    // padding as long as the rest of the payload is a protocol error
+    let end = rest.len().checked_sub(pad_len as usize).ok_or(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR))?;
+    Ok((&rest[..end], Some(pad_len)))
+}
+
+struct Writer<'o> {
+    out: &'o mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.out[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn padded(&mut self, pad_len: Option<u8>, parts: &[&[u8]]) {
+        if let Some(pad_len) = pad_len { self.put(&[pad_len]); }
+        for part in parts {
+            self.put(part);
+        }
+        let padding = pad_len.unwrap_or(0) as usize;
+        self.out[self.pos..self.pos + padding].fill(0);
+        self.pos += padding;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let priority = Priority { exclusive: true, dependency: 3, weight: 15 };
+        let frames = [
+            Frame::Data { stream_id: 1, end_stream: true, data: b"hello", pad_len: None },
+            Frame::Data { stream_id: 1, end_stream: false, data: b"", pad_len: Some(3) },
+            Frame::Headers { stream_id: 5, end_stream: false, end_headers: true, priority: Some(priority), fragment: b"\x82", pad_len: Some(2) },
+            Frame::Headers { stream_id: 5, end_stream: true, end_headers: false, priority: None, fragment: b"\x82\x84", pad_len: None },
+            Frame::Priority { stream_id: 7, priority },
+            Frame::RstStream { stream_id: 7, error_code: ErrorCode::CANCEL },
+            Frame::PushPromise { stream_id: 1, end_headers: true, promised_stream_id: 2, fragment: b"\x82", pad_len: Some(1) },
+            Frame::Ping { ack: true, data: *b"12345678" },
+            Frame::Goaway { last_stream_id: MAX_WINDOW_SIZE, error_code: ErrorCode::ENHANCE_YOUR_CALM, debug_data: b"slow down" },
+            Frame::WindowUpdate { stream_id: 0, increment: MAX_WINDOW_SIZE },
+            Frame::Continuation { stream_id: 5, end_headers: true, fragment: b"\x86" },
+            Frame::Unknown { header: FrameHeader::new(2, FrameType::Unknown(0xa), Flags(0x3), 9), payload: b"ab" },
+        ];
+        for frame in frames {
+            let mut out = [0; 64];
+            let len = frame.write(&mut out).unwrap();
+            assert_eq!(len, frame.encoded_len());
+            assert_eq!(Frame::parse(&out[..len], DEFAULT_MAX_FRAME_SIZE), Ok((frame, len)));
+            assert_eq!(Frame::parse(&out[..len - 1], DEFAULT_MAX_FRAME_SIZE), Err(Http2Error::Incomplete(len)));
+            assert_eq!(frame.write(&mut out[..len - 1]), Err(Http2Error::BufferTooSmall));
+        }
+
+        let settings = [Setting::HeaderTableSize(0), Setting::EnablePush(false), Setting::Unknown(0xff, 1)];
+        let mut out = [0; 64];
+        let len = write_settings(&settings, &mut out).unwrap();
+        let (frame, _) = Frame::parse(&out[..len], DEFAULT_MAX_FRAME_SIZE).unwrap();
+        let Frame::Settings { ack: false, settings: parsed } = frame else { panic!("{frame:?}") };
+        assert!(parsed.eq(settings));
+        let mut rewritten = [0; 64];
+        assert_eq!(frame.write(&mut rewritten), Ok(len));
+        assert_eq!(rewritten[..len], out[..len]);
+    }
+
+    #[test]
+    fn invalid_frames_are_rejected() {
+        // padding as long as the payload
+        let frame = b"\0\0\x02\0\x08\0\0\0\x01\x02\0";
+        assert_eq!(Frame::parse(frame, DEFAULT_MAX_FRAME_SIZE), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        // `DATA` on stream 0
+        let frame = b"\0\0\0\0\0\0\0\0\0";
+        assert_eq!(Frame::parse(frame, DEFAULT_MAX_FRAME_SIZE), Err(Http2Error::Connection(ErrorCode::PROTOCOL_ERROR)));
+        // longer than advertised
+        let frame = b"\0\x40\x01\0\0\0\0\0\x01";
+        assert_eq!(Frame::parse(frame, DEFAULT_MAX_FRAME_SIZE), Err(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR)));
+        // a `SETTINGS` acknowledgment with a payload
+        let frame = b"\0\0\x06\x04\x01\0\0\0\0\0\x01\0\0\0\0";
+        assert_eq!(Frame::parse(frame, DEFAULT_MAX_FRAME_SIZE), Err(Http2Error::Connection(ErrorCode::FRAME_SIZE_ERROR)));
+    }
+
+    #[test]
+    fn oversized_payload_is_an_error() {
+        let data = vec![0; MAX_FRAME_SIZE as usize + 1];
+        let frame = Frame::Data { stream_id: 1, end_stream: false, data: &data, pad_len: None };
+        let mut out = vec![0; frame.encoded_len()];
+        assert_eq!(frame.encoded_len(), FRAME_HEADER_LEN + data.len());
+        assert_eq!(frame.write(&mut out), Err(Http2Error::FrameTooLarge));
+
+        let frame = Frame::Data { stream_id: 1, end_stream: false, data: &data[1..], pad_len: None };
+        assert_eq!(frame.write(&mut out), Ok(FRAME_HEADER_LEN + MAX_FRAME_SIZE as usize));
+        assert_eq!(FrameHeader::parse(&out).unwrap().length(), MAX_FRAME_SIZE);
+    }
+}
@@ -0,0 +1,625 @@
+use crate::{ Header, HeaderName, StandardHeaderName };
+use super::frame::{ ErrorCode, Http2Error };
+use super::huffman::{ self, HuffmanError };
+
+/// the size of the dynamic table before any `SETTINGS_HEADER_TABLE_SIZE`
+pub const DEFAULT_TABLE_SIZE: usize = 4096;
+
+// the overhead of every entry of the dynamic table (rfc 7541 section 4.1)
+pub(crate) const ENTRY_OVERHEAD: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpackError {
+    /// the header block ends in the middle of a field
+    Truncated,
+    /// an integer doesn't fit in 32 bits, or in 62 bits for the stream ids and insert counts of qpack
+    IntegerOverflow,
+    /// an index that isn't in the static or dynamic table
+    InvalidIndex,
+    InvalidHuffman,
+    /// a decoded name or value isn't utf-8
+    InvalidUtf8,
+    /// a dynamic table size update above the limit, after the first field or missing after the limit was lowered
+    InvalidTableSizeUpdate,
+    /// the output buffer is too small
+    BufferTooSmall,
+    /// there is no room left in the header buffer
+    HeaderBufferFull,
+    /// a decoded name with uppercase letters, the message is malformed (rfc 9113 section 8.2.1) but the whole block
+    /// was decoded, so the dynamic table is still in sync and only the stream has to be reset
+    UppercaseName,
+}
+
+impl From<HuffmanError> for HpackError {
+    fn from(error: HuffmanError) -> Self {
+        match error {
+            HuffmanError::Invalid => Self::InvalidHuffman,
+            HuffmanError::BufferTooSmall => Self::BufferTooSmall,
+        }
+    }
+}
+
+/// the compression state is lost after any error, so it ends the connection
+impl From<HpackError> for Http2Error {
+    fn from(_: HpackError) -> Self { Self::Connection(ErrorCode::COMPRESSION_ERROR) }
+}
+
+/// the static table (rfc 7541 appendix A), indexed from 1
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""), (":method", "GET"), (":method", "POST"), (":path", "/"), (":path", "/index.html"),
+    (":scheme", "http"), (":scheme", "https"), (":status", "200"), (":status", "204"), (":status", "206"),
+    (":status", "304"), (":status", "400"), (":status", "404"), (":status", "500"), ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"), ("accept-language", ""), ("accept-ranges", ""), ("accept", ""),
+    ("access-control-allow-origin", ""), ("age", ""), ("allow", ""), ("authorization", ""), ("cache-control", ""),
+    ("content-disposition", ""), ("content-encoding", ""), ("content-language", ""), ("content-length", ""),
+    ("content-location", ""), ("content-range", ""), ("content-type", ""), ("cookie", ""), ("date", ""),
+    ("etag", ""), ("expect", ""), ("expires", ""), ("from", ""), ("host", ""), ("if-match", ""),
+    ("if-modified-since", ""), ("if-none-match", ""), ("if-range", ""), ("if-unmodified-since", ""),
+    ("last-modified", ""), ("link", ""), ("location", ""), ("max-forwards", ""), ("proxy-authenticate", ""),
+    ("proxy-authorization", ""), ("range", ""), ("referer", ""), ("refresh", ""), ("retry-after", ""),
+    ("server", ""), ("set-cookie", ""), ("strict-transport-security", ""), ("transfer-encoding", ""),
+    ("user-agent", ""), ("vary", ""), ("via", ""), ("www-authenticate", ""),
+];
+
+/// the header name for a lowercase http/2 name, standard headers are mapped onto [`StandardHeaderName`]
+pub(crate) fn header_name(name: &str) -> HeaderName<'_> {
+    match StandardHeaderName::from_ignore_case(name) {
+        Some(name) => HeaderName::Standard(name),
+        None => HeaderName::Custom(name),
+    }
+}
+
+/// headers whose values are sent as never-indexed literals, so they aren't exposed to compression attacks or kept
+/// in an intermediary's table
+pub(crate) fn is_sensitive(name: &str) -> bool {
+    [StandardHeaderName::Authoritzation, StandardHeaderName::ProxyAuthorization, StandardHeaderName::Cookie,
+        StandardHeaderName::SetCookie].iter().any(|header| header.as_str().eq_ignore_ascii_case(name))
+}
+
+/// a table of recently sent fields, the entries are stored newest first as `name length, value length, name,
+/// value` in a fixed buffer of `N` bytes, which always fits a table of at most `N` bytes
+#[derive(Debug, Clone)]
+pub(crate) struct DynamicTable<const N: usize> {
+    buf: [u8; N],
+    used: usize,
+    pub(crate) size: usize,
+    pub(crate) max_size: usize,
+}
+
+impl<const N: usize> DynamicTable<N> {
+    pub(crate) fn new(max_size: usize) -> Self {
+        Self { buf: [0; N], used: 0, size: 0, max_size: max_size.min(N) }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        let mut rest = &self.buf[..self.used];
+        core::iter::from_fn(move || {
+            let (lengths, entry) = rest.split_first_chunk::<8>()?;
+            let [a, b, c, d, e, f, g, h] = *lengths;
+            let name_len = u32::from_be_bytes([a, b, c, d]) as usize;
+            let value_len = u32::from_be_bytes([e, f, g, h]) as usize;
+            let (name, entry) = entry.split_at(name_len);
+            let (value, entry) = entry.split_at(value_len);
+            rest = entry;
+            Some((name, value))
+        })
+    }
+
+    /// the entry at `index`, counted from 0 for the newest
+    pub(crate) fn get(&self, index: usize) -> Option<(&[u8], &[u8])> { self.entries().nth(index) }
+
+    pub(crate) fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size.min(N);
+        while self.size > self.max_size {
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        let Some((name, value)) = self.entries().last() else { return };
+        let entry_len = 8 + name.len() + value.len();
+        self.size -= name.len() + value.len() + ENTRY_OVERHEAD;
+        self.used -= entry_len;
+    }
+
+    /// add an entry with its name lowercased, evicting the oldest entries to make room
+    pub(crate) fn insert(&mut self, name: &[u8], value: &[u8]) {
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        // an entry larger than the whole table just empties it
+        if size > self.max_size {
+            self.used = 0;
+            self.size = 0;
+            return;
+        }
+        while self.size + size > self.max_size {
+            self.evict();
+        }
+        let entry_len = 8 + name.len() + value.len();
+        self.buf.copy_within(..self.used, entry_len);
+        self.buf[..4].copy_from_slice(&(name.len() as u32).to_be_bytes());
+        self.buf[4..8].copy_from_slice(&(value.len() as u32).to_be_bytes());
+        self.buf[8..8 + name.len()].copy_from_slice(name);
+        self.buf[8..8 + name.len()].make_ascii_lowercase();
+        self.buf[8 + name.len()..entry_len].copy_from_slice(value);
+        self.used += entry_len;
+        self.size += size;
+    }
+
+    /// the index of an entry with the same name and value, or else of one with the same name
+    pub(crate) fn find(&self, name: &str, value: &str) -> (Option<usize>, Option<usize>) {
+        let mut name_index = None;
+        for (index, (entry_name, entry_value)) in self.entries().enumerate() {
+            if !entry_name.eq_ignore_ascii_case(name.as_bytes()) { continue; }
+            if entry_value == value.as_bytes() { return (Some(index), Some(index)); }
+            name_index.get_or_insert(index);
+        }
+        (None, name_index)
+    }
+}
+
+/// decodes header blocks (rfc 7541 section 3), keeping a dynamic table of up to `N` bytes
+///
+/// an `N` below [`DEFAULT_TABLE_SIZE`] has to be advertised in `SETTINGS_HEADER_TABLE_SIZE` before the peer sends
+/// any header block
+#[derive(Debug, Clone)]
+pub struct HpackDecoder<const N: usize = DEFAULT_TABLE_SIZE> {
+    table: DynamicTable<N>,
+    max_size: usize,
+    update_required: bool,
+}
+
+impl<const N: usize> Default for HpackDecoder<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> HpackDecoder<N> {
+    pub fn new() -> Self {
+        Self { table: DynamicTable::new(DEFAULT_TABLE_SIZE), max_size: DEFAULT_TABLE_SIZE.min(N), update_required: false }
+    }
+
+    /// the largest table the peer's encoder may use, advertised as `SETTINGS_HEADER_TABLE_SIZE` and capped at `N`
+    pub fn max_table_size(&self) -> usize { self.max_size }
+
+    /// set the limit once the peer acknowledged the `SETTINGS_HEADER_TABLE_SIZE`, lowering it requires the next
+    /// header block to start with a dynamic table size update
+    pub fn set_max_table_size(&mut self, max_size: usize) {
+        let max_size = max_size.min(N);
+        if max_size < self.table.max_size { self.update_required = true; }
+        self.max_size = max_size;
+    }
+
+    /// decode a whole header block into the unused (empty) slots of `headers`, returns the number of headers
+    ///
+    /// names and values that had to be copied, from the dynamic table or out of huffman encoding, are written to
+    /// `buf`, others are borrowed from the block or the static table
+    pub fn decode<'b>(
+        &mut self,
+        mut block: &'b [u8],
+        buf: &'b mut [u8],
+        headers: &mut [Header<'b>],
+    ) -> Result<usize, HpackError> {
+        let mut strings = StringBuf(buf);
+        let mut slots = headers.iter_mut().filter(|header| header.name().as_str().is_empty());
+        let mut count = 0;
+        let mut first = true;
+        let mut uppercase = false;
+        while let Some(&byte) = block.first() {
+            if byte & 0b1110_0000 == 0b0010_0000 {
+                if !first { return Err(HpackError::InvalidTableSizeUpdate); }
+                let (size, len) = decode_int(block, 5)?;
+                if size > self.max_size { return Err(HpackError::InvalidTableSizeUpdate); }
+                self.table.set_max_size(size);
+                self.update_required = false;
+                block = &block[len..];
+                continue;
+            }
+            if self.update_required { return Err(HpackError::InvalidTableSizeUpdate); }
+            first = false;
+
+            let (name, value) = if byte & 0x80 != 0 {
+                let (index, len) = decode_int(block, 7)?;
+                block = &block[len..];
+                self.entry(index, &mut strings)?
+            } else {
+                let (prefix, index) = match byte & 0b1100_0000 {
+                    0b0100_0000 => (6, true),
+                    _ => (4, false),
+                };
+                let (name_index, len) = decode_int(block, prefix)?;
+                block = &block[len..];
+                let name = match name_index {
+                    0 => {
+                        let (name, len) = decode_string(block, 7, &mut strings)?;
+                        block = &block[len..];
+                        name
+                    }
+                    name_index => self.entry(name_index, &mut strings)?.0,
+                };
+                let (value, len) = decode_string(block, 7, &mut strings)?;
+                block = &block[len..];
+                if index { self.table.insert(name.as_bytes(), value.as_bytes()); }
+                (name, value)
+            };
+
+            // checked on the raw name, `header_name` folds the case of standard names
+            uppercase |= name.bytes().any(|b| b.is_ascii_uppercase());
+            let slot = slots.next().ok_or(HpackError::HeaderBufferFull)?;
+            *slot = Header::new(header_name(name), value);
+            count += 1;
+        }
+        // a lowered limit has to be acknowledged even by an empty block
+        if self.update_required { return Err(HpackError::InvalidTableSizeUpdate); }
+        if uppercase { return Err(HpackError::UppercaseName); }
+        Ok(count)
+    }
+
+    fn entry<'b>(&self, index: usize, strings: &mut StringBuf<'b>) -> Result<(&'b str, &'b str), HpackError> {
+        match index {
+            0 => Err(HpackError::InvalidIndex),
+            1..=61 => Ok(STATIC_TABLE[index - 1]),
+            index => {
+                let (name, value) = self.table.get(index - 62).ok_or(HpackError::InvalidIndex)?;
+                Ok((strings.push(name)?, strings.push(value)?))
+            }
+        }
+    }
+}
+
+/// encodes header blocks, keeping a dynamic table of up to `N` bytes
+///
+/// an error leaves the encoder out of step with the peer's decoder, so the connection can't be used any more
+#[derive(Debug, Clone)]
+pub struct HpackEncoder<const N: usize = DEFAULT_TABLE_SIZE> {
+    table: DynamicTable<N>,
+    // the smallest and the last table size set since the last header block
+    pending_update: Option<(usize, usize)>,
+}
+
+impl<const N: usize> Default for HpackEncoder<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> HpackEncoder<N> {
+    pub fn new() -> Self {
+        Self { table: DynamicTable::new(DEFAULT_TABLE_SIZE), pending_update: None }
+    }
+
+    /// apply the peer's `SETTINGS_HEADER_TABLE_SIZE`, capped at `N`, the change is signalled at the start of the
+    /// next header block
+    pub fn set_max_table_size(&mut self, max_size: usize) {
+        let max_size = max_size.min(N);
+        let smallest = self.pending_update.map_or(max_size, |(smallest, _)| smallest.min(max_size));
+        self.pending_update = Some((smallest, max_size));
+    }
+
+    /// encode `headers` as a header block to the start of `out`, returns the block's length
+    ///
+    /// `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` are never indexed
+    pub fn encode(&mut self, headers: &[Header<'_>], out: &mut [u8]) -> Result<usize, HpackError> {
+        let mut len = 0;
+        for header in headers.iter().filter(|header| !header.name().as_str().is_empty()) {
+            let name = header.name().as_str();
+            len += self.encode_field(name, header.value(), is_sensitive(name), &mut out[len..])?;
+        }
+        if len == 0 { len = self.write_table_size_update(&mut BlockWriter::new(out))?; }
+        Ok(len)
+    }
+
+    /// encode a single field, e.g. a pseudo-header, continuing a header block, returns the field's length
+    ///
+    /// the name is sent lowercased, `sensitive` fields are never indexed
+    pub fn encode_field(&mut self, name: &str, value: &str, sensitive: bool, out: &mut [u8]) -> Result<usize, HpackError> {
+        let mut out = BlockWriter::new(out);
+        self.write_table_size_update(&mut out)?;
+
+        let (mut index, mut name_index) = (None, None);
+        for (i, &(entry_name, entry_value)) in STATIC_TABLE.iter().enumerate() {
+            if !entry_name.eq_ignore_ascii_case(name) { continue; }
+            name_index.get_or_insert(i + 1);
+            if entry_value == value {
+                index = Some(i + 1);
+                break;
+            }
+        }
+        if index.is_none() {
+            let (dynamic_index, dynamic_name_index) = self.table.find(name, value);
+            index = dynamic_index.map(|index| index + 62);
+            name_index = name_index.or(dynamic_name_index.map(|index| index + 62));
+        }
+
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        let (flags, prefix, insert) = match index {
+            Some(index) if !sensitive => {
+                out.int(0x80, 7, index)?;
+                return Ok(out.pos);
+            }
+            _ if sensitive => (0b0001_0000, 4, false),
+            _ if size <= self.table.max_size => (0b0100_0000, 6, true),
+            _ => (0, 4, false),
+        };
+        match name_index {
+            Some(name_index) => out.int(flags, prefix, name_index)?,
+            None => {
+                out.int(flags, prefix, 0)?;
+                out.string(0, 7, name.as_bytes(), true)?;
+            }
+        }
+        out.string(0, 7, value.as_bytes(), false)?;
+        if insert { self.table.insert(name.as_bytes(), value.as_bytes()); }
+        Ok(out.pos)
+    }
+
+    fn write_table_size_update(&mut self, out: &mut BlockWriter<'_>) -> Result<usize, HpackError> {
+        let Some((smallest, max_size)) = self.pending_update else { return Ok(0) };
+        if smallest < max_size {
+            out.int(0b0010_0000, 5, smallest)?;
+            self.table.set_max_size(smallest);
+        }
+        out.int(0b0010_0000, 5, max_size)?;
+        self.table.set_max_size(max_size);
+        self.pending_update = None;
+        Ok(out.pos)
+    }
+}
+
+/// decode an integer with an `prefix` bit prefix (rfc 7541 section 5.1), returns the value and its length
+pub(crate) fn decode_int(input: &[u8], prefix: u8) -> Result<(usize, usize), HpackError> {
+    decode_int_up_to(input, prefix, u32::MAX as u64).map(|(value, len)| (value as usize, len))
+}
+
+/// decode an integer of up to 62 bits, like the stream ids that qpack refers to (rfc 9204 section 4.1.1)
+pub(crate) fn decode_int62(input: &[u8], prefix: u8) -> Result<(u64, usize), HpackError> {
+    decode_int_up_to(input, prefix, (1 << 62) - 1)
+}
+
+fn decode_int_up_to(input: &[u8], prefix: u8, max: u64) -> Result<(u64, usize), HpackError> {
+    let (&first, rest) = input.split_first().ok_or(HpackError::Truncated)?;
+    let max_prefix = (1u16 << prefix) as u64 - 1;
+    let mut value = (first as u64) & max_prefix;
+    if value < max_prefix { return Ok((value, 1)); }
+    // no more continuation bytes than the bits of `max` need
+    let max_bits = (u64::BITS - max.leading_zeros()) as usize;
+    for (i, &byte) in rest.iter().enumerate() {
+        if i * 7 >= max_bits { return Err(HpackError::IntegerOverflow); }
+        value += ((byte & 0x7f) as u64) << (i * 7);
+        if value > max { return Err(HpackError::IntegerOverflow); }
+        if byte & 0x80 == 0 { return Ok((value, i + 2)); }
+    }
+    Err(HpackError::Truncated)
+}
+
+/// decode a string literal with an `prefix` bit length prefix, preceded by the huffman flag (rfc 7541 section
+/// 5.2), returns the string and the literal's length
+pub(crate) fn decode_string<'b>(
+    input: &'b [u8],
+    prefix: u8,
+    strings: &mut StringBuf<'b>,
+) -> Result<(&'b str, usize), HpackError> {
+    let huffman = input.first().is_some_and(|byte| byte >> prefix & 1 == 1);
+    let (len, int_len) = decode_int(input, prefix)?;
+    let string = input.get(int_len..int_len + len).ok_or(HpackError::Truncated)?;
+    let string = match huffman {
+        true => strings.push_huffman(string)?,
+        false => core::str::from_utf8(string).map_err(|_| HpackError::InvalidUtf8)?,
+    };
+    Ok((string, int_len + len))
+}
+
+/// the rest of the buffer that decoded names and values are written to
+pub(crate) struct StringBuf<'b>(pub(crate) &'b mut [u8]);
+
+impl<'b> StringBuf<'b> {
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Result<&'b str, HpackError> {
+        if bytes.len() > self.0.len() { return Err(HpackError::BufferTooSmall); }
+        let (string, rest) = core::mem::take(&mut self.0).split_at_mut(bytes.len());
+        self.0 = rest;
+        string.copy_from_slice(bytes);
+        core::str::from_utf8(string).map_err(|_| HpackError::InvalidUtf8)
+    }
+
+    fn push_huffman(&mut self, encoded: &[u8]) -> Result<&'b str, HpackError> {
+        let len = huffman::decode(encoded, self.0)?;
+        let (string, rest) = core::mem::take(&mut self.0).split_at_mut(len);
+        self.0 = rest;
+        core::str::from_utf8(string).map_err(|_| HpackError::InvalidUtf8)
+    }
+}
+
+/// writes the integers and string literals of a header block
+pub(crate) struct BlockWriter<'o> {
+    out: &'o mut [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'o> BlockWriter<'o> {
+    pub(crate) fn new(out: &'o mut [u8]) -> Self { Self { out, pos: 0 } }
+
+    fn byte(&mut self, byte: u8) -> Result<(), HpackError> {
+        *self.out.get_mut(self.pos).ok_or(HpackError::BufferTooSmall)? = byte;
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// an integer with a `prefix` bit prefix, the bits above the prefix in the first byte are `flags`
+    pub(crate) fn int(&mut self, flags: u8, prefix: u8, value: usize) -> Result<(), HpackError> {
+        self.int64(flags, prefix, value as u64)
+    }
+
+    /// an integer that may not fit in a `usize`
+    pub(crate) fn int64(&mut self, flags: u8, prefix: u8, value: u64) -> Result<(), HpackError> {
+        let max_prefix = (1u64 << prefix) - 1;
+        if value < max_prefix { return self.byte(flags | value as u8); }
+        self.byte(flags | max_prefix as u8)?;
+        let mut value = value - max_prefix;
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.byte(value as u8)
+    }
+
+    /// a string literal, huffman encoded when that is shorter
+    pub(crate) fn string(&mut self, flags: u8, prefix: u8, string: &[u8], lowercase: bool) -> Result<(), HpackError> {
+        let bytes = || string.iter().map(move |byte| if lowercase { byte.to_ascii_lowercase() } else { *byte });
+        let huffman_len = huffman::encoded_len(bytes());
+        if huffman_len < string.len() {
+            self.int(flags | 1 << prefix, prefix, huffman_len)?;
+            self.pos += huffman::encode(bytes(), &mut self.out[self.pos..])?;
+            return Ok(());
+        }
+        self.int(flags, prefix, string.len())?;
+        let out = self.out.get_mut(self.pos..self.pos + string.len()).ok_or(HpackError::BufferTooSmall)?;
+        for (out, byte) in out.iter_mut().zip(bytes()) {
+            *out = byte;
+        }
+        self.pos += string.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+    use super::*;
+    use crate::header::EMPTY_HEADER;
+
+    const DATE_1: &str = "Mon, 21 Oct 2013 20:13:21 GMT";
+    const DATE_2: &str = "Mon, 21 Oct 2013 20:13:22 GMT";
+    const SET_COOKIE: &str = "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1";
+
+    // the requests of rfc 7541 appendix c.3 and c.4
+    const REQUESTS: [&[(&str, &str)]; 3] = [
+        &[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")],
+        &[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com"),
+            ("cache-control", "no-cache")],
+        &[(":method", "GET"), (":scheme", "https"), (":path", "/index.html"), (":authority", "www.example.com"),
+            ("custom-key", "custom-value")],
+    ];
+
+    // the responses of rfc 7541 appendix c.5 and c.6
+    const RESPONSES: [&[(&str, &str)]; 3] = [
+        &[(":status", "302"), ("cache-control", "private"), ("date", DATE_1), ("location", "https://www.example.com")],
+        &[(":status", "307"), ("cache-control", "private"), ("date", DATE_1), ("location", "https://www.example.com")],
+        &[(":status", "200"), ("cache-control", "private"), ("date", DATE_2), ("location", "https://www.example.com"),
+            ("content-encoding", "gzip"), ("set-cookie", SET_COOKIE)],
+    ];
+
+    fn hex(input: &str) -> Vec<u8> {
+        let digits: Vec<u8> = input.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits.chunks(2).map(|pair| u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap()).collect()
+    }
+
+    // decode each block in turn, checking the fields and the size of the dynamic table after it
+    fn decode_all<const N: usize>(blocks: [&str; 3], fields: [&[(&str, &str)]; 3], sizes: [usize; 3]) {
+        let mut decoder = HpackDecoder::<N>::new();
+        for ((block, fields), size) in blocks.into_iter().zip(fields).zip(sizes) {
+            let block = hex(block);
+            let mut buf = [0; 256];
+            let mut headers = [EMPTY_HEADER; 8];
+            assert_eq!(decoder.decode(&block, &mut buf, &mut headers), Ok(fields.len()));
+            for (header, (name, value)) in headers.iter().zip(fields) {
+                assert!(header.name().as_str().eq_ignore_ascii_case(name), "{name}");
+                assert_eq!(header.value(), *value);
+            }
+            assert_eq!(decoder.table.size, size);
+        }
+    }
+
+    fn encode_all<const N: usize>(blocks: [&str; 3], fields: [&[(&str, &str)]; 3]) {
+        let mut encoder = HpackEncoder::<N>::new();
+        for (block, fields) in blocks.into_iter().zip(fields) {
+            let mut out = [0; 256];
+            let mut len = 0;
+            for (name, value) in fields {
+                len += encoder.encode_field(name, value, false, &mut out[len..]).unwrap();
+            }
+            assert_eq!(out[..len], hex(block)[..]);
+        }
+    }
+
+    const C3: [&str; 3] = [
+        "8286 8441 0f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+        "8286 84be 5808 6e6f 2d63 6163 6865",
+        "8287 85bf 400a 6375 7374 6f6d 2d6b 6579 0c63 7573 746f 6d2d 7661 6c75 65",
+    ];
+    const C4: [&str; 3] = [
+        "8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff",
+        "8286 84be 5886 a8eb 1064 9cbf",
+        "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+    ];
+    const C5: [&str; 3] = [
+        "4803 3330 3258 0770 7269 7661 7465 611d 4d6f 6e2c 2032 3120 4f63 7420 3230 3133 2032 303a 3133 3a32 3120 474d
+         546e 1768 7474 7073 3a2f 2f77 7777 2e65 7861 6d70 6c65 2e63 6f6d",
+        "4803 3330 37c1 c0bf",
+        "88c1 611d 4d6f 6e2c 2032 3120 4f63 7420 3230 3133 2032 303a 3133 3a32 3220 474d 54c0 5a04 677a 6970 7738 666f
+         6f3d 4153 444a 4b48 514b 425a 584f 5157 454f 5049 5541 5851 5745 4f49 553b 206d 6178 2d61 6765 3d33 3630 303b
+         2076 6572 7369 6f6e 3d31",
+    ];
+    const C6: [&str; 3] = [
+        "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 66e0 82a6 2d1b ff6e 919d 29ad 1718 63c7
+         8f0b 97c8 e9ae 82ae 43d3",
+        "4883 640e ffc1 c0bf",
+        "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a 839b d9ab 77ad 94e7 821d d7f2 e6c7 b335
+         dfdf cd5b 3960 d5af 2708 7f36 72c1 ab27 0fb5 291f 9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+    ];
+
+    #[test]
+    fn requests_without_huffman() {
+        decode_all::<4096>(C3, REQUESTS, [57, 110, 164]);
+    }
+
+    #[test]
+    fn requests_with_huffman() {
+        decode_all::<4096>(C4, REQUESTS, [57, 110, 164]);
+        // the encoder uses huffman whenever it is shorter
+        encode_all::<4096>(C4, REQUESTS);
+    }
+
+    // a table of 256 bytes, so entries are evicted
+    #[test]
+    fn responses_without_huffman() {
+        decode_all::<256>(C5, RESPONSES, [222, 222, 215]);
+    }
+
+    #[test]
+    fn responses_with_huffman() {
+        decode_all::<256>(C6, RESPONSES, [222, 222, 215]);
+        // `307` is as long with huffman as without, so it is sent as it is
+        encode_all::<256>([C6[0], C5[1], C6[2]], RESPONSES);
+    }
+
+    #[test]
+    fn table_size_updates() {
+        let mut encoder = HpackEncoder::<256>::new();
+        let mut decoder = HpackDecoder::<256>::new();
+        let (mut out, mut buf) = ([0; 128], [0; 128]);
+        let headers = [Header::new(HeaderName::Custom("custom-key"), "custom-value")];
+        let len = encoder.encode(&headers, &mut out).unwrap();
+        decoder.decode(&out[..len], &mut buf, &mut [EMPTY_HEADER; 1]).unwrap();
+        assert_eq!(decoder.table.size, 54);
+
+        // lowering the limit evicts everything, and has to be signalled by the next block
+        decoder.set_max_table_size(32);
+        assert_eq!(decoder.decode(&out[..0], &mut buf, &mut []), Err(HpackError::InvalidTableSizeUpdate));
+        encoder.set_max_table_size(32);
+        let len = encoder.encode(&headers, &mut out).unwrap();
+        assert_eq!(out[0], 0x3f);
+        let mut headers = [EMPTY_HEADER; 1];
+        assert_eq!(decoder.decode(&out[..len], &mut buf, &mut headers), Ok(1));
+        assert_eq!(headers[0].value(), "custom-value");
+        assert_eq!((decoder.table.size, decoder.table.get(0)), (0, None));
+    }
+
+    #[test]
+    fn uppercase_names_are_rejected() {
+        let mut decoder = HpackDecoder::<256>::new();
+        let mut buf = [0; 64];
+        let mut headers = [EMPTY_HEADER; 2];
+        // a literal with incremental indexing of `Content-Type: x`, followed by a reference to it
+        let block = b"\x40\x0cContent-Type\x01x\xbe";
+        assert_eq!(decoder.decode(block, &mut buf, &mut headers), Err(HpackError::UppercaseName));
+        // the whole block was decoded, so the entry is in the table
+        assert_eq!(decoder.table.get(0), Some((&b"content-type"[..], &b"x"[..])));
+    }
+}
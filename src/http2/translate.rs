@@ -0,0 +1,280 @@
+use crate::{ Header, HeaderName, Request, Response, StandardHeaderName, Version };
+use crate::header::{ self, has_token, list_values, push_header };
+use super::pseudo::{ MalformedError, RequestPseudoHeaders, ResponsePseudoHeaders, check_headers };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranslateError {
+    /// the http/2 message is malformed, e.g. has a `TE` other than `trailers`
+    Malformed(MalformedError),
+    /// there is no room left in the header buffer
+    HeaderBufferFull,
+    /// the buffer for lowercased names, joined cookies or the `:path` is too small
+    BufferTooSmall,
+}
+
+impl From<MalformedError> for TranslateError {
+    fn from(error: MalformedError) -> Self { Self::Malformed(error) }
+}
+
+/// turn a decoded http/2 request into an http/1.1 one, the `:authority` becomes the `Host` unless there already is
+/// one, and `Cookie` crumbs are joined into a single header
+///
+/// joined cookies are written to `buf`
+pub fn request_to_http1<'r, B>(
+    pseudo: &RequestPseudoHeaders<'r>,
+    request: &mut Request<'r, '_, B>,
+    buf: &'r mut [u8],
+) -> Result<(), TranslateError> {
+    pseudo.apply(request);
+    request.version(Version::V11);
+    check_headers(request.headers)?;
+    if let (Some(authority), None) = (pseudo.authority, request.header(StandardHeaderName::Host)) {
+        if !push_header(request.headers, StandardHeaderName::Host, authority) { return Err(TranslateError::HeaderBufferFull); }
+    }
+    join_cookies(request.headers, buf)
+}
+
+/// turn a decoded http/2 response into an http/1.1 one
+pub fn response_to_http1<B>(pseudo: &ResponsePseudoHeaders, response: &mut Response<'_, '_, B>) -> Result<(), TranslateError> {
+    pseudo.apply(response);
+    response.version(Version::V11);
+    check_headers(response.headers)?;
+    Ok(())
+}
+
+/// turn an http/1.1 request into an http/2 one, returns the pseudo-headers to encode ahead of the headers
+///
+/// connection-specific headers, and those named by `Connection`, are removed, as is `Host` which becomes the
+/// `:authority`. a `TE` is kept as `trailers` if it includes it. `Cookie` headers are split into one header per
+/// crumb, and custom header names are lowercased into `buf`, as is a `:path` that needs a leading `/`. `scheme` is
+/// used for targets that don't carry one
+pub fn request_to_http2<'r, B>(
+    request: &mut Request<'r, '_, B>,
+    scheme: &'r str,
+    buf: &'r mut [u8],
+) -> Result<RequestPseudoHeaders<'r>, TranslateError> {
+    let path_len = RequestPseudoHeaders::path_buf_len(request.target());
+    let (path_buf, buf) = buf.split_at_mut_checked(path_len).ok_or(TranslateError::BufferTooSmall)?;
+    let pseudo = RequestPseudoHeaders::from_request(request, scheme, path_buf).ok_or(TranslateError::BufferTooSmall)?;
+    // the request is left unchanged if the crumbs or lowercased names don't fit, counted before any are removed
+    if header::unused_slots(request.headers) < extra_crumbs(request.headers) { return Err(TranslateError::HeaderBufferFull); }
+    if uppercase_len(request.headers) > buf.len() { return Err(TranslateError::BufferTooSmall); }
+    request.version(Version::V20);
+    strip_connection_headers(request.headers);
+    for header in request.headers.iter_mut().filter(|header| header.is(StandardHeaderName::Host)) {
+        *header = Header::default();
+    }
+    split_cookies(request.headers)?;
+    lowercase_names(request.headers, buf)?;
+    Ok(pseudo)
+}
+
+/// turn an http/1.1 response into an http/2 one, returns the pseudo-headers to encode ahead of the headers
+///
+/// connection-specific headers are removed and custom header names lowercased into `buf`, as for
+/// [`request_to_http2`]
+pub fn response_to_http2<'r, B>(
+    response: &mut Response<'r, '_, B>,
+    buf: &'r mut [u8],
+) -> Result<ResponsePseudoHeaders, TranslateError> {
+    let pseudo = ResponsePseudoHeaders::from_response(response);
+    if uppercase_len(response.headers) > buf.len() { return Err(TranslateError::BufferTooSmall); }
+    response.version(Version::V20);
+    strip_connection_headers(response.headers);
+    lowercase_names(response.headers, buf)?;
+    Ok(pseudo)
+}
+
+// remove the headers named by `Connection` first, then the connection-specific headers themselves
+fn strip_connection_headers(headers: &mut [Header<'_>]) {
+    for i in 0..headers.len() {
+        let name = headers[i].name().as_str();
+        // a sender of `TE` has to nominate it too, whether it is kept is up to the `trailers` rule below
+        let exempt = headers[i].is(StandardHeaderName::Connection) || headers[i].is(StandardHeaderName::TE);
+        let nominated = !name.is_empty() && !exempt
+            && list_values(headers, StandardHeaderName::Connection).any(|token| token.eq_ignore_ascii_case(name));
+        if nominated { headers[i] = Header::default(); }
+    }
+    let trailers = has_token(headers, StandardHeaderName::TE, "trailers");
+    for header in headers.iter_mut() {
+        let connection_header = [StandardHeaderName::Connection, StandardHeaderName::KeepAlive,
+            StandardHeaderName::TransferEncoding, StandardHeaderName::Upgrade].into_iter().any(|name| header.is(name))
+            || header.name().as_str().eq_ignore_ascii_case("proxy-connection");
+        if connection_header || (header.is(StandardHeaderName::TE) && !trailers) { *header = Header::default(); }
+    }
+    // only `trailers` is allowed in http/2, kept once
+    let mut te = headers.iter_mut().filter(|header| header.is(StandardHeaderName::TE));
+    if let Some(header) = te.next() { *header = Header::new(HeaderName::Standard(StandardHeaderName::TE), "trailers"); }
+    te.for_each(|header| *header = Header::default());
+}
+
+// the crumbs of a `Cookie` header value
+fn crumbs(value: &str) -> impl Iterator<Item = &str> {
+    value.split(';').map(str::trim).filter(|crumb| !crumb.is_empty())
+}
+
+// the number of headers splitting the cookies adds
+fn extra_crumbs(headers: &[Header<'_>]) -> usize {
+    let cookies = headers.iter().filter(|header| header.is(StandardHeaderName::Cookie));
+    cookies.map(|header| crumbs(header.value()).count().saturating_sub(1)).sum()
+}
+
+// split every `Cookie` into one header per crumb for better compression (rfc 9113 section 8.2.3)
+fn split_cookies(headers: &mut [Header<'_>]) -> Result<(), TranslateError> {
+    for i in 0..headers.len() {
+        if !headers[i].is(StandardHeaderName::Cookie) { continue; }
+        let mut crumbs = crumbs(headers[i].value());
+        headers[i] = Header::new(HeaderName::Standard(StandardHeaderName::Cookie), crumbs.next().unwrap_or_default());
+        for crumb in crumbs {
+            if !push_header(headers, StandardHeaderName::Cookie, crumb) { return Err(TranslateError::HeaderBufferFull); }
+        }
+    }
+    Ok(())
+}
+
+// join the `Cookie` crumbs into the first `Cookie` as `a=b; c=d`, an http/1.1 message has at most one
+fn join_cookies<'r>(headers: &mut [Header<'r>], buf: &'r mut [u8]) -> Result<(), TranslateError> {
+    if headers.iter().filter(|header| header.is(StandardHeaderName::Cookie)).count() < 2 { return Ok(()); }
+    let mut len = 0;
+    for crumb in headers.iter().filter(|header| header.is(StandardHeaderName::Cookie)).map(Header::value) {
+        let separator: &[u8] = if len == 0 { b"" } else { b"; " };
+        for part in [separator, crumb.as_bytes()] {
+            buf.get_mut(len..len + part.len()).ok_or(TranslateError::BufferTooSmall)?.copy_from_slice(part);
+            len += part.len();
+        }
+    }
+    let (joined, _) = buf.split_at_mut(len);
+    let joined = core::str::from_utf8(joined).map_err(|_| TranslateError::BufferTooSmall)?;
+    let mut cookies = headers.iter_mut().filter(|header| header.is(StandardHeaderName::Cookie));
+    if let Some(header) = cookies.next() { *header = Header::new(HeaderName::Standard(StandardHeaderName::Cookie), joined); }
+    cookies.for_each(|header| *header = Header::default());
+    Ok(())
+}
+
+// the length of the custom names with uppercase letters, which are lowercased into a buffer
+fn uppercase_len(headers: &[Header<'_>]) -> usize {
+    let names = headers.iter().filter_map(|header| match *header.name() {
+        HeaderName::Custom(name) if name.bytes().any(|b| b.is_ascii_uppercase()) => Some(name.len()),
+        _ => None,
+    });
+    names.sum()
+}
+
+// http/2 field names are lowercase, standard names are lowercased by the encoder
+fn lowercase_names<'r>(headers: &mut [Header<'r>], mut buf: &'r mut [u8]) -> Result<(), TranslateError> {
+    for header in headers.iter_mut() {
+        let HeaderName::Custom(name) = *header.name() else { continue };
+        if !name.bytes().any(|b| b.is_ascii_uppercase()) { continue; }
+        if name.len() > buf.len() { return Err(TranslateError::BufferTooSmall); }
+        let (lowercase, rest) = core::mem::take(&mut buf).split_at_mut(name.len());
+        buf = rest;
+        lowercase.copy_from_slice(name.as_bytes());
+        lowercase.make_ascii_lowercase();
+        let lowercase = core::str::from_utf8(lowercase).map_err(|_| TranslateError::BufferTooSmall)?;
+        *header = Header::new(HeaderName::Custom(lowercase), header.value());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_request;
+
+    #[test]
+    fn te_trailers_survives_connection_nomination() {
+        let mut headers = [crate::header::EMPTY_HEADER; 6];
+        let input = "POST /rpc HTTP/1.1\r\nHost: example.com\r\nConnection: TE\r\nTE: trailers\r\n\r\n";
+        let mut request = parse_request(input, &mut headers).unwrap();
+        let pseudo = request_to_http2(&mut request, "https", &mut []).unwrap();
+        assert_eq!(pseudo.authority, Some("example.com"));
+        assert_eq!(request.header(StandardHeaderName::TE).map(Header::value), Some("trailers"));
+        assert!(request.header(StandardHeaderName::Connection).is_none());
+        assert!(request.header(StandardHeaderName::Host).is_none());
+    }
+
+    #[test]
+    fn lowercase_names_are_translated() {
+        let mut headers = [crate::header::EMPTY_HEADER; 6];
+        let input = "POST /upload HTTP/1.1\r\nhost: example.com\r\ntransfer-encoding: chunked\r\nx-trace: 1\r\n\r\n";
+        let mut request = parse_request(input, &mut headers).unwrap();
+        let pseudo = request_to_http2(&mut request, "https", &mut []).unwrap();
+        assert_eq!(pseudo.authority, Some("example.com"));
+        let names: [&str; 6] = core::array::from_fn(|i| request.headers()[i].name().as_str());
+        assert_eq!(names, ["", "", "x-trace", "", "", ""]);
+    }
+
+    #[test]
+    fn cookies_are_split_only_if_they_fit() {
+        let input = "GET http://example.com?q HTTP/1.1\r\nConnection: close\r\nCookie: a=1; b=2;; c=3\r\n\r\n";
+        let mut headers = [crate::header::EMPTY_HEADER; 3];
+        let mut request = parse_request(input, &mut headers).unwrap();
+        let mut buf = [0; 3];
+        assert_eq!(request_to_http2(&mut request, "https", &mut buf), Err(TranslateError::HeaderBufferFull));
+        assert_eq!(request.version, Version::V11);
+        assert!(request.header(StandardHeaderName::Connection).is_some());
+        assert_eq!(request.header(StandardHeaderName::Cookie).map(Header::value), Some("a=1; b=2;; c=3"));
+
+        let mut headers = [crate::header::EMPTY_HEADER; 4];
+        let mut request = parse_request(input, &mut headers).unwrap();
+        let pseudo = request_to_http2(&mut request, "https", &mut buf).unwrap();
+        assert_eq!((pseudo.scheme, pseudo.path), (Some("http"), Some("/?q")));
+        let mut crumbs: [&str; 4] = core::array::from_fn(|i| request.headers()[i].value());
+        crumbs.sort_unstable();
+        assert_eq!(crumbs, ["", "a=1", "b=2", "c=3"]);
+    }
+
+    #[test]
+    fn names_are_lowercased_only_if_they_fit() {
+        let input = "HTTP/1.1 200 OK\r\nConnection: X-Gone\r\nX-Gone: 1\r\nX-Kept: 2\r\nKeep-Alive: 5\r\n\r\n";
+        let (mut small, mut buf) = ([0; 6], [0; 12]);
+        let mut headers = [crate::header::EMPTY_HEADER; 4];
+        let mut response = crate::parse_response(input, &mut headers).unwrap();
+        assert_eq!(response_to_http2(&mut response, &mut small), Err(TranslateError::BufferTooSmall));
+        assert_eq!(response.version, Version::V11);
+
+        let pseudo = response_to_http2(&mut response, &mut buf).unwrap();
+        assert_eq!(pseudo.fields().next(), Some((":status", "200")));
+        let names: [&str; 4] = core::array::from_fn(|i| response.headers()[i].name().as_str());
+        assert_eq!(names, ["", "", "x-kept", ""]);
+    }
+
+    #[test]
+    fn http2_messages_are_translated_to_http1() {
+        let mut headers = [
+            Header::new(HeaderName::Custom(":method"), "GET"),
+            Header::new(HeaderName::Custom(":scheme"), "https"),
+            Header::new(HeaderName::Custom(":authority"), "example.com"),
+            Header::new(HeaderName::Custom(":path"), "/a"),
+            Header::new(HeaderName::Standard(StandardHeaderName::Cookie), "a=1"),
+            Header::new(HeaderName::Custom("accept"), "*/*"),
+            Header::new(HeaderName::Standard(StandardHeaderName::Cookie), "b=2"),
+        ];
+        let pseudo = RequestPseudoHeaders::from_headers(&headers).unwrap();
+        let mut small = [0; 7];
+        let mut request = Request::new((), &mut headers);
+        assert_eq!(request_to_http1(&pseudo, &mut request, &mut small), Err(TranslateError::BufferTooSmall));
+
+        let mut headers = headers;
+        let mut buf = [0; 8];
+        let mut request = Request::new((), &mut headers);
+        request_to_http1(&pseudo, &mut request, &mut buf).unwrap();
+        assert_eq!((request.version, request.target()), (Version::V11, "/a"));
+        assert_eq!(request.header(StandardHeaderName::Host).map(Header::value), Some("example.com"));
+        let cookies: [&str; 7] = core::array::from_fn(|i| match request.headers()[i].is(StandardHeaderName::Cookie) {
+            true => request.headers()[i].value(),
+            false => "",
+        });
+        assert_eq!(cookies, ["", "", "", "", "a=1; b=2", "", ""]);
+
+        let mut headers = [Header::new(HeaderName::Custom(":status"), "204"), Header::new(HeaderName::Standard(StandardHeaderName::Connection), "close")];
+        let pseudo = ResponsePseudoHeaders::from_headers(&headers[..1]).unwrap();
+        let mut response = Response::new((), &mut headers);
+        assert_eq!(response_to_http1(&pseudo, &mut response), Err(TranslateError::Malformed(MalformedError::ConnectionHeader)));
+        let mut headers = [Header::new(HeaderName::Custom(":status"), "204"), Header::new(HeaderName::Custom("server"), "x")];
+        let mut response = Response::new((), &mut headers);
+        response_to_http1(&pseudo, &mut response).unwrap();
+        assert_eq!((response.version, response.status_code), (Version::V11, crate::StatusCode::NoContext));
+        assert!(response.headers()[0].name().as_str().is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{ Header, Request, Response, Role, StatusCode, Version };
+use crate::base64;
+use crate::header::{ self, StandardHeaderName, has_token };
+use super::connection::Connection;
+use super::frame::{ Flags, Frame, FrameHeader, FrameType, Setting, Settings };
+
+// at most 16 settings are sent or accepted in `HTTP2-Settings`
+const MAX_SETTINGS_LEN: usize = 16 * 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeError {
+    /// the upgrade request isn't at least HTTP/1.1
+    InvalidVersion,
+    /// `Upgrade` doesn't include `h2c`
+    MissingUpgrade,
+    /// `Connection` doesn't include both `Upgrade` and `HTTP2-Settings`
+    MissingConnectionOptions,
+    /// there isn't exactly one `HTTP2-Settings`, or it isn't a base64url encoded `SETTINGS` payload
+    InvalidSettings,
+    /// the server didn't respond with `101 Switching Protocols`
+    InvalidStatus,
+    /// there is no room left in the header buffer
+    HeaderBufferFull,
+}
+
+/// an `HTTP2-Settings` value, the base64url of a `SETTINGS` payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct H2cSettings {
+    settings: Settings,
+    encoded: [u8; MAX_SETTINGS_LEN / 3 * 4],
+    len: usize,
+}
+
+impl H2cSettings {
+    /// `None` for more than 16 settings
+    pub fn new(settings: &[Setting]) -> Option<Self> {
+        let mut payload = [0; MAX_SETTINGS_LEN];
+        let payload = payload.get_mut(..settings.len() * 6)?;
+        let mut applied = Settings::default();
+        for (chunk, &setting) in payload.chunks_exact_mut(6).zip(settings) {
+            chunk.copy_from_slice(&setting.bytes());
+            applied.apply(setting);
+        }
+        let mut encoded = [0; MAX_SETTINGS_LEN / 3 * 4];
+        let len = base64::encode_url_to_slice(payload, &mut encoded)?.len();
+        Some(Self { settings: applied, encoded, len })
+    }
+
+    /// the settings applied over the defaults
+    pub fn settings(&self) -> Settings { self.settings }
+    pub fn as_str(&self) -> &str { core::str::from_utf8(&self.encoded[..self.len]).unwrap_or_default() }
+}
+
+/// a validated `Upgrade: h2c` request (rfc 7540 section 3.2)
+///
+/// a request with a body has to be read whole before switching protocols
+#[derive(Debug, Clone, Copy)]
+pub struct H2cUpgrade {
+    settings: Settings,
+}
+
+impl H2cUpgrade {
+    pub fn from_request<B>(request: &Request<'_, '_, B>) -> Result<Self, UpgradeError> {
+        if request.version == Version::V10 { return Err(UpgradeError::InvalidVersion); }
+        if !has_token(request.headers(), StandardHeaderName::Upgrade, "h2c") { return Err(UpgradeError::MissingUpgrade); }
+        if !has_token(request.headers(), StandardHeaderName::Connection, "upgrade")
+            || !has_token(request.headers(), StandardHeaderName::Connection, "http2-settings") {
+            return Err(UpgradeError::MissingConnectionOptions);
+        }
+        let mut values = request.headers().iter().filter(|header| header.is(StandardHeaderName::Http2Settings));
+        let (Some(value), None) = (values.next(), values.next()) else { return Err(UpgradeError::InvalidSettings) };
+
+        let mut payload = [0; MAX_SETTINGS_LEN];
+        let payload = base64::decode_url(value.value().trim(), &mut payload).ok_or(UpgradeError::InvalidSettings)?;
+        let frame_header = FrameHeader::new(payload.len() as u32, FrameType::Settings, Flags::default(), 0);
+        let Ok(Frame::Settings { settings: values, .. }) = Frame::from_payload(frame_header, payload) else {
+            return Err(UpgradeError::InvalidSettings);
+        };
+        let mut settings = Settings::default();
+        values.for_each(|setting| settings.apply(setting));
+        Ok(Self { settings })
+    }
+
+    /// the client's settings, acknowledged by the `101 Switching Protocols`
+    pub fn settings(&self) -> Settings { self.settings }
+
+    /// turn `response` into the `101 Switching Protocols` accepting the upgrade, after which the server sends its
+    /// connection preface
+    ///
+    /// the headers are added to the unused (empty) slots of the response's header buffer, the response is left
+    /// unchanged if they don't fit
+    pub fn accept<B>(&self, response: &mut Response<'_, '_, B>) -> Result<(), UpgradeError> {
+        if header::unused_slots(response.headers) < 2 { return Err(UpgradeError::HeaderBufferFull); }
+        push_header(response.headers, StandardHeaderName::Connection, "Upgrade")?;
+        push_header(response.headers, StandardHeaderName::Upgrade, "h2c")?;
+        response.status_code = StatusCode::SwitchingProtocols;
+        Ok(())
+    }
+
+    /// the server's connection after the upgrade, the upgraded request is stream 1, half-closed for the client
+    pub fn connection<const S: usize>(&self) -> Connection<S> { Connection::upgraded(Role::Server, self.settings) }
+}
+
+/// turn `request` into an `Upgrade: h2c` request, a body is sent whole before the server switches protocols
+///
+/// the headers are added to the unused (empty) slots of the request's header buffer, the request is left unchanged
+/// if they don't fit
+pub fn upgrade_h2c_request<'r, B>(
+    request: &mut Request<'r, '_, B>,
+    settings: &'r H2cSettings,
+) -> Result<(), UpgradeError> {
+    if header::unused_slots(request.headers) < 3 { return Err(UpgradeError::HeaderBufferFull); }
+    push_header(request.headers, StandardHeaderName::Upgrade, "h2c")?;
+    push_header(request.headers, StandardHeaderName::Connection, "Upgrade, HTTP2-Settings")?;
+    push_header(request.headers, StandardHeaderName::Http2Settings, settings.as_str())?;
+    request.version = Version::V11;
+    Ok(())
+}
+
+/// check the server's response to an `Upgrade: h2c` request, the client's connection then starts with the
+/// upgraded request as stream 1 using [`Connection::upgraded`]
+pub fn verify_h2c_response<B>(response: &Response<'_, '_, B>) -> Result<(), UpgradeError> {
+    if response.status_code != StatusCode::SwitchingProtocols { return Err(UpgradeError::InvalidStatus); }
+    if !has_token(response.headers(), StandardHeaderName::Upgrade, "h2c") { return Err(UpgradeError::MissingUpgrade); }
+    Ok(())
+}
+
+fn push_header<'r>(headers: &mut [Header<'r>], name: StandardHeaderName, value: &'r str) -> Result<(), UpgradeError> {
+    header::push_header(headers, name, value).then_some(()).ok_or(UpgradeError::HeaderBufferFull)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_request;
+    use crate::header::EMPTY_HEADER;
+
+    #[test]
+    fn accept_sets_the_status_after_the_headers() {
+        let mut request_headers = [EMPTY_HEADER; 4];
+        let input = "GET / HTTP/1.1\r\nHost: example.com\r\nConnection: Upgrade, HTTP2-Settings\r\nUpgrade: h2c\r\n\
+            HTTP2-Settings: AAMAAABkAAQAAP__\r\n\r\n";
+        let request = parse_request(input, &mut request_headers).unwrap();
+        let upgrade = H2cUpgrade::from_request(&request).unwrap();
+        assert_eq!(upgrade.settings().max_concurrent_streams, Some(100));
+
+        let mut headers = [EMPTY_HEADER; 2];
+        headers[0] = Header::new(crate::HeaderName::Standard(StandardHeaderName::Date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        let mut response = Response::new((), &mut headers);
+        let status_code = response.status_code;
+        assert_eq!(upgrade.accept(&mut response), Err(UpgradeError::HeaderBufferFull));
+        assert_eq!(response.status_code, status_code);
+        assert!(response.headers()[1].name().as_str().is_empty());
+
+        let mut headers = [EMPTY_HEADER; 2];
+        let mut response = Response::new((), &mut headers);
+        upgrade.accept(&mut response).unwrap();
+        assert_eq!(verify_h2c_response(&response), Ok(()));
+    }
+
+    #[test]
+    fn upgrade_request_is_unchanged_without_room() {
+        let settings = H2cSettings::new(&[Setting::MaxConcurrentStreams(100)]).unwrap();
+        let mut headers = [EMPTY_HEADER; 3];
+        headers[0] = Header::new(crate::HeaderName::Standard(StandardHeaderName::Host), "example.com");
+        let mut request = Request::new((), &mut headers);
+        request.version(Version::V10);
+        assert_eq!(upgrade_h2c_request(&mut request, &settings), Err(UpgradeError::HeaderBufferFull));
+        assert_eq!(request.version, Version::V10);
+        assert!(request.headers()[1..].iter().all(|header| header.name().as_str().is_empty()));
+
+        let mut headers = [EMPTY_HEADER; 3];
+        let mut request = Request::new((), &mut headers);
+        request.version(Version::V10);
+        upgrade_h2c_request(&mut request, &settings).unwrap();
+        assert_eq!(request.version, Version::V11);
+        let settings = request.header(StandardHeaderName::Http2Settings).map(Header::value);
+        assert_eq!(settings, Some("AAMAAABk"));
+    }
+}
@@ -0,0 +1,7 @@
+mod frame;
+pub use frame::{
+    MAX_VARINT, ErrorCode, Http3Error, StreamType, FrameType, FrameHeader, Setting, SettingsIter, Settings, Frame,
+    decode_varint, varint_len, encode_varint, write_settings,
+};
+mod qpack;
+pub use qpack::{ QpackError, EncoderInstruction, DecoderInstruction, DecodedSection, QpackDecoder, QpackEncoder };
@@ -0,0 +1,465 @@
+/// the largest value of a variable-length integer
+pub const MAX_VARINT: u64 = (1 << 62) - 1;
+
+/// an error code of `GOAWAY` and of closing a connection or resetting a stream (rfc 9114 section 8.1, rfc 9204
+/// section 6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode(pub u64);
+
+impl ErrorCode {
+    pub const NO_ERROR: Self = Self(0x100);
+    pub const GENERAL_PROTOCOL_ERROR: Self = Self(0x101);
+    pub const INTERNAL_ERROR: Self = Self(0x102);
+    pub const STREAM_CREATION_ERROR: Self = Self(0x103);
+    pub const CLOSED_CRITICAL_STREAM: Self = Self(0x104);
+    pub const FRAME_UNEXPECTED: Self = Self(0x105);
+    pub const FRAME_ERROR: Self = Self(0x106);
+    pub const EXCESSIVE_LOAD: Self = Self(0x107);
+    pub const ID_ERROR: Self = Self(0x108);
+    pub const SETTINGS_ERROR: Self = Self(0x109);
+    pub const MISSING_SETTINGS: Self = Self(0x10a);
+    pub const REQUEST_REJECTED: Self = Self(0x10b);
+    pub const REQUEST_CANCELLED: Self = Self(0x10c);
+    pub const REQUEST_INCOMPLETE: Self = Self(0x10d);
+    pub const MESSAGE_ERROR: Self = Self(0x10e);
+    pub const CONNECT_ERROR: Self = Self(0x10f);
+    pub const VERSION_FALLBACK: Self = Self(0x110);
+    pub const QPACK_DECOMPRESSION_FAILED: Self = Self(0x200);
+    pub const QPACK_ENCODER_STREAM_ERROR: Self = Self(0x201);
+    pub const QPACK_DECODER_STREAM_ERROR: Self = Self(0x202);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Http3Error {
+    /// more input is needed, at least this many bytes in total
+    Incomplete(usize),
+    /// an error that closes the whole connection
+    Connection(ErrorCode),
+    /// an error that only resets the stream
+    Stream(ErrorCode),
+    /// the output buffer is too small
+    BufferTooSmall,
+    /// a value to write above [`MAX_VARINT`]
+    VarintTooLarge,
+}
+
+/// decode a variable-length integer (rfc 9000 section 16), returns the value and its length
+pub fn decode_varint(input: &[u8]) -> Result<(u64, usize), Http3Error> {
+    let first = *input.first().ok_or(Http3Error::Incomplete(1))?;
+    let len = 1 << (first >> 6);
+    let bytes = input.get(..len).ok_or(Http3Error::Incomplete(len))?;
+    let value = bytes[1..].iter().fold((first & 0x3f) as u64, |value, &byte| value << 8 | byte as u64);
+    Ok((value, len))
+}
+
+/// the length of `value` as a variable-length integer
+pub fn varint_len(value: u64) -> usize {
+    match value {
+        0..=0x3f => 1,
+        0x40..=0x3fff => 2,
+        0x4000..=0x3fff_ffff => 4,
+        _ => 8,
+    }
+}
+
+/// write `value` as a variable-length integer in as few bytes as possible, returns its length
+pub fn encode_varint(value: u64, out: &mut [u8]) -> Result<usize, Http3Error> {
+    if value > MAX_VARINT { return Err(Http3Error::VarintTooLarge); }
+    let len = varint_len(value);
+    let out = out.get_mut(..len).ok_or(Http3Error::BufferTooSmall)?;
+    out.copy_from_slice(&value.to_be_bytes()[8 - len..]);
+    out[0] |= (len.trailing_zeros() as u8) << 6;
+    Ok(len)
+}
+
+/// the type of a unidirectional stream, sent as a variable-length integer at its start (rfc 9114 section 6.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamType {
+    Control,
+    Push,
+    QpackEncoder,
+    QpackDecoder,
+    /// streams of unknown types are to be ignored, or aborted with `STREAM_CREATION_ERROR`
+    Unknown(u64),
+}
+
+impl StreamType {
+    pub fn from_u64(ty: u64) -> Self {
+        match ty {
+            0x0 => Self::Control,
+            0x1 => Self::Push,
+            0x2 => Self::QpackEncoder,
+            0x3 => Self::QpackDecoder,
+            ty => Self::Unknown(ty),
+        }
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            Self::Control => 0x0,
+            Self::Push => 0x1,
+            Self::QpackEncoder => 0x2,
+            Self::QpackDecoder => 0x3,
+            Self::Unknown(ty) => *ty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Headers,
+    CancelPush,
+    Settings,
+    PushPromise,
+    Goaway,
+    MaxPushId,
+    /// frames of unknown types are ignored, except for the reserved http/2 frame types
+    Unknown(u64),
+}
+
+impl FrameType {
+    pub fn from_u64(ty: u64) -> Self {
+        match ty {
+            0x0 => Self::Data,
+            0x1 => Self::Headers,
+            0x3 => Self::CancelPush,
+            0x4 => Self::Settings,
+            0x5 => Self::PushPromise,
+            0x7 => Self::Goaway,
+            0xd => Self::MaxPushId,
+            ty => Self::Unknown(ty),
+        }
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            Self::Data => 0x0,
+            Self::Headers => 0x1,
+            Self::CancelPush => 0x3,
+            Self::Settings => 0x4,
+            Self::PushPromise => 0x5,
+            Self::Goaway => 0x7,
+            Self::MaxPushId => 0xd,
+            Self::Unknown(ty) => *ty,
+        }
+    }
+
+    /// whether frames of this type can be sent on the control stream, the others are `FRAME_UNEXPECTED` there
+    pub fn is_control(&self) -> bool {
+        matches!(self, Self::CancelPush | Self::Settings | Self::Goaway | Self::MaxPushId | Self::Unknown(_))
+    }
+
+    /// whether frames of this type can be sent on a request stream, the others are `FRAME_UNEXPECTED` there
+    ///
+    /// only `DATA`, `HEADERS` and unknown frames are allowed on push streams
+    pub fn is_request(&self) -> bool {
+        matches!(self, Self::Data | Self::Headers | Self::PushPromise | Self::Unknown(_))
+    }
+}
+
+/// the type and length preceding every frame (rfc 9114 section 7.1)
+///
+/// a `DATA` payload can be read as it arrives after parsing just the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    ty: FrameType,
+    length: u64,
+}
+
+impl FrameHeader {
+    /// the length is the length of the payload
+    pub fn new(ty: FrameType, length: u64) -> Self { Self { ty, length } }
+
+    /// parse a frame header, returns the header and its length
+    pub fn parse(input: &[u8]) -> Result<(Self, usize), Http3Error> {
+        let (ty, ty_len) = decode_varint(input)?;
+        let (length, length_len) = decode_varint(&input[ty_len..]).map_err(|error| match error {
+            Http3Error::Incomplete(len) => Http3Error::Incomplete(ty_len + len),
+            error => error,
+        })?;
+        Ok((Self { ty: FrameType::from_u64(ty), length }, ty_len + length_len))
+    }
+
+    pub fn ty(&self) -> FrameType { self.ty }
+    pub fn length(&self) -> u64 { self.length }
+    pub fn encoded_len(&self) -> usize { varint_len(self.ty.as_u64()) + varint_len(self.length) }
+
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, Http3Error> {
+        let len = encode_varint(self.ty.as_u64(), out)?;
+        Ok(len + encode_varint(self.length, &mut out[len..])?)
+    }
+}
+
+/// a parameter of a `SETTINGS` frame (rfc 9114 section 7.2.4.1, rfc 9204 section 5, rfc 9220 section 3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    QpackMaxTableCapacity(u64),
+    MaxFieldSectionSize(u64),
+    QpackBlockedStreams(u64),
+    EnableConnectProtocol(bool),
+    /// unknown settings are ignored
+    Unknown(u64, u64),
+}
+
+impl Setting {
+    fn parse(id: u64, value: u64) -> Result<Self, Http3Error> {
+        Ok(match id {
+            0x1 => Self::QpackMaxTableCapacity(value),
+            0x6 => Self::MaxFieldSectionSize(value),
+            0x7 => Self::QpackBlockedStreams(value),
+            0x8 if value > 1 => return Err(Http3Error::Connection(ErrorCode::SETTINGS_ERROR)),
+            0x8 => Self::EnableConnectProtocol(value == 1),
+            // the http/2 settings that have no http/3 counterpart
+            0x0 | 0x2..=0x5 => return Err(Http3Error::Connection(ErrorCode::SETTINGS_ERROR)),
+            id => Self::Unknown(id, value),
+        })
+    }
+
+    fn id_value(&self) -> (u64, u64) {
+        match *self {
+            Self::QpackMaxTableCapacity(value) => (0x1, value),
+            Self::MaxFieldSectionSize(value) => (0x6, value),
+            Self::QpackBlockedStreams(value) => (0x7, value),
+            Self::EnableConnectProtocol(value) => (0x8, value as u64),
+            Self::Unknown(id, value) => (id, value),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        let (id, value) = self.id_value();
+        varint_len(id) + varint_len(value)
+    }
+}
+
+/// the (already validated) parameters of a `SETTINGS` frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingsIter<'f>(&'f [u8]);
+
+impl SettingsIter<'_> {
+    fn next_raw(&mut self) -> Option<Result<(u64, u64), Http3Error>> {
+        if self.0.is_empty() { return None; }
+        let pair = decode_varint(self.0).and_then(|(id, id_len)| {
+            let (value, value_len) = decode_varint(&self.0[id_len..])?;
+            Ok(((id, value), id_len + value_len))
+        });
+        Some(match pair {
+            Ok((pair, len)) => {
+                self.0 = &self.0[len..];
+                Ok(pair)
+            }
+            Err(_) => Err(Http3Error::Connection(ErrorCode::FRAME_ERROR)),
+        })
+    }
+}
+
+impl Iterator for SettingsIter<'_> {
+    type Item = Setting;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, value) = self.next_raw()?.ok()?;
+        Setting::parse(id, value).ok()
+    }
+}
+
+/// the settings of one endpoint, with the defaults from rfc 9114 section 7.2.4.1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Settings {
+    /// 0 by default, so the dynamic table can't be used until the peer's settings arrive
+    pub qpack_max_table_capacity: u64,
+    /// unlimited by default
+    pub max_field_section_size: Option<u64>,
+    pub qpack_blocked_streams: u64,
+    pub enable_connect_protocol: bool,
+}
+
+impl Settings {
+    pub fn apply(&mut self, setting: Setting) {
+        match setting {
+            Setting::QpackMaxTableCapacity(value) => self.qpack_max_table_capacity = value,
+            Setting::MaxFieldSectionSize(value) => self.max_field_section_size = Some(value),
+            Setting::QpackBlockedStreams(value) => self.qpack_blocked_streams = value,
+            Setting::EnableConnectProtocol(value) => self.enable_connect_protocol = value,
+            Setting::Unknown(..) => {}
+        }
+    }
+}
+
+/// a frame, borrowing its payload from the input
+///
+/// header blocks are encoded field sections, see [`QpackDecoder`](super::QpackDecoder)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame<'f> {
+    Data { data: &'f [u8] },
+    Headers { field_section: &'f [u8] },
+    CancelPush { push_id: u64 },
+    Settings { settings: SettingsIter<'f> },
+    PushPromise { push_id: u64, field_section: &'f [u8] },
+    /// a stream id sent by a server, a push id sent by a client
+    Goaway { id: u64 },
+    MaxPushId { push_id: u64 },
+    /// frames of unknown types are to be ignored
+    Unknown { ty: u64, payload: &'f [u8] },
+}
+
+impl<'f> Frame<'f> {
+    /// parse a whole frame from the start of `input`, returns the frame and the number of bytes it took up
+    ///
+    /// there is no limit on the length of a frame, a caller that buffers whole frames has to bound
+    /// [`Http3Error::Incomplete`] itself, e.g. by `SETTINGS_MAX_FIELD_SECTION_SIZE`
+    pub fn parse(input: &'f [u8]) -> Result<(Self, usize), Http3Error> {
+        let (header, header_len) = FrameHeader::parse(input)?;
+        let len = usize::try_from(header.length).ok()
+            .and_then(|length| length.checked_add(header_len))
+            .ok_or(Http3Error::Connection(ErrorCode::EXCESSIVE_LOAD))?;
+        let payload = input.get(header_len..len).ok_or(Http3Error::Incomplete(len))?;
+        Ok((Self::from_payload(header, payload)?, len))
+    }
+
+    /// check a frame's payload against its header
+    pub fn from_payload(header: FrameHeader, payload: &'f [u8]) -> Result<Self, Http3Error> {
+        let frame_error = Http3Error::Connection(ErrorCode::FRAME_ERROR);
+        // a payload that is exactly one variable-length integer
+        let single_varint = || match decode_varint(payload) {
+            Ok((value, len)) if len == payload.len() => Ok(value),
+            _ => Err(frame_error),
+        };
+
+        Ok(match header.ty {
+            FrameType::Data => Self::Data { data: payload },
+            FrameType::Headers => Self::Headers { field_section: payload },
+            FrameType::CancelPush => Self::CancelPush { push_id: single_varint()? },
+            FrameType::Settings => {
+                let mut raw = SettingsIter(payload);
+                // at most 64 distinct ids are checked for duplicates, more are an excessive load
+                let mut seen = [0u64; 64];
+                let mut count = 0;
+                while let Some(pair) = raw.next_raw() {
+                    let (id, value) = pair?;
+                    Setting::parse(id, value)?;
+                    if seen[..count].contains(&id) { return Err(Http3Error::Connection(ErrorCode::SETTINGS_ERROR)); }
+                    *seen.get_mut(count).ok_or(Http3Error::Connection(ErrorCode::EXCESSIVE_LOAD))? = id;
+                    count += 1;
+                }
+                Self::Settings { settings: SettingsIter(payload) }
+            }
+            FrameType::PushPromise => {
+                let (push_id, len) = decode_varint(payload).map_err(|_| frame_error)?;
+                Self::PushPromise { push_id, field_section: &payload[len..] }
+            }
+            FrameType::Goaway => Self::Goaway { id: single_varint()? },
+            FrameType::MaxPushId => Self::MaxPushId { push_id: single_varint()? },
+            // the http/2 frame types that have no http/3 counterpart
+            FrameType::Unknown(0x2 | 0x6 | 0x8 | 0x9) => return Err(Http3Error::Connection(ErrorCode::FRAME_UNEXPECTED)),
+            FrameType::Unknown(ty) => Self::Unknown { ty, payload },
+        })
+    }
+
+    /// the header of the frame when written
+    pub fn header(&self) -> FrameHeader {
+        let (ty, length) = match *self {
+            Self::Data { data } => (FrameType::Data, data.len()),
+            Self::Headers { field_section } => (FrameType::Headers, field_section.len()),
+            Self::CancelPush { push_id } => (FrameType::CancelPush, varint_len(push_id)),
+            Self::Settings { settings } => (FrameType::Settings, settings.0.len()),
+            Self::PushPromise { push_id, field_section } => {
+                (FrameType::PushPromise, varint_len(push_id) + field_section.len())
+            }
+            Self::Goaway { id } => (FrameType::Goaway, varint_len(id)),
+            Self::MaxPushId { push_id } => (FrameType::MaxPushId, varint_len(push_id)),
+            Self::Unknown { ty, payload } => (FrameType::Unknown(ty), payload.len()),
+        };
+        FrameHeader::new(ty, length as u64)
+    }
+
+    /// the length of the whole frame when written
+    pub fn encoded_len(&self) -> usize {
+        let header = self.header();
+        header.encoded_len() + header.length as usize
+    }
+
+    /// write the frame to the start of `out`, returns the frame's length
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, Http3Error> {
+        let len = self.encoded_len();
+        let out = out.get_mut(..len).ok_or(Http3Error::BufferTooSmall)?;
+        let pos = self.header().write(out)?;
+        let payload = &mut out[pos..];
+        match *self {
+            Self::Data { data: bytes } | Self::Headers { field_section: bytes } | Self::Unknown { payload: bytes, .. } => {
+                payload.copy_from_slice(bytes);
+            }
+            Self::Settings { settings } => payload.copy_from_slice(settings.0),
+            Self::CancelPush { push_id: id } | Self::Goaway { id } | Self::MaxPushId { push_id: id } => {
+                encode_varint(id, payload)?;
+            }
+            Self::PushPromise { push_id, field_section } => {
+                let id_len = encode_varint(push_id, payload)?;
+                payload[id_len..].copy_from_slice(field_section);
+            }
+        }
+        Ok(len)
+    }
+}
+
+/// write a `SETTINGS` frame with the given parameters, returns the frame's length
+///
+/// it has to be the first frame of the control stream
+pub fn write_settings(settings: &[Setting], out: &mut [u8]) -> Result<usize, Http3Error> {
+    let length = settings.iter().map(Setting::encoded_len).sum::<usize>();
+    let mut pos = FrameHeader::new(FrameType::Settings, length as u64).write(out)?;
+    for setting in settings {
+        let (id, value) = setting.id_value();
+        pos += encode_varint(id, out.get_mut(pos..).ok_or(Http3Error::BufferTooSmall)?)?;
+        pos += encode_varint(value, out.get_mut(pos..).ok_or(Http3Error::BufferTooSmall)?)?;
+    }
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_boundaries() {
+        let cases: [(u64, &[u8]); 8] = [
+            (0, b"\x00"),
+            (63, b"\x3f"),
+            (64, b"\x40\x40"),
+            (16_383, b"\x7f\xff"),
+            (16_384, b"\x80\x00\x40\x00"),
+            ((1 << 30) - 1, b"\xbf\xff\xff\xff"),
+            (1 << 30, b"\xc0\x00\x00\x00\x40\x00\x00\x00"),
+            (MAX_VARINT, b"\xff\xff\xff\xff\xff\xff\xff\xff"),
+        ];
+        for (value, encoded) in cases {
+            let mut out = [0; 8];
+            assert_eq!(encode_varint(value, &mut out), Ok(encoded.len()), "{value}");
+            assert_eq!(&out[..encoded.len()], encoded);
+            assert_eq!(varint_len(value), encoded.len());
+            assert_eq!(decode_varint(encoded), Ok((value, encoded.len())));
+            if encoded.len() > 1 {
+                assert_eq!(decode_varint(&encoded[..encoded.len() - 1]), Err(Http3Error::Incomplete(encoded.len())));
+            }
+        }
+        assert_eq!(encode_varint(MAX_VARINT + 1, &mut [0; 8]), Err(Http3Error::VarintTooLarge));
+        assert_eq!(encode_varint(1 << 30, &mut [0; 7]), Err(Http3Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn goaway_ids_are_not_truncated() {
+        let mut out = [0; 16];
+        assert_eq!(Frame::Goaway { id: MAX_VARINT + 1 }.write(&mut out), Err(Http3Error::VarintTooLarge));
+        let len = Frame::Goaway { id: MAX_VARINT }.write(&mut out).unwrap();
+        assert_eq!(Frame::parse(&out[..len]), Ok((Frame::Goaway { id: MAX_VARINT }, len)));
+    }
+
+    #[test]
+    fn reserved_settings_are_rejected() {
+        for id in [0x0, 0x2, 0x3, 0x4, 0x5] {
+            // a `SETTINGS` frame with the single setting `id = 0`, ids are below 64 so they take one byte
+            let frame = [0x04, 0x02, id, 0x00];
+            assert_eq!(Frame::parse(&frame), Err(Http3Error::Connection(ErrorCode::SETTINGS_ERROR)), "{id}");
+        }
+        let frame = [0x04, 0x02, 0x21, 0x00];
+        assert!(Frame::parse(&frame).is_ok());
+    }
+}
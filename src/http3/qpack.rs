@@ -0,0 +1,677 @@
+use crate::Header;
+use crate::http2::DEFAULT_TABLE_SIZE;
+use crate::http2::hpack::{
+    BlockWriter, DynamicTable, ENTRY_OVERHEAD, HpackError, StringBuf, decode_int, decode_int62, decode_string, header_name, is_sensitive,
+};
+use super::frame::{ ErrorCode, Http3Error };
+
+// the room left ahead of the field lines for the field section prefix, two integers of at most 10 bytes each
+const PREFIX_ROOM: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QpackError {
+    /// the field section refers to entries that haven't arrived on the encoder stream yet, it can be decoded once
+    /// the insert count reaches this required insert count
+    Blocked(u64),
+    /// a field section that can't be decoded
+    DecompressionFailed,
+    /// an invalid instruction on the encoder stream
+    EncoderStream,
+    /// an invalid instruction on the decoder stream
+    DecoderStream,
+    /// the output buffer is too small
+    BufferTooSmall,
+    /// there is no room left in the header buffer
+    HeaderBufferFull,
+    /// a decoded name with uppercase letters, the message is malformed (rfc 9114 section 4.2) and the stream has to
+    /// be reset with `H3_MESSAGE_ERROR`
+    UppercaseName,
+}
+
+/// a blocked field section is only an error once more streams are blocked than `SETTINGS_QPACK_BLOCKED_STREAMS`
+impl From<QpackError> for Http3Error {
+    fn from(error: QpackError) -> Self {
+        match error {
+            QpackError::EncoderStream => Self::Connection(ErrorCode::QPACK_ENCODER_STREAM_ERROR),
+            QpackError::DecoderStream => Self::Connection(ErrorCode::QPACK_DECODER_STREAM_ERROR),
+            QpackError::BufferTooSmall => Self::BufferTooSmall,
+            QpackError::UppercaseName => Self::Stream(ErrorCode::MESSAGE_ERROR),
+            _ => Self::Connection(ErrorCode::QPACK_DECOMPRESSION_FAILED),
+        }
+    }
+}
+
+// the hpack primitives fail with the error of whatever is being decoded
+fn map_error(context: QpackError) -> impl Fn(HpackError) -> QpackError {
+    move |error| match error {
+        HpackError::BufferTooSmall => QpackError::BufferTooSmall,
+        HpackError::HeaderBufferFull => QpackError::HeaderBufferFull,
+        _ => context,
+    }
+}
+
+// an instruction is incomplete until all of its bytes have arrived on the stream
+fn instruction<T>(parsed: Result<T, HpackError>, context: QpackError) -> Result<Option<T>, QpackError> {
+    match parsed {
+        Ok(parsed) => Ok(Some(parsed)),
+        Err(HpackError::Truncated) => Ok(None),
+        Err(error) => Err(map_error(context)(error)),
+    }
+}
+
+/// the static table (rfc 9204 appendix A), indexed from 0
+const STATIC_TABLE: [(&str, &str); 99] = [
+    (":authority", ""), (":path", "/"), ("age", "0"), ("content-disposition", ""), ("content-length", "0"),
+    ("cookie", ""), ("date", ""), ("etag", ""), ("if-modified-since", ""), ("if-none-match", ""),
+    ("last-modified", ""), ("link", ""), ("location", ""), ("referer", ""), ("set-cookie", ""),
+    (":method", "CONNECT"), (":method", "DELETE"), (":method", "GET"), (":method", "HEAD"), (":method", "OPTIONS"),
+    (":method", "POST"), (":method", "PUT"), (":scheme", "http"), (":scheme", "https"), (":status", "103"),
+    (":status", "200"), (":status", "304"), (":status", "404"), (":status", "503"), ("accept", "*/*"),
+    ("accept", "application/dns-message"), ("accept-encoding", "gzip, deflate, br"), ("accept-ranges", "bytes"),
+    ("access-control-allow-headers", "cache-control"), ("access-control-allow-headers", "content-type"),
+    ("access-control-allow-origin", "*"), ("cache-control", "max-age=0"), ("cache-control", "max-age=2592000"),
+    ("cache-control", "max-age=604800"), ("cache-control", "no-cache"), ("cache-control", "no-store"),
+    ("cache-control", "public, max-age=31536000"), ("content-encoding", "br"), ("content-encoding", "gzip"),
+    ("content-type", "application/dns-message"), ("content-type", "application/javascript"),
+    ("content-type", "application/json"), ("content-type", "application/x-www-form-urlencoded"),
+    ("content-type", "image/gif"), ("content-type", "image/jpeg"), ("content-type", "image/png"),
+    ("content-type", "text/css"), ("content-type", "text/html; charset=utf-8"), ("content-type", "text/plain"),
+    ("content-type", "text/plain;charset=utf-8"), ("range", "bytes=0-"), ("strict-transport-security", "max-age=31536000"),
+    ("strict-transport-security", "max-age=31536000; includesubdomains"),
+    ("strict-transport-security", "max-age=31536000; includesubdomains; preload"), ("vary", "accept-encoding"),
+    ("vary", "origin"), ("x-content-type-options", "nosniff"), ("x-xss-protection", "1; mode=block"),
+    (":status", "100"), (":status", "204"), (":status", "206"), (":status", "302"), (":status", "400"),
+    (":status", "403"), (":status", "421"), (":status", "425"), (":status", "500"), ("accept-language", ""),
+    ("access-control-allow-credentials", "FALSE"), ("access-control-allow-credentials", "TRUE"),
+    ("access-control-allow-headers", "*"), ("access-control-allow-methods", "get"),
+    ("access-control-allow-methods", "get, post, options"), ("access-control-allow-methods", "options"),
+    ("access-control-expose-headers", "content-length"), ("access-control-request-headers", "content-type"),
+    ("access-control-request-method", "get"), ("access-control-request-method", "post"), ("alt-svc", "clear"),
+    ("authorization", ""), ("content-security-policy", "script-src 'none'; object-src 'none'; base-uri 'none'"),
+    ("early-data", "1"), ("expect-ct", ""), ("forwarded", ""), ("if-range", ""), ("origin", ""),
+    ("purpose", "prefetch"), ("server", ""), ("timing-allow-origin", "*"), ("upgrade-insecure-requests", "1"),
+    ("user-agent", ""), ("x-forwarded-for", ""), ("x-frame-options", "deny"), ("x-frame-options", "sameorigin"),
+];
+
+/// an instruction on the encoder stream (rfc 9204 section 4.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderInstruction<'i> {
+    SetDynamicTableCapacity(usize),
+    /// `index` is into the static table, or relative to the newest dynamic entry
+    InsertWithNameReference { static_table: bool, index: usize, value: &'i str },
+    InsertWithLiteralName { name: &'i str, value: &'i str },
+    /// `index` is relative to the newest dynamic entry
+    Duplicate(usize),
+}
+
+impl<'i> EncoderInstruction<'i> {
+    /// parse an instruction from the start of `input`, returns the instruction and its length, or `None` until the
+    /// whole instruction has arrived
+    ///
+    /// huffman encoded names and values are decoded to `buf`
+    pub fn parse(input: &'i [u8], buf: &'i mut [u8]) -> Result<Option<(Self, usize)>, QpackError> {
+        Self::parse_with(input, &mut StringBuf(buf))
+    }
+
+    fn parse_with(input: &'i [u8], strings: &mut StringBuf<'i>) -> Result<Option<(Self, usize)>, QpackError> {
+        let Some(&byte) = input.first() else { return Ok(None) };
+        let parsed = (|| {
+            if byte & 0x80 != 0 {
+                let (index, len) = decode_int(input, 6)?;
+                let (value, value_len) = decode_string(&input[len..], 7, strings)?;
+                let static_table = byte & 0x40 != 0;
+                Ok((Self::InsertWithNameReference { static_table, index, value }, len + value_len))
+            } else if byte & 0x40 != 0 {
+                let (name, len) = decode_string(input, 5, strings)?;
+                let (value, value_len) = decode_string(&input[len..], 7, strings)?;
+                Ok((Self::InsertWithLiteralName { name, value }, len + value_len))
+            } else if byte & 0x20 != 0 {
+                let (capacity, len) = decode_int(input, 5)?;
+                Ok((Self::SetDynamicTableCapacity(capacity), len))
+            } else {
+                let (index, len) = decode_int(input, 5)?;
+                Ok((Self::Duplicate(index), len))
+            }
+        })();
+        instruction(parsed, QpackError::EncoderStream)
+    }
+
+    /// write the instruction to the start of `out`, returns its length
+    ///
+    /// literal names are sent lowercased
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, QpackError> {
+        let mut out = BlockWriter::new(out);
+        match *self {
+            Self::SetDynamicTableCapacity(capacity) => out.int(0x20, 5, capacity),
+            Self::InsertWithNameReference { static_table, index, value } => {
+                out.int(if static_table { 0xc0 } else { 0x80 }, 6, index)
+                    .and_then(|_| out.string(0, 7, value.as_bytes(), false))
+            }
+            Self::InsertWithLiteralName { name, value } => {
+                out.string(0x40, 5, name.as_bytes(), true).and_then(|_| out.string(0, 7, value.as_bytes(), false))
+            }
+            Self::Duplicate(index) => out.int(0, 5, index),
+        }.map_err(map_error(QpackError::EncoderStream))?;
+        Ok(out.pos)
+    }
+}
+
+/// an instruction on the decoder stream (rfc 9204 section 4.4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderInstruction {
+    /// the field section on this stream, which referred to the dynamic table, was decoded
+    SectionAcknowledgment(u64),
+    /// the stream was reset or abandoned before its field sections were decoded
+    StreamCancellation(u64),
+    /// this many more inserts were received
+    InsertCountIncrement(u64),
+}
+
+impl DecoderInstruction {
+    /// parse an instruction from the start of `input`, returns the instruction and its length, or `None` until the
+    /// whole instruction has arrived
+    pub fn parse(input: &[u8]) -> Result<Option<(Self, usize)>, QpackError> {
+        let Some(&byte) = input.first() else { return Ok(None) };
+        let parsed = match byte {
+            byte if byte & 0x80 != 0 => decode_int62(input, 7).map(|(id, len)| (Self::SectionAcknowledgment(id), len)),
+            byte if byte & 0x40 != 0 => decode_int62(input, 6).map(|(id, len)| (Self::StreamCancellation(id), len)),
+            _ => decode_int62(input, 6).map(|(increment, len)| (Self::InsertCountIncrement(increment), len)),
+        };
+        instruction(parsed, QpackError::DecoderStream)
+    }
+
+    /// write the instruction to the start of `out`, returns its length
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, QpackError> {
+        let mut out = BlockWriter::new(out);
+        match *self {
+            Self::SectionAcknowledgment(id) => out.int64(0x80, 7, id),
+            Self::StreamCancellation(id) => out.int64(0x40, 6, id),
+            Self::InsertCountIncrement(increment) => out.int64(0, 6, increment),
+        }.map_err(map_error(QpackError::DecoderStream))?;
+        Ok(out.pos)
+    }
+}
+
+/// a decoded field section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedSection {
+    /// the number of headers
+    pub count: usize,
+    /// above 0 when the section referred to the dynamic table, it is then acknowledged with
+    /// [`QpackDecoder::acknowledge_section`]
+    pub required_insert_count: u64,
+}
+
+/// decodes field sections (rfc 9204 section 4.5), keeping a dynamic table of up to `N` bytes that is filled by the
+/// peer's encoder stream
+///
+/// `N` is advertised as `SETTINGS_QPACK_MAX_TABLE_CAPACITY`, see [`max_table_capacity`](Self::max_table_capacity)
+#[derive(Debug, Clone)]
+pub struct QpackDecoder<const N: usize = DEFAULT_TABLE_SIZE> {
+    table: DynamicTable<N>,
+    insert_count: u64,
+    known_received_count: u64,
+}
+
+impl<const N: usize> Default for QpackDecoder<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> QpackDecoder<N> {
+    /// the table is empty with a capacity of 0 until the encoder stream sets one
+    pub fn new() -> Self { Self { table: DynamicTable::new(0), insert_count: 0, known_received_count: 0 } }
+
+    /// the `SETTINGS_QPACK_MAX_TABLE_CAPACITY` to advertise
+    pub fn max_table_capacity(&self) -> u64 { N as u64 }
+
+    /// the number of entries inserted into the dynamic table so far
+    pub fn insert_count(&self) -> u64 { self.insert_count }
+
+    /// apply the complete instructions at the start of the peer's encoder stream, returns how many bytes were
+    /// used, the rest has to be kept until more of the stream arrives
+    ///
+    /// huffman encoded names and values are decoded to `buf`, which only has to hold one instruction's
+    pub fn recv_encoder_stream(&mut self, input: &[u8], buf: &mut [u8]) -> Result<usize, QpackError> {
+        let stream_error = map_error(QpackError::EncoderStream);
+        let mut pos = 0;
+        loop {
+            let mut strings = StringBuf(&mut *buf);
+            let Some((instruction, len)) = EncoderInstruction::parse_with(&input[pos..], &mut strings)? else { break };
+            match instruction {
+                EncoderInstruction::SetDynamicTableCapacity(capacity) => {
+                    if capacity > N { return Err(QpackError::EncoderStream); }
+                    self.table.set_max_size(capacity);
+                }
+                EncoderInstruction::InsertWithNameReference { static_table: true, index, value } => {
+                    let (name, _) = STATIC_TABLE.get(index).ok_or(QpackError::EncoderStream)?;
+                    self.insert(name, value)?;
+                }
+                EncoderInstruction::InsertWithNameReference { static_table: false, index, value } => {
+                    // the referenced entry may be evicted by the insert, so its name is copied out first
+                    let (name, _) = self.table.get(index).ok_or(QpackError::EncoderStream)?;
+                    let name = strings.push(name).map_err(&stream_error)?;
+                    self.insert(name, value)?;
+                }
+                EncoderInstruction::InsertWithLiteralName { name, value } => self.insert(name, value)?,
+                EncoderInstruction::Duplicate(index) => {
+                    let (name, value) = self.table.get(index).ok_or(QpackError::EncoderStream)?;
+                    let name = strings.push(name).map_err(&stream_error)?;
+                    let value = strings.push(value).map_err(&stream_error)?;
+                    self.insert(name, value)?;
+                }
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    fn insert(&mut self, name: &str, value: &str) -> Result<(), QpackError> {
+        if name.len() + value.len() + ENTRY_OVERHEAD > self.table.max_size { return Err(QpackError::EncoderStream); }
+        self.table.insert(name.as_bytes(), value.as_bytes());
+        self.insert_count += 1;
+        Ok(())
+    }
+
+    /// decode a whole field section into the unused (empty) slots of `headers`
+    ///
+    /// names and values that had to be copied, from the dynamic table or out of huffman encoding, are written to
+    /// `buf`, others are borrowed from the section or the static table. a [`QpackError::Blocked`] section is
+    /// decoded again once enough of the encoder stream arrived
+    pub fn decode<'b>(
+        &self,
+        section: &'b [u8],
+        buf: &'b mut [u8],
+        headers: &mut [Header<'b>],
+    ) -> Result<DecodedSection, QpackError> {
+        let failed = map_error(QpackError::DecompressionFailed);
+        let (encoded_insert_count, mut pos) = decode_int(section, 8).map_err(&failed)?;
+        let required_insert_count = decode_insert_count(encoded_insert_count as u64, (N / ENTRY_OVERHEAD) as u64,
+            self.insert_count).ok_or(QpackError::DecompressionFailed)?;
+        let negative = section.get(pos).is_some_and(|byte| byte & 0x80 != 0);
+        let (delta_base, len) = decode_int(&section[pos..], 7).map_err(&failed)?;
+        pos += len;
+        let base = match negative {
+            false => required_insert_count + delta_base as u64,
+            true => required_insert_count.checked_sub(delta_base as u64 + 1).ok_or(QpackError::DecompressionFailed)?,
+        };
+        if required_insert_count > self.insert_count { return Err(QpackError::Blocked(required_insert_count)); }
+
+        let mut strings = StringBuf(buf);
+        let mut slots = headers.iter_mut().filter(|header| header.name().as_str().is_empty());
+        let mut count = 0;
+        let mut uppercase = false;
+        let mut largest_reference = 0;
+        // an entry of the dynamic table by its absolute index
+        let mut entry = |index: Option<u64>, strings: &mut StringBuf<'b>| {
+            let index = index.filter(|&index| index < required_insert_count).ok_or(QpackError::DecompressionFailed)?;
+            largest_reference = largest_reference.max(index + 1);
+            let (name, value) = self.table.get((self.insert_count - 1 - index) as usize)
+                .ok_or(QpackError::DecompressionFailed)?;
+            Ok::<_, QpackError>((strings.push(name).map_err(&failed)?, strings.push(value).map_err(&failed)?))
+        };
+        let static_entry = |index: usize| STATIC_TABLE.get(index).copied().ok_or(QpackError::DecompressionFailed);
+        let relative = |index: usize| base.checked_sub(index as u64 + 1);
+        let post_base = |index: usize| Some(base + index as u64);
+
+        while let Some(&byte) = section.get(pos) {
+            let (index, len) = match byte {
+                byte if byte & 0x80 != 0 => decode_int(&section[pos..], 6),
+                byte if byte & 0xc0 == 0x40 => decode_int(&section[pos..], 4),
+                byte if byte & 0xe0 == 0x20 => Ok((0, 0)),
+                byte if byte & 0xf0 == 0x10 => decode_int(&section[pos..], 4),
+                _ => decode_int(&section[pos..], 3),
+            }.map_err(&failed)?;
+            pos += len;
+
+            let (name, value) = if byte & 0x80 != 0 || byte & 0xf0 == 0x10 {
+                match byte {
+                    byte if byte & 0xc0 == 0xc0 => static_entry(index)?,
+                    byte if byte & 0x80 != 0 => entry(relative(index), &mut strings)?,
+                    _ => entry(post_base(index), &mut strings)?,
+                }
+            } else {
+                let name = match byte {
+                    byte if byte & 0xe0 == 0x20 => {
+                        let (name, len) = decode_string(&section[pos..], 3, &mut strings).map_err(&failed)?;
+                        pos += len;
+                        name
+                    }
+                    byte if byte & 0xd0 == 0x50 => static_entry(index)?.0,
+                    byte if byte & 0xc0 == 0x40 => entry(relative(index), &mut strings)?.0,
+                    _ => entry(post_base(index), &mut strings)?.0,
+                };
+                let (value, len) = decode_string(&section[pos..], 7, &mut strings).map_err(&failed)?;
+                pos += len;
+                (name, value)
+            };
+
+            // checked on the raw name, `header_name` folds the case of standard names
+            uppercase |= name.bytes().any(|b| b.is_ascii_uppercase());
+            let slot = slots.next().ok_or(QpackError::HeaderBufferFull)?;
+            *slot = Header::new(header_name(name), value);
+            count += 1;
+        }
+        // the required insert count has to be exactly what the section refers to
+        if largest_reference != required_insert_count { return Err(QpackError::DecompressionFailed); }
+        if uppercase { return Err(QpackError::UppercaseName); }
+        Ok(DecodedSection { count, required_insert_count })
+    }
+
+    /// write the acknowledgment of a field section that referred to the dynamic table to the start of `out`, returns
+    /// its length, 0 for sections that don't have to be acknowledged
+    pub fn acknowledge_section(&mut self, stream_id: u64, section: &DecodedSection, out: &mut [u8]) -> Result<usize, QpackError> {
+        if section.required_insert_count == 0 { return Ok(0); }
+        self.known_received_count = self.known_received_count.max(section.required_insert_count);
+        DecoderInstruction::SectionAcknowledgment(stream_id).write(out)
+    }
+
+    /// write an insert count increment for the inserts the peer's encoder doesn't know were received to the start
+    /// of `out`, returns its length, 0 when there are none
+    ///
+    /// entries are only referenced by the encoder once they are known to be received
+    pub fn insert_count_increment(&mut self, out: &mut [u8]) -> Result<usize, QpackError> {
+        let increment = self.insert_count - self.known_received_count;
+        if increment == 0 { return Ok(0); }
+        let len = DecoderInstruction::InsertCountIncrement(increment).write(out)?;
+        self.known_received_count = self.insert_count;
+        Ok(len)
+    }
+}
+
+/// encodes field sections, keeping a dynamic table of up to `N` bytes that is sent on the encoder stream
+///
+/// entries are only inserted while they fit without evicting any, and are only referenced once the peer's decoder
+/// acknowledged them, so field sections never block and acknowledged entries are never evicted. an error leaves
+/// the encoder out of step with the peer's decoder, so the connection can't be used any more
+#[derive(Debug, Clone)]
+pub struct QpackEncoder<const N: usize = DEFAULT_TABLE_SIZE> {
+    table: DynamicTable<N>,
+    max_capacity: usize,
+    max_entries: u64,
+    insert_count: u64,
+    known_received_count: u64,
+}
+
+impl<const N: usize> Default for QpackEncoder<N> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const N: usize> QpackEncoder<N> {
+    /// the dynamic table isn't used until the peer's settings arrive
+    pub fn new() -> Self {
+        Self { table: DynamicTable::new(0), max_capacity: 0, max_entries: 0, insert_count: 0, known_received_count: 0 }
+    }
+
+    /// apply the peer's `SETTINGS_QPACK_MAX_TABLE_CAPACITY`
+    pub fn set_max_table_capacity(&mut self, max_capacity: u64) {
+        self.max_entries = max_capacity / ENTRY_OVERHEAD as u64;
+        self.max_capacity = max_capacity.min(N as u64) as usize;
+    }
+
+    /// write a set dynamic table capacity instruction to the start of `out`, returns its length
+    ///
+    /// the capacity is capped at the peer's maximum and `N`, and can't be lowered below what is in use
+    pub fn set_table_capacity(&mut self, capacity: usize, out: &mut [u8]) -> Result<usize, QpackError> {
+        let capacity = capacity.min(self.max_capacity).max(self.table.size);
+        let len = EncoderInstruction::SetDynamicTableCapacity(capacity).write(out)?;
+        self.table.set_max_size(capacity);
+        Ok(len)
+    }
+
+    /// apply the complete instructions at the start of the peer's decoder stream, returns how many bytes were
+    /// used, the rest has to be kept until more of the stream arrives
+    pub fn recv_decoder_stream(&mut self, input: &[u8]) -> Result<usize, QpackError> {
+        let mut pos = 0;
+        while let Some((instruction, len)) = DecoderInstruction::parse(&input[pos..])? {
+            if let DecoderInstruction::InsertCountIncrement(increment) = instruction {
+                if increment == 0 || self.known_received_count + increment > self.insert_count {
+                    return Err(QpackError::DecoderStream);
+                }
+                self.known_received_count += increment;
+            }
+            // only acknowledged entries are referenced, so acknowledgments and cancellations tell nothing new
+            pos += len;
+        }
+        Ok(pos)
+    }
+
+    /// encode `headers` as a field section to the start of `out`, new dynamic table entries are inserted with
+    /// instructions written to the start of `encoder_stream`, returns the length of the section and of the
+    /// instructions
+    ///
+    /// `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie` are never indexed
+    pub fn encode(
+        &mut self,
+        headers: &[Header<'_>],
+        encoder_stream: &mut [u8],
+        out: &mut [u8],
+    ) -> Result<(usize, usize), QpackError> {
+        let fields = headers.iter()
+            .filter(|header| !header.name().as_str().is_empty())
+            .map(|header| (header.name().as_str(), header.value()));
+        self.encode_fields(fields, encoder_stream, out)
+    }
+
+    /// encode a field section of pseudo-headers and headers, e.g. a
+    /// [`RequestPseudoHeaders`](crate::http2::RequestPseudoHeaders)'s fields followed by the regular headers
+    ///
+    /// the names are sent lowercased, see [`encode`](Self::encode)
+    pub fn encode_fields<'f>(
+        &mut self,
+        fields: impl IntoIterator<Item = (&'f str, &'f str)>,
+        encoder_stream: &mut [u8],
+        out: &mut [u8],
+    ) -> Result<(usize, usize), QpackError> {
+        let (prefix, lines) = out.split_at_mut_checked(PREFIX_ROOM).ok_or(QpackError::BufferTooSmall)?;
+        let mut lines = BlockWriter::new(lines);
+        let mut instructions = 0;
+        // the base is the known received count, every reference is below it
+        let base = self.known_received_count;
+        let mut largest_reference = 0;
+        for (name, value) in fields {
+            let reference = self.encode_field(name, value, base, &mut lines, &mut encoder_stream[instructions..], &mut instructions)
+                .map_err(|_| QpackError::BufferTooSmall)?;
+            largest_reference = largest_reference.max(reference);
+        }
+
+        let required_insert_count = largest_reference;
+        let encoded_insert_count = match required_insert_count {
+            0 => 0,
+            count => count % (2 * self.max_entries) + 1,
+        };
+        let mut prefix = BlockWriter::new(prefix);
+        prefix.int(0, 8, encoded_insert_count as usize).map_err(|_| QpackError::BufferTooSmall)?;
+        let delta_base = if required_insert_count == 0 { 0 } else { base - required_insert_count };
+        prefix.int(0, 7, delta_base as usize).map_err(|_| QpackError::BufferTooSmall)?;
+
+        let (prefix_len, lines_len) = (prefix.pos, lines.pos);
+        out.copy_within(PREFIX_ROOM..PREFIX_ROOM + lines_len, prefix_len);
+        Ok((prefix_len + lines_len, instructions))
+    }
+
+    // encode one field line, returns one past the largest absolute index it refers to, 0 for none
+    fn encode_field(
+        &mut self,
+        name: &str,
+        value: &str,
+        base: u64,
+        lines: &mut BlockWriter<'_>,
+        encoder_stream: &mut [u8],
+        instructions: &mut usize,
+    ) -> Result<u64, HpackError> {
+        let sensitive = is_sensitive(name);
+        let (mut index, mut name_index) = (None, None);
+        for (i, &(entry_name, entry_value)) in STATIC_TABLE.iter().enumerate() {
+            if !entry_name.eq_ignore_ascii_case(name) { continue; }
+            name_index.get_or_insert(i);
+            if entry_value == value {
+                index = Some(i);
+                break;
+            }
+        }
+        if let (Some(index), false) = (index, sensitive) {
+            lines.int(0xc0, 6, index)?;
+            return Ok(0);
+        }
+
+        // dynamic entries by relative index, usable once acknowledged
+        let (dynamic_index, dynamic_name_index) = self.table.find(name, value);
+        let acknowledged = |relative: Option<usize>| {
+            relative.map(|relative| self.insert_count - 1 - relative as u64).filter(|&index| index < base)
+        };
+        let (acknowledged_index, acknowledged_name_index) = (acknowledged(dynamic_index), acknowledged(dynamic_name_index));
+        if let (Some(index), false) = (acknowledged_index, sensitive) {
+            lines.int(0x80, 6, (base - 1 - index) as usize)?;
+            return Ok(index + 1);
+        }
+
+        // insert the field for later sections while it fits without evicting
+        let size = name.len() + value.len() + ENTRY_OVERHEAD;
+        if !sensitive && dynamic_index.is_none() && self.table.size + size <= self.table.max_size {
+            let instruction = match (name_index, dynamic_name_index) {
+                (Some(index), _) => EncoderInstruction::InsertWithNameReference { static_table: true, index, value },
+                (None, Some(index)) => EncoderInstruction::InsertWithNameReference { static_table: false, index, value },
+                (None, None) => EncoderInstruction::InsertWithLiteralName { name, value },
+            };
+            *instructions += instruction.write(encoder_stream).map_err(|_| HpackError::BufferTooSmall)?;
+            self.table.insert(name.as_bytes(), value.as_bytes());
+            self.insert_count += 1;
+        }
+
+        let never_indexed = if sensitive { 0x20 } else { 0 };
+        let mut reference = 0;
+        match (name_index, acknowledged_name_index) {
+            (Some(index), _) => lines.int(0x50 | never_indexed, 4, index)?,
+            (None, Some(index)) => {
+                lines.int(0x40 | never_indexed, 4, (base - 1 - index) as usize)?;
+                reference = index + 1;
+            }
+            (None, None) => lines.string(0x20 | never_indexed >> 1, 3, name.as_bytes(), true)?,
+        }
+        lines.string(0, 7, value.as_bytes(), false)?;
+        Ok(reference)
+    }
+}
+
+/// the required insert count of a field section from its encoded form (rfc 9204 section 4.5.1.1)
+fn decode_insert_count(encoded: u64, max_entries: u64, total_inserts: u64) -> Option<u64> {
+    if encoded == 0 { return Some(0); }
+    let full_range = 2 * max_entries;
+    if encoded > full_range { return None; }
+    let max_value = total_inserts + max_entries;
+    let max_wrapped = max_value / full_range * full_range;
+    let mut required_insert_count = max_wrapped + encoded - 1;
+    if required_insert_count > max_value {
+        if required_insert_count <= full_range { return None; }
+        required_insert_count -= full_range;
+    }
+    (required_insert_count != 0).then_some(required_insert_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::EMPTY_HEADER;
+
+    const HEADERS: [(&str, &str); 4] = [(":method", "GET"), (":path", "/index.html"), ("x-trace", "abc"), ("cookie", "a=b")];
+
+    // standard names are decoded to their usual spelling
+    fn assert_fields(headers: &[Header<'_>]) {
+        for (header, (name, value)) in headers.iter().zip(HEADERS) {
+            assert!(header.name().as_str().eq_ignore_ascii_case(name), "{name}");
+            assert_eq!(header.value(), value);
+        }
+    }
+
+    #[test]
+    fn sections_refer_to_acknowledged_entries() {
+        let mut encoder = QpackEncoder::<256>::new();
+        let mut decoder = QpackDecoder::<256>::new();
+        encoder.set_max_table_capacity(decoder.max_table_capacity());
+        let (mut encoder_stream, mut decoder_stream) = ([0; 256], [0; 64]);
+        let (mut section, mut strings, mut buf) = ([0; 256], [0; 64], [0; 256]);
+
+        let mut instructions = encoder.set_table_capacity(256, &mut encoder_stream).unwrap();
+        let (len, inserts) = encoder.encode_fields(HEADERS, &mut encoder_stream[instructions..], &mut section).unwrap();
+        instructions += inserts;
+        assert!(inserts > 0);
+        assert_eq!(decoder.recv_encoder_stream(&encoder_stream[..instructions], &mut strings).unwrap(), instructions);
+        let insert_count = decoder.insert_count();
+        // nothing is acknowledged yet, so the first section is self-contained
+        let mut headers = [EMPTY_HEADER; 4];
+        let decoded = decoder.decode(&section[..len], &mut buf, &mut headers).unwrap();
+        assert_eq!(decoded, DecodedSection { count: 4, required_insert_count: 0 });
+        assert_fields(&headers);
+        assert_eq!(decoder.acknowledge_section(0, &decoded, &mut decoder_stream), Ok(0));
+
+        let acknowledged = decoder.insert_count_increment(&mut decoder_stream).unwrap();
+        assert_eq!(encoder.recv_decoder_stream(&decoder_stream[..acknowledged]), Ok(acknowledged));
+
+        let (second_len, inserts) = encoder.encode_fields(HEADERS, &mut encoder_stream, &mut section).unwrap();
+        assert_eq!(inserts, 0);
+        assert!(second_len < len);
+        let mut headers = [EMPTY_HEADER; 4];
+        let decoded = decoder.decode(&section[..second_len], &mut buf, &mut headers).unwrap();
+        assert_eq!(decoded.required_insert_count, insert_count);
+        assert_fields(&headers);
+        // cookies are never indexed
+        assert!(headers[3].is(crate::StandardHeaderName::Cookie));
+        let acknowledgment = decoder.acknowledge_section(4, &decoded, &mut decoder_stream).unwrap();
+        assert_eq!(DecoderInstruction::parse(&decoder_stream[..acknowledgment]),
+            Ok(Some((DecoderInstruction::SectionAcknowledgment(4), acknowledgment))));
+        assert_eq!(encoder.recv_decoder_stream(&decoder_stream[..acknowledgment]), Ok(acknowledgment));
+    }
+
+    #[test]
+    fn decoder_instructions_carry_62_bit_stream_ids() {
+        let mut out = [0; 16];
+        for instruction in [
+            DecoderInstruction::SectionAcknowledgment((1 << 62) - 1),
+            DecoderInstruction::StreamCancellation(1 << 32),
+            DecoderInstruction::InsertCountIncrement(63),
+        ] {
+            let len = instruction.write(&mut out).unwrap();
+            assert_eq!(DecoderInstruction::parse(&out[..len]), Ok(Some((instruction, len))));
+            assert_eq!(DecoderInstruction::parse(&out[..len - 1]), Ok(None));
+        }
+        // a stream id above 2^62 - 1, and one padded with a continuation byte too many
+        let len = DecoderInstruction::SectionAcknowledgment((1 << 62) - 1).write(&mut out).unwrap();
+        out[len - 1] += 1;
+        assert_eq!(DecoderInstruction::parse(&out[..len]), Err(QpackError::DecoderStream));
+        let padded = b"\xff\x80\x80\x80\x80\x80\x80\x80\x80\x80\x00";
+        assert_eq!(DecoderInstruction::parse(padded), Err(QpackError::DecoderStream));
+    }
+
+    #[test]
+    fn blocked_section_decodes_once_the_encoder_stream_arrives() {
+        let mut decoder = QpackDecoder::<256>::new();
+        let (mut strings, mut buf) = ([0; 64], [0; 64]);
+        // set capacity 220, insert `x-trace: abc` with a literal name
+        let encoder_stream = b"\x3f\xbd\x01\x47x-trace\x03abc";
+        // required insert count 1, base 1, an indexed field line with relative index 0
+        let section = b"\x02\x00\x80";
+        let mut headers = [EMPTY_HEADER; 1];
+        assert_eq!(decoder.decode(section, &mut [0; 64], &mut [EMPTY_HEADER; 1]), Err(QpackError::Blocked(1)));
+
+        // the instructions can arrive in pieces
+        assert_eq!(decoder.recv_encoder_stream(&encoder_stream[..5], &mut strings), Ok(3));
+        assert_eq!(decoder.insert_count(), 0);
+        assert_eq!(decoder.recv_encoder_stream(&encoder_stream[3..], &mut strings), Ok(encoder_stream.len() - 3));
+        assert_eq!(decoder.insert_count(), 1);
+
+        let decoded = decoder.decode(section, &mut buf, &mut headers).unwrap();
+        assert_eq!(decoded, DecodedSection { count: 1, required_insert_count: 1 });
+        assert_eq!(headers[0].name().as_str(), "x-trace");
+        assert_eq!(headers[0].value(), "abc");
+    }
+
+    #[test]
+    fn uppercase_names_are_rejected() {
+        let decoder = QpackDecoder::<256>::new();
+        let mut buf = [0; 64];
+        let mut headers = [EMPTY_HEADER; 1];
+        // a literal field line with the literal name `Content-Type`
+        let section = b"\x00\x00\x27\x05Content-Type\x01x";
+        assert_eq!(decoder.decode(section, &mut buf, &mut headers), Err(QpackError::UppercaseName));
+    }
+}
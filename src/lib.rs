@@ -1,14 +1,95 @@
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 pub use squid::{ Url, parse_url };
 mod status_code;
 mod header;
  mod parsers;
+mod syntax;
+mod media_type;
+mod negotiation;
+mod cookie;
+mod date;
+mod conditional;
+mod range;
+mod cache_control;
+mod cache;
+mod base64;
+mod hash;
+mod auth;
+mod websocket;
+mod sse;
+pub mod http2;
+pub mod http3;
+mod proxy;
+mod forwarded;
+mod multipart;
+mod form;
+mod content_disposition;
 pub use status_code::StatusCode;
-pub use header::Header;
+pub use header::{ Header, HeaderName, StandardHeaderName };
 pub use parsers::*;
+pub use syntax::{ List, Params };
+pub use media_type::{ MediaType, MediaTypeError };
+pub use negotiation::{
+    Quality, QualityItem, QualityList, NotAcceptable,
+    negotiate, negotiate_media_type, negotiate_language, negotiate_encoding, negotiate_charset,
+};
+pub use cookie::{ CookiePair, Cookies, SameSite, SetCookie, SetCookieError, parse_cookie };
+#[cfg(feature = "alloc")]
+pub use cookie::{ CookieJar, CookieJarError };
+pub use date::{ HttpDate, InvalidHttpDate };
+pub use conditional::{
+    EntityTag, EntityTags, InvalidEntityTag, Validators, Precondition,
+    evaluate, if_range_matches, is_any,
+};
+pub use range::{
+    RangeSpec, RangeSpecs, RangeError, ByteRange, ContentRange,
+    ByteRangesWriter, ByteRangesContentType, ByteRangesPartHeader, ByteRangesClosing,
+    parse_range, resolve_ranges,
+};
+pub use cache_control::{
+    CacheControl, Directive, Directives, ResponseTimes, Freshness, FreshnessState,
+    freshness, freshness_lifetime,
+};
+pub use cache::{
+    CacheKey, SecondaryKey, CacheEntry, CacheStore, Cache,
+    is_storable, is_heuristically_cacheable,
+};
+#[cfg(feature = "alloc")]
+pub use cache::{ MemoryStore, StoredResponse };
+pub use auth::{
+    AuthError, AuthParams, Challenge, Challenges, Credentials, BasicCredentials, BearerToken, ChallengeBuilder,
+    DigestAlgorithm, DigestError, DigestChallenge, DigestResponse, DigestClient, DigestAuthorization,
+    parse_challenges,
+};
+pub use websocket::{
+    WEBSOCKET_VERSION, HandshakeError, WebSocketKey, AcceptKey, WebSocketUpgrade,
+    Opcode, FrameError, Role, FrameHeader, Frame, CloseCode, CloseFrame,
+    upgrade_request, verify_response, apply_mask,
+};
+#[cfg(feature = "alloc")]
+pub use websocket::{ Message, MessageAssembler };
+pub use sse::{ EventStreamLine, EventStreamParser, SseEvent, SseComment };
+#[cfg(feature = "alloc")]
+pub use sse::{ DecodedEvent, EventStreamDecoder };
+pub use proxy::{
+    ProxyError, ProxyCommand, ProxyTransport, ProxyAddresses, ProxyHeader, Tlv, TlvIter, SslInfo, parse_proxy_header,
+};
+pub use forwarded::{
+    ForwardedError, NodeName, NodePort, Node, ForwardedElement, ForwardedClient,
+    forwarded_elements, x_forwarded_for, resolve_client,
+};
+pub use multipart::{
+    MultipartError, MultipartEvent, MultipartParser, Part, MultipartWriter,
+    MultipartContentType, MultipartPartHeader, MultipartClosing,
+};
+pub use form::{ FormError, FormPair, FormPairs, FormEncoded, FormSerializer, query_pairs, form_pairs, form_decode };
+pub use content_disposition::{ ContentDispositionError, ContentDisposition, ContentDispositionBuilder };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Method {
     /// request a representaion of the sepecified resource
     Get,
@@ -34,6 +115,27 @@ impl Default for Method {
     fn default() -> Self { Self::Get }
 }
 
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Head => "HEAD",
+            Self::Post => "POST",
+            Self::Patch => "PATCH",
+            Self::Put => "PUT",
+            Self::Delete => "DELETE",
+            Self::Trace => "TRACE",
+            Self::Connect => "CONNECT",
+            Self::Options => "OPTIONS",
+        }
+    }
+
+    /// methods that are essentially read-only (rfc 9110 section 9.2.1)
+    pub fn is_safe(&self) -> bool {
+        matches!(self, Self::Get | Self::Head | Self::Options | Self::Trace)
+    }
+}
+
 impl From<&str> for Method {
     fn from(value: &str) -> Self {
         match value {
@@ -51,7 +153,7 @@ impl From<&str> for Method {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Version {
     /// HTTP/1.0
     V10,
@@ -59,6 +161,8 @@ pub enum Version {
     V11,
     /// HTTP/2.0
     V20,
+    /// HTTP/3
+    V30,
 }
 
 impl Default for Version {
@@ -71,6 +175,7 @@ impl From<&str> for Version {
             "HTTP/1.0" => Self::V10,
             "HTTP/1.1" => Self::V11,
             "HTTP/2.0" => Self::V20,
+            "HTTP/3" => Self::V30,
             _ => Self::V11,
         }
     }
@@ -79,6 +184,7 @@ impl From<&str> for Version {
 pub struct Request<'r, 'h, B> {
     method: Method,
     target: Url<'r>,
+    raw_target: &'r str,
     version: Version,
     headers: &'h mut [Header<'r>],
     body: B,
@@ -101,6 +207,7 @@ impl<'r, 'h, B> Request<'r, 'h, B> {
         Self {
             method: Method::default(),
             target: parse_url("/").unwrap(),
+            raw_target: "/",
             version: Version::default(),
             headers,
             body,
@@ -108,8 +215,19 @@ impl<'r, 'h, B> Request<'r, 'h, B> {
     }
 
     pub fn method(&mut self, method: Method) { self.method = method; }
-    pub fn url_target(&mut self, target: &'r str) { self.target = parse_url(target).unwrap(); }
+    pub fn url_target(&mut self, target: &'r str) {
+        self.target = parse_url(target).unwrap();
+        self.raw_target = target;
+    }
     pub fn version(&mut self, version: Version) { self.version = version; }
+
+    /// the request target as it was sent, e.g. `/index.html?q=1`
+    pub fn target(&self) -> &'r str { self.raw_target }
+    pub fn headers(&self) -> &[Header<'r>] { self.headers }
+    /// the first header with the given name
+    pub fn header(&self, name: StandardHeaderName) -> Option<&Header<'r>> {
+        self.headers.iter().find(|header| header.is(name))
+    }
 }
 
 pub struct Response<'r, 'h, B> {
@@ -142,6 +260,12 @@ impl<'r, 'h, B> Response<'r, 'h, B> {
 
     pub fn version(&mut self, version: Version) { self.version = version; }
     pub fn status_code(&mut self, code: StatusCode) { self.status_code = code; }
+
+    pub fn headers(&self) -> &[Header<'r>] { self.headers }
+    /// the first header with the given name
+    pub fn header(&self, name: StandardHeaderName) -> Option<&Header<'r>> {
+        self.headers.iter().find(|header| header.is(name))
+    }
 }
 
 // #[cfg(test)]
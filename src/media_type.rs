@@ -0,0 +1,188 @@
+use crate::syntax::{ self, Params };
+
+/// a media type (or media range) such as `text/html; charset=utf-8`, borrowed from the header value
+#[derive(Debug, Clone, Copy)]
+pub struct MediaType<'m> {
+    ty: &'m str,
+    subtype: &'m str,
+    params: &'m str,
+}
+
+#[derive(Debug)]
+pub enum MediaTypeError {
+    MissingSubtype,
+    InvalidType,
+    InvalidSubtype,
+    InvalidParameter,
+}
+
+impl<'m> MediaType<'m> {
+    pub const STAR_STAR: MediaType<'static> = MediaType::from_static("*", "*");
+    pub const TEXT_PLAIN: MediaType<'static> = MediaType::from_static("text", "plain");
+    pub const TEXT_HTML: MediaType<'static> = MediaType::from_static("text", "html");
+    pub const TEXT_CSS: MediaType<'static> = MediaType::from_static("text", "css");
+    pub const TEXT_JAVASCRIPT: MediaType<'static> = MediaType::from_static("text", "javascript");
+    pub const TEXT_CSV: MediaType<'static> = MediaType::from_static("text", "csv");
+    pub const TEXT_EVENT_STREAM: MediaType<'static> = MediaType::from_static("text", "event-stream");
+    pub const APPLICATION_JSON: MediaType<'static> = MediaType::from_static("application", "json");
+    pub const APPLICATION_XML: MediaType<'static> = MediaType::from_static("application", "xml");
+    pub const APPLICATION_OCTET_STREAM: MediaType<'static> = MediaType::from_static("application", "octet-stream");
+    pub const APPLICATION_WWW_FORM_URLENCODED: MediaType<'static> = MediaType::from_static("application", "x-www-form-urlencoded");
+    pub const APPLICATION_PDF: MediaType<'static> = MediaType::from_static("application", "pdf");
+    pub const MULTIPART_FORM_DATA: MediaType<'static> = MediaType::from_static("multipart", "form-data");
+    pub const MULTIPART_BYTERANGES: MediaType<'static> = MediaType::from_static("multipart", "byteranges");
+    pub const IMAGE_PNG: MediaType<'static> = MediaType::from_static("image", "png");
+    pub const IMAGE_JPEG: MediaType<'static> = MediaType::from_static("image", "jpeg");
+    pub const IMAGE_GIF: MediaType<'static> = MediaType::from_static("image", "gif");
+    pub const IMAGE_SVG: MediaType<'static> = MediaType::from_static("image", "svg+xml");
+
+    const fn from_static(ty: &'static str, subtype: &'static str) -> MediaType<'static> {
+        MediaType { ty, subtype, params: "" }
+    }
+
+    pub fn parse(input: &'m str) -> Result<Self, MediaTypeError> {
+        let input = syntax::trim_ows(input);
+        let (essence, params) = input.split_once(';').unwrap_or((input, ""));
+        let (ty, subtype) = syntax::trim_ows(essence).split_once('/').ok_or(MediaTypeError::MissingSubtype)?;
+
+        if !syntax::is_token(ty) { return Err(MediaTypeError::InvalidType); }
+        if !syntax::is_token(subtype) { return Err(MediaTypeError::InvalidSubtype); }
+        for param in syntax::Split::new(params, b';') {
+            let (name, value) = param.split_once('=').ok_or(MediaTypeError::InvalidParameter)?;
+            let value = syntax::trim_ows(value);
+            let valid_value = syntax::is_token(value) || syntax::is_quoted_string(value);
+            if !syntax::is_token(syntax::trim_ows(name)) || !valid_value {
+                return Err(MediaTypeError::InvalidParameter);
+            }
+        }
+
+        Ok(Self { ty, subtype, params })
+    }
+
+    /// the top-level type, e.g. `text` in `text/html`
+    pub fn ty(&self) -> &'m str { self.ty }
+    /// the subtype, including any structured syntax suffix, e.g. `svg+xml`
+    pub fn subtype(&self) -> &'m str { self.subtype }
+    /// the subtype without its structured syntax suffix, e.g. `svg` in `image/svg+xml`
+    pub fn subtype_name(&self) -> &'m str {
+        self.subtype.rsplit_once('+').map_or(self.subtype, |(name, _)| name)
+    }
+    /// the structured syntax suffix, e.g. `xml` in `image/svg+xml`
+    pub fn suffix(&self) -> Option<&'m str> {
+        self.subtype.rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+    pub fn params(&self) -> Params<'m> { Params::new(self.params) }
+    /// look up a parameter by its (case-insensitive) name
+    pub fn param(&self, name: &str) -> Option<&'m str> { syntax::find_param(self.params, name) }
+    pub fn charset(&self) -> Option<&'m str> { self.param("charset") }
+    pub fn boundary(&self) -> Option<&'m str> { self.param("boundary") }
+
+    /// compare the type and subtype, ignoring parameters
+    pub fn essence_eq(&self, other: &MediaType<'_>) -> bool {
+        self.ty.eq_ignore_ascii_case(other.ty) && self.subtype.eq_ignore_ascii_case(other.subtype)
+    }
+
+    pub fn is_wildcard(&self) -> bool { self.ty == "*" || self.subtype == "*" }
+
+    /// check whether this media type falls within a media range such as `text/*` or `*/*`
+    ///
+    /// every parameter of the range has to be present (with an equal value) on this media type
+    pub fn matches(&self, range: &MediaType<'_>) -> bool {
+        let ty = range.ty == "*" || range.ty.eq_ignore_ascii_case(self.ty);
+        let subtype = range.subtype == "*" || range.subtype.eq_ignore_ascii_case(self.subtype);
+        ty && subtype && range.params().all(|(name, value)| {
+            self.param(name).is_some_and(|own| param_value_eq(name, own, value))
+        })
+    }
+
+    /// how specific a media range is, `*/*` < `type/*` < `type/subtype` < `type/subtype;params`
+    pub fn specificity(&self) -> usize {
+        match (self.ty, self.subtype) {
+            ("*", _) => 0,
+            (_, "*") => 1,
+            _ => 2 + self.params().count(),
+        }
+    }
+}
+
+// the charset parameter is case-insensitive, other parameter values are compared exactly
+fn param_value_eq(name: &str, a: &str, b: &str) -> bool {
+    if name.eq_ignore_ascii_case("charset") { a.eq_ignore_ascii_case(b) } else { a == b }
+}
+
+impl PartialEq for MediaType<'_> {
+    fn eq(&self, other: &MediaType<'_>) -> bool {
+        self.essence_eq(other)
+            && self.params().count() == other.params().count()
+            && self.params().all(|(name, value)| other.param(name).is_some_and(|v| param_value_eq(name, value, v)))
+    }
+}
+
+impl<'m> TryFrom<&'m str> for MediaType<'m> {
+    type Error = MediaTypeError;
+    fn try_from(value: &'m str) -> Result<Self, Self::Error> { Self::parse(value) }
+}
+
+impl core::fmt::Display for MediaType<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}/{}", self.ty, self.subtype)?;
+        for param in syntax::Split::new(self.params, b';') {
+            write!(f, "; {param}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::ToString, vec::Vec };
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let media_type = MediaType::parse(" Image/SVG+xml ;charset=UTF-8 ; q=0.5 ").unwrap();
+        assert_eq!((media_type.ty(), media_type.subtype()), ("Image", "SVG+xml"));
+        assert_eq!((media_type.subtype_name(), media_type.suffix()), ("SVG", Some("xml")));
+        assert_eq!(media_type.charset(), Some("UTF-8"));
+        assert_eq!(media_type.param("Q"), Some("0.5"));
+        assert_eq!(media_type.to_string(), "Image/SVG+xml; charset=UTF-8; q=0.5");
+        assert_eq!(MediaType::parse("text/plain").unwrap().suffix(), None);
+
+        assert!(matches!(MediaType::parse("text"), Err(MediaTypeError::MissingSubtype)));
+        assert!(matches!(MediaType::parse("te xt/plain"), Err(MediaTypeError::InvalidType)));
+        assert!(matches!(MediaType::parse("/plain"), Err(MediaTypeError::InvalidType)));
+        assert!(matches!(MediaType::parse("text/"), Err(MediaTypeError::InvalidSubtype)));
+        assert!(matches!(MediaType::parse("text/plain; charset"), Err(MediaTypeError::InvalidParameter)));
+        assert!(matches!(MediaType::parse("text/plain; charset=a b"), Err(MediaTypeError::InvalidParameter)));
+        assert!(matches!(MediaType::parse("text/plain; =utf-8"), Err(MediaTypeError::InvalidParameter)));
+    }
+
+    #[test]
+    fn quoted_parameters() {
+        let media_type = MediaType::parse(r#"multipart/form-data; boundary="a;b=\"c\""; x="""#).unwrap();
+        assert_eq!(media_type.boundary(), Some(r#"a;b=\"c\""#));
+        assert_eq!(media_type.param("x"), Some(""));
+        assert_eq!(media_type.params().map(|(name, _)| name).collect::<Vec<_>>(), ["boundary", "x"]);
+        assert!(matches!(MediaType::parse(r#"text/plain; a="b"#), Err(MediaTypeError::InvalidParameter)));
+    }
+
+    #[test]
+    fn comparison() {
+        let html = MediaType::parse("TEXT/Html; Charset=\"utf-8\"").unwrap();
+        assert!(html.essence_eq(&MediaType::TEXT_HTML));
+        assert!(!html.essence_eq(&MediaType::TEXT_PLAIN));
+        assert_ne!(html, MediaType::TEXT_HTML);
+        assert_eq!(html, MediaType::parse("text/html;charset=UTF-8").unwrap());
+        assert_ne!(html, MediaType::parse("text/html; charset=utf-8; level=1").unwrap());
+        assert_ne!(MediaType::parse("text/html; level=A").unwrap(), MediaType::parse("text/html; level=a").unwrap());
+
+        assert!(html.matches(&MediaType::STAR_STAR));
+        assert!(html.matches(&MediaType::parse("text/*; charset=UTF-8").unwrap()));
+        assert!(!html.matches(&MediaType::parse("text/*; level=1").unwrap()));
+        assert!(!html.matches(&MediaType::parse("image/*").unwrap()));
+        assert!(!MediaType::STAR_STAR.matches(&MediaType::TEXT_HTML));
+        assert_eq!([MediaType::STAR_STAR, MediaType::parse("text/*").unwrap(), MediaType::TEXT_HTML, html]
+            .map(|media_type| media_type.specificity()), [0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,431 @@
+use core::fmt::{ self, Display };
+use crate::{ ContentDisposition, ContentDispositionBuilder, ContentDispositionError, Header, HeaderName, MediaType, StandardHeaderName };
+use crate::syntax::{ self, Quoted };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultipartError {
+    /// the media type isn't `multipart/*` or has no `boundary`
+    MissingBoundary,
+    /// a boundary that is empty, longer than 70 characters or has characters not allowed by rfc 2046
+    InvalidBoundary,
+    /// a delimiter that isn't followed by a line break or `--`
+    InvalidDelimiter,
+    /// a part header that isn't utf-8 or has no colon
+    InvalidHeader,
+    /// a part has more headers than there are slots in the header buffer
+    HeaderBufferFull,
+}
+
+/// an event of a multipart body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultipartEvent<'i> {
+    /// the start of a part, its headers were put in this many slots at the start of the header buffer
+    Part(usize),
+    /// a chunk of the current part's body
+    Body(&'i [u8]),
+    /// the end of the current part's body
+    PartEnd,
+    /// the close delimiter, whatever follows is the epilogue and is ignored
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Body,
+    Epilogue,
+}
+
+/// parses a multipart body (rfc 2046 section 5.1) as its bytes arrive
+///
+/// part bodies are streamed out as they come in, only the bytes that may be the start of a delimiter are held
+/// back until more input shows whether they are. the headers of a part must arrive whole
+#[derive(Debug, Clone, Copy)]
+pub struct MultipartParser<'b> {
+    boundary: &'b str,
+    state: State,
+}
+
+impl<'b> MultipartParser<'b> {
+    pub fn new(boundary: &'b str) -> Result<Self, MultipartError> {
+        if !is_boundary(boundary) { return Err(MultipartError::InvalidBoundary); }
+        Ok(Self { boundary, state: State::Preamble })
+    }
+
+    /// a parser for the boundary of a `multipart/*` media type, e.g. `multipart/form-data; boundary=...`
+    pub fn from_media_type(media_type: &MediaType<'b>) -> Result<Self, MultipartError> {
+        if !media_type.ty().eq_ignore_ascii_case("multipart") { return Err(MultipartError::MissingBoundary); }
+        Self::new(media_type.boundary().ok_or(MultipartError::MissingBoundary)?)
+    }
+
+    /// whether the close delimiter has been seen
+    pub fn is_done(&self) -> bool { self.state == State::Epilogue }
+
+    /// the next event at the start of `input` and the number of bytes it took up, `None` if more input is needed
+    ///
+    /// the headers of a new part are put in the slots at the start of `headers`, the rest are emptied. once the
+    /// parser is done all input is ignored and `None` returned
+    pub fn next<'i>(
+        &mut self,
+        input: &'i [u8],
+        headers: &mut [Header<'i>],
+    ) -> Result<Option<(MultipartEvent<'i>, usize)>, MultipartError> {
+        // state is only updated once an event is returned, so input that is fed again is parsed the same way
+        let mut state = self.state;
+        let mut consumed = 0;
+        let event = loop {
+            let rest = &input[consumed..];
+            match state {
+                State::Preamble => {
+                    // the first delimiter may be at the very start, without the line break
+                    if consumed == 0 && starts_with_dash_boundary(rest, self.boundary) {
+                        consumed += 2 + self.boundary.len();
+                        state = State::Delimiter;
+                        continue;
+                    }
+                    // the preamble is skipped along with the first delimiter
+                    let Delimiter::Found(start) = find_delimiter(rest, self.boundary) else { return Ok(None) };
+                    consumed += start + self.delimiter_len();
+                    state = State::Delimiter;
+                }
+                State::Delimiter => {
+                    if rest.starts_with(b"--") {
+                        consumed += 2;
+                        state = State::Epilogue;
+                        break MultipartEvent::End;
+                    }
+                    // transport padding may follow the boundary
+                    let padding = rest.iter().take_while(|&&b| b == b' ' || b == b'\t').count();
+                    match &rest[padding..] {
+                        [b'\r', b'\n', ..] => {
+                            consumed += padding + 2;
+                            state = State::Headers;
+                        }
+                        [] | [b'\r'] => return Ok(None),
+                        [b'-'] if padding == 0 => return Ok(None),
+                        _ => return Err(MultipartError::InvalidDelimiter),
+                    }
+                }
+                State::Headers => {
+                    let Some((count, len)) = parse_headers(rest, headers)? else { return Ok(None) };
+                    consumed += len;
+                    state = State::Body;
+                    break MultipartEvent::Part(count);
+                }
+                State::Body => match find_delimiter(rest, self.boundary) {
+                    Delimiter::Found(0) => {
+                        consumed += self.delimiter_len();
+                        state = State::Delimiter;
+                        break MultipartEvent::PartEnd;
+                    }
+                    Delimiter::Found(end) | Delimiter::Partial(end) if end > 0 => {
+                        consumed += end;
+                        break MultipartEvent::Body(&rest[..end]);
+                    }
+                    _ => return Ok(None),
+                },
+                State::Epilogue => return Ok(None),
+            }
+        };
+        self.state = state;
+        Ok(Some((event, consumed)))
+    }
+
+    // `CRLF--boundary`
+    fn delimiter_len(&self) -> usize { 4 + self.boundary.len() }
+}
+
+enum Delimiter {
+    /// a whole delimiter starts at this index
+    Found(usize),
+    /// no delimiter, but the input from this index on may be the start of one
+    Partial(usize),
+}
+
+fn find_delimiter(input: &[u8], boundary: &str) -> Delimiter {
+    let boundary = boundary.as_bytes();
+    let is_delimiter_prefix = |start: usize| {
+        let candidate = &input[start..];
+        let (prefix, rest) = candidate.split_at(candidate.len().min(4));
+        b"\r\n--".starts_with(prefix) && boundary.starts_with(&rest[..rest.len().min(boundary.len())])
+    };
+    for start in (0..input.len()).filter(|&i| input[i] == b'\r') {
+        if !is_delimiter_prefix(start) { continue; }
+        return match input.len() - start >= 4 + boundary.len() {
+            true => Delimiter::Found(start),
+            false => Delimiter::Partial(start),
+        };
+    }
+    Delimiter::Partial(input.len())
+}
+
+fn starts_with_dash_boundary(input: &[u8], boundary: &str) -> bool {
+    input.strip_prefix(b"--").is_some_and(|rest| rest.starts_with(boundary.as_bytes()))
+}
+
+// the header lines up to and including the empty line, or `None` if it hasn't arrived yet
+fn parse_headers<'i>(input: &'i [u8], headers: &mut [Header<'i>]) -> Result<Option<(usize, usize)>, MultipartError> {
+    let len = match input {
+        [b'\r', b'\n', ..] => 2,
+        _ => match input.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(end) => end + 4,
+            None => return Ok(None),
+        },
+    };
+    let block = core::str::from_utf8(&input[..len - 2]).map_err(|_| MultipartError::InvalidHeader)?;
+    let mut count = 0;
+    for line in block.split_terminator("\r\n") {
+        let (name, value) = line.split_once(':').ok_or(MultipartError::InvalidHeader)?;
+        if !syntax::is_token(name) { return Err(MultipartError::InvalidHeader); }
+        let name = match StandardHeaderName::from_ignore_case(name) {
+            Some(name) => HeaderName::Standard(name),
+            None => HeaderName::Custom(name),
+        };
+        let slot = headers.get_mut(count).ok_or(MultipartError::HeaderBufferFull)?;
+        *slot = Header::new(name, syntax::trim_ows(value));
+        count += 1;
+    }
+    headers[count..].iter_mut().for_each(|header| *header = Header::default());
+    Ok(Some((count, len)))
+}
+
+// `bchars` from rfc 2046 section 5.1.1, the boundary can't end in a space
+fn is_boundary(boundary: &str) -> bool {
+    (1..=70).contains(&boundary.len()) && !boundary.ends_with(' ')
+        && boundary.bytes().all(|b| b.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(&b))
+}
+
+/// the headers of a part, as put in the header buffer on [`MultipartEvent::Part`]
+#[derive(Debug, Clone, Copy)]
+pub struct Part<'a, 'i> {
+    headers: &'a [Header<'i>],
+}
+
+impl<'a, 'i> Part<'a, 'i> {
+    pub fn new(headers: &'a [Header<'i>]) -> Self { Self { headers } }
+
+    pub fn headers(&self) -> &'a [Header<'i>] { self.headers }
+
+    pub fn header(&self, name: StandardHeaderName) -> Option<&'i str> {
+        self.headers.iter().find(|header| header.is(name)).map(Header::value)
+    }
+
+    /// the `Content-Disposition` of the part, `None` if it has none or it is malformed
+    pub fn content_disposition(&self) -> Option<ContentDisposition<'i>> {
+        ContentDisposition::parse(self.header(StandardHeaderName::ContentDisposition)?).ok()
+    }
+
+    /// the decoded `name` of the form field, see [`ContentDisposition::name`]
+    pub fn name<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b str>, ContentDispositionError>
+    where
+        'i: 'b,
+    {
+        match self.content_disposition() {
+            Some(content_disposition) => content_disposition.name(buf),
+            None => Ok(None),
+        }
+    }
+
+    /// the decoded `filename` of an uploaded file, see [`ContentDisposition::filename`]
+    pub fn filename<'b>(&self, buf: &'b mut [u8]) -> Result<Option<&'b str>, ContentDispositionError>
+    where
+        'i: 'b,
+    {
+        match self.content_disposition() {
+            Some(content_disposition) => content_disposition.filename(buf),
+            None => Ok(None),
+        }
+    }
+
+    /// the `Content-Type` of the part, `text/plain` when it has none
+    pub fn content_type(&self) -> Option<MediaType<'i>> {
+        match self.header(StandardHeaderName::ContentType) {
+            Some(content_type) => MediaType::parse(content_type).ok(),
+            None => Some(MediaType::TEXT_PLAIN),
+        }
+    }
+}
+
+/// writes a multipart body, the bytes of each part are written by the caller after its header
+#[derive(Debug, Clone, Copy)]
+pub struct MultipartWriter<'w> {
+    boundary: &'w str,
+}
+
+impl<'w> MultipartWriter<'w> {
+    /// `boundary` must not occur in any part, a long random string is the usual choice
+    pub fn new(boundary: &'w str) -> Result<Self, MultipartError> {
+        if !is_boundary(boundary) { return Err(MultipartError::InvalidBoundary); }
+        Ok(Self { boundary })
+    }
+
+    /// the `Content-Type` of the body, `multipart/form-data; boundary=...`
+    pub fn content_type(&self) -> MultipartContentType<'w> { MultipartContentType(self.boundary) }
+
+    /// the delimiter and `headers` preceding the bytes of a part, empty header slots are skipped
+    pub fn part_header<'a>(&self, headers: &'a [Header<'a>]) -> MultipartPartHeader<'a>
+    where
+        'w: 'a,
+    {
+        MultipartPartHeader { boundary: self.boundary, headers, form_data: None }
+    }
+
+    /// the delimiter and headers preceding the value of a form field, with its `Content-Disposition` and, for a
+    /// file, its `Content-Type`
+    pub fn form_data<'a>(
+        &self,
+        name: &'a str,
+        filename: Option<&'a str>,
+        content_type: Option<&'a str>,
+    ) -> MultipartPartHeader<'a>
+    where
+        'w: 'a,
+    {
+        let mut disposition = ContentDispositionBuilder::form_data(name);
+        if let Some(filename) = filename { disposition.set_filename(filename); }
+        MultipartPartHeader { boundary: self.boundary, headers: &[], form_data: Some((disposition, content_type)) }
+    }
+
+    /// the close delimiter, after the last part
+    pub fn closing(&self) -> MultipartClosing<'w> { MultipartClosing(self.boundary) }
+}
+
+pub struct MultipartContentType<'w>(&'w str);
+
+impl Display for MultipartContentType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match syntax::is_token(self.0) {
+            true => write!(f, "multipart/form-data; boundary={}", self.0),
+            false => write!(f, "multipart/form-data; boundary={}", Quoted(self.0)),
+        }
+    }
+}
+
+pub struct MultipartPartHeader<'a> {
+    boundary: &'a str,
+    headers: &'a [Header<'a>],
+    /// the `Content-Disposition` and `Content-Type` of a form field
+    form_data: Option<(ContentDispositionBuilder<'a>, Option<&'a str>)>,
+}
+
+impl Display for MultipartPartHeader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\r\n--{}\r\n", self.boundary)?;
+        if let Some((disposition, content_type)) = self.form_data {
+            write!(f, "Content-Disposition: {disposition}\r\n")?;
+            if let Some(content_type) = content_type { write!(f, "Content-Type: {content_type}\r\n")?; }
+        }
+        for header in self.headers.iter().filter(|header| !header.name().as_str().is_empty()) {
+            write!(f, "{}: {}\r\n", header.name().as_str(), header.value())?;
+        }
+        f.write_str("\r\n")
+    }
+}
+
+pub struct MultipartClosing<'w>(&'w str);
+
+impl Display for MultipartClosing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\r\n--{}--\r\n", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ format, string::String, vec::Vec };
+    use super::*;
+    use crate::header::EMPTY_HEADER;
+
+    #[test]
+    fn form_data_round_trips() {
+        let writer = MultipartWriter::new("XyZ").unwrap();
+        let body = format!("{}data{}", writer.form_data("a\"b", Some("C:\\f\r\n.txt"), Some("text/plain")), writer.closing());
+        let mut parser = MultipartParser::new("XyZ").unwrap();
+        let mut headers = [EMPTY_HEADER; 4];
+        let (event, _) = parser.next(body.as_bytes(), &mut headers).unwrap().unwrap();
+        let MultipartEvent::Part(count) = event else { panic!("{event:?}") };
+        let part = Part::new(&headers[..count]);
+        let mut buf = [0; 32];
+        assert_eq!(part.name(&mut buf), Ok(Some("a\"b")));
+        assert_eq!(part.filename(&mut buf), Ok(Some("C:\\f\r\n.txt")));
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Part(Vec<(String, String)>),
+        Body(Vec<u8>),
+        PartEnd,
+        End,
+    }
+
+    // parses the body as it arrives in chunks of `chunk_size`, joining the body chunks of each part
+    fn parse(boundary: &str, body: &[u8], chunk_size: usize) -> Result<Vec<Event>, MultipartError> {
+        let mut parser = MultipartParser::new(boundary)?;
+        let mut events = Vec::new();
+        let mut buf = Vec::new();
+        for chunk in body.chunks(chunk_size) {
+            buf.extend_from_slice(chunk);
+            let mut headers = [EMPTY_HEADER; 4];
+            let mut consumed = 0;
+            while let Some((event, len)) = parser.next(&buf[consumed..], &mut headers)? {
+                assert!(len > 0);
+                consumed += len;
+                match (event, events.last_mut()) {
+                    (MultipartEvent::Part(count), _) => events.push(Event::Part(headers[..count].iter()
+                        .map(|header| (header.name().as_str().into(), header.value().into()))
+                        .collect())),
+                    (MultipartEvent::Body(data), Some(Event::Body(body))) => body.extend_from_slice(data),
+                    (MultipartEvent::Body(data), _) => events.push(Event::Body(data.into())),
+                    (MultipartEvent::PartEnd, _) => events.push(Event::PartEnd),
+                    (MultipartEvent::End, _) => events.push(Event::End),
+                }
+            }
+            buf.drain(..consumed);
+        }
+        Ok(events)
+    }
+
+    #[test]
+    fn split_chunks_give_the_same_events() {
+        let body = b"preamble\r\n--XyZ \t\r\n\
+            Content-Disposition: form-data; name=\"a\"\r\nX-Custom:  1 \r\n\r\n\
+            one\r\n--Xy\r\n-\r\n--XyZ\r\n\
+            \r\n\
+            \r\r\n\r\n--XyZ--epilogue\r\n--XyZ\r\n";
+        let expected = [
+            Event::Part([("Content-Disposition", "form-data; name=\"a\""), ("X-Custom", "1")]
+                .map(|(name, value)| (name.into(), value.into())).into()),
+            Event::Body(b"one\r\n--Xy\r\n-".into()),
+            Event::PartEnd,
+            Event::Part(Vec::new()),
+            Event::Body(b"\r\r\n".into()),
+            Event::PartEnd,
+            Event::End,
+        ];
+        for chunk_size in 1..=body.len() {
+            assert_eq!(parse("XyZ", body, chunk_size).as_deref(), Ok(&expected[..]), "chunks of {chunk_size}");
+        }
+
+        // an empty body and a delimiter right at the start
+        let body = b"--XyZ\r\n\r\n\r\n--XyZ--";
+        for chunk_size in 1..=body.len() {
+            assert_eq!(parse("XyZ", body, chunk_size), Ok([Event::Part(Vec::new()), Event::PartEnd, Event::End].into()));
+        }
+    }
+
+    #[test]
+    fn split_chunks_give_the_same_errors() {
+        for (body, error) in [
+            (&b"--XyZ\r\nno colon\r\n\r\n"[..], MultipartError::InvalidHeader),
+            (b"--XyZ\r\na: 1\r\nb: 2\r\nc: 3\r\nd: 4\r\ne: 5\r\n\r\n", MultipartError::HeaderBufferFull),
+            (b"--XyZ\r\n\r\nbody\r\n--XyZx\r\n", MultipartError::InvalidDelimiter),
+        ] {
+            for chunk_size in 1..=body.len() {
+                assert_eq!(parse("XyZ", body, chunk_size).map(drop), Err(error), "chunks of {chunk_size}");
+            }
+        }
+    }
+}
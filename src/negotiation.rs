@@ -0,0 +1,238 @@
+use crate::{ Header, MediaType, StatusCode };
+use crate::header::StandardHeaderName;
+use crate::syntax::{ self, Split };
+
+/// a `q=` weight, stored in thousandths (`0.5` is `Quality(500)`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Quality(u16);
+
+impl Quality {
+    pub const MIN: Quality = Quality(0);
+    pub const MAX: Quality = Quality(1000);
+
+    /// parse a `qvalue` (`0`, `0.x`, `1`, `1.000`, up to three decimal places)
+    pub fn parse(input: &str) -> Option<Self> {
+        let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+        if fraction.len() > 3 || !fraction.bytes().all(|b| b.is_ascii_digit()) { return None; }
+        let mut thousandths = 0;
+        for (idx, digit) in fraction.bytes().enumerate() {
+            thousandths += (digit - b'0') as u16 * [100, 10, 1][idx];
+        }
+        match whole {
+            "0" => Some(Self(thousandths)),
+            "1" if thousandths == 0 => Some(Self::MAX),
+            _ => None,
+        }
+    }
+
+    pub fn thousandths(&self) -> u16 { self.0 }
+    /// a weight of zero means "not acceptable"
+    pub fn is_acceptable(&self) -> bool { self.0 > 0 }
+}
+
+impl Default for Quality {
+    fn default() -> Self { Self::MAX }
+}
+
+/// one element of a quality-weighted list, e.g. `text/html;level=1;q=0.7`
+#[derive(Debug, Clone, Copy)]
+pub struct QualityItem<'q> {
+    value: &'q str,
+    quality: Quality,
+}
+
+impl<'q> QualityItem<'q> {
+    /// the element without its weight (and any parameters following the weight)
+    pub fn value(&self) -> &'q str { self.value }
+    pub fn quality(&self) -> Quality { self.quality }
+
+    fn parse(element: &'q str) -> Option<Self> {
+        let mut params = Split::new(element, b';');
+        let first = params.next()?;
+        let mut value_end = first.len();
+        for param in params {
+            match syntax::param(param) {
+                Some((name, weight)) if name.eq_ignore_ascii_case("q") => {
+                    let quality = Quality::parse(weight)?;
+                    let value = syntax::trim_ows(&element[..value_end]);
+                    return Some(Self { value, quality });
+                }
+                // extend the value up to (and including) this parameter
+                _ => value_end = param.as_ptr() as usize - element.as_ptr() as usize + param.len(),
+            }
+        }
+        Some(Self { value: syntax::trim_ows(&element[..value_end]), quality: Quality::MAX })
+    }
+}
+
+/// the elements of a quality-weighted list header (`Accept`, `Accept-Language`, ...)
+///
+/// elements with a malformed weight are skipped
+pub struct QualityList<'q>(Split<'q>);
+
+impl<'q> QualityList<'q> {
+    pub fn new(input: &'q str) -> Self { Self(syntax::list(input)) }
+}
+
+impl<'q> Iterator for QualityList<'q> {
+    type Item = QualityItem<'q>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(QualityItem::parse)
+    }
+}
+
+/// none of the available representations are acceptable to the client
+#[derive(Debug)]
+pub struct NotAcceptable;
+
+impl From<NotAcceptable> for StatusCode {
+    fn from(_: NotAcceptable) -> Self { StatusCode::NotAcceptable }
+}
+
+/// pick the best of the `available` representations (in order of server preference) for an `Accept*` header
+///
+/// the matching rules depend on the header, `Accept` is matched by media range, `Accept-Language` by
+/// language prefix, `Accept-Encoding` and `Accept-Charset` (and any other header) by token
+pub fn negotiate<'a>(available: &[&'a str], header: &Header<'_>) -> Result<&'a str, NotAcceptable> {
+    if header.is(StandardHeaderName::Accept) { return negotiate_media_type(available, header.value()); }
+    if header.is(StandardHeaderName::AcceptLanguage) { return negotiate_language(available, header.value()); }
+    if header.is(StandardHeaderName::AcceptEncoding) { return negotiate_encoding(available, header.value()); }
+    negotiate_charset(available, header.value())
+}
+
+/// choose a media type for an `Accept` header, the most specific matching media range decides its weight
+pub fn negotiate_media_type<'a>(available: &[&'a str], accept: &str) -> Result<&'a str, NotAcceptable> {
+    best(available, |candidate| {
+        let candidate = MediaType::parse(candidate).ok()?;
+        let mut best: Option<(usize, Quality)> = None;
+        for item in QualityList::new(accept) {
+            let Ok(range) = MediaType::parse(item.value()) else { continue };
+            if !candidate.matches(&range) { continue; }
+            if best.is_none_or(|(specificity, _)| range.specificity() > specificity) {
+                best = Some((range.specificity(), item.quality()));
+            }
+        }
+        best.map(|(_, quality)| quality)
+    })
+}
+
+/// choose a language tag for an `Accept-Language` header using basic filtering (rfc 4647 section 3.3.1)
+///
+/// a range matches a tag when it equals the tag or a prefix of it ending at a `-`, the longest matching range
+/// decides the weight
+pub fn negotiate_language<'a>(available: &[&'a str], accept_language: &str) -> Result<&'a str, NotAcceptable> {
+    best(available, |candidate| {
+        let mut best: Option<(usize, Quality)> = None;
+        for item in QualityList::new(accept_language) {
+            let range = item.value();
+            let matches = range == "*" || candidate.get(..range.len()).is_some_and(|prefix| {
+                prefix.eq_ignore_ascii_case(range)
+                    && matches!(candidate.as_bytes().get(range.len()), None | Some(b'-'))
+            });
+            let specificity = if range == "*" { 0 } else { range.len() };
+            if matches && best.is_none_or(|(longest, _)| specificity > longest) {
+                best = Some((specificity, item.quality()));
+            }
+        }
+        best.map(|(_, quality)| quality)
+    })
+}
+
+/// choose a content coding for an `Accept-Encoding` header
+///
+/// `identity` is always acceptable unless it is excluded (by `identity;q=0` or `*;q=0`)
+pub fn negotiate_encoding<'a>(available: &[&'a str], accept_encoding: &str) -> Result<&'a str, NotAcceptable> {
+    best(available, |candidate| {
+        let candidate = candidate.strip_prefix("x-").unwrap_or(candidate);
+        match token_quality(candidate, accept_encoding, |coding| coding.strip_prefix("x-").unwrap_or(coding)) {
+            None if candidate.eq_ignore_ascii_case("identity") => Some(Quality::MAX),
+            quality => quality,
+        }
+    })
+}
+
+/// choose a charset for an `Accept-Charset` header
+pub fn negotiate_charset<'a>(available: &[&'a str], accept_charset: &str) -> Result<&'a str, NotAcceptable> {
+    best(available, |candidate| token_quality(candidate, accept_charset, |charset| charset))
+}
+
+// the weight of an exact (case-insensitive) match, falling back to the weight of `*`
+fn token_quality(candidate: &str, header: &str, normalize: impl Fn(&str) -> &str) -> Option<Quality> {
+    let mut wildcard = None;
+    for item in QualityList::new(header) {
+        if item.value() == "*" {
+            wildcard.get_or_insert(item.quality());
+        } else if normalize(item.value()).eq_ignore_ascii_case(candidate) {
+            return Some(item.quality());
+        }
+    }
+    wildcard
+}
+
+// the first of the highest weighted acceptable candidates
+fn best<'a>(available: &[&'a str], quality: impl Fn(&str) -> Option<Quality>) -> Result<&'a str, NotAcceptable> {
+    let mut best: Option<(&'a str, Quality)> = None;
+    for &candidate in available {
+        let Some(quality) = quality(candidate) else { continue };
+        if quality.is_acceptable() && best.is_none_or(|(_, current)| quality > current) {
+            best = Some((candidate, quality));
+        }
+    }
+    best.map(|(candidate, _)| candidate).ok_or(NotAcceptable)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+    use super::*;
+    use crate::HeaderName;
+
+    #[test]
+    fn quality_values() {
+        assert_eq!(Quality::parse("1"), Some(Quality::MAX));
+        assert_eq!(Quality::parse("1.000"), Some(Quality::MAX));
+        assert_eq!(Quality::parse("0.5"), Some(Quality(500)));
+        assert_eq!(Quality::parse("0.05"), Some(Quality(50)));
+        assert_eq!(Quality::parse("0.001").map(|q| q.thousandths()), Some(1));
+        assert_eq!(Quality::parse("0."), Some(Quality::MIN));
+        for invalid in ["1.001", "2", "0.0001", "-0", ".5", "0.5x", "", " 1"] {
+            assert_eq!(Quality::parse(invalid), None, "{invalid}");
+        }
+
+        let items: Vec<_> = QualityList::new("text/html;level=1;q=0.7;ext=x, text/*;Q=0, image/png;q=2, , */*")
+            .map(|item| (item.value(), item.quality().thousandths()))
+            .collect();
+        assert_eq!(items, [("text/html;level=1", 700), ("text/*", 0), ("*/*", 1000)]);
+    }
+
+    #[test]
+    fn most_specific_range_decides() {
+        let accept = "text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5";
+        assert_eq!(negotiate_media_type(&["text/plain", "image/png"], accept).unwrap(), "image/png");
+        assert_eq!(negotiate_media_type(&["text/plain", "text/html"], accept).unwrap(), "text/html");
+        assert_eq!(negotiate_media_type(&["text/html", "text/html;level=1"], accept).unwrap(), "text/html;level=1");
+        // ties go to the server's preference
+        assert_eq!(negotiate_media_type(&["image/gif", "image/png"], accept).unwrap(), "image/gif");
+
+        assert_eq!(negotiate_language(&["en", "en-GB", "fr"], "en-gb, en;q=0.8, *;q=0.1").unwrap(), "en-GB");
+        assert_eq!(negotiate_language(&["fr", "eng"], "en, *;q=0.1").unwrap(), "fr");
+    }
+
+    #[test]
+    fn zero_quality_excludes() {
+        assert!(negotiate_media_type(&["text/html"], "text/html;q=0, */*").is_err());
+        assert_eq!(negotiate_media_type(&["text/html", "text/plain"], "text/html;q=0, */*").unwrap(), "text/plain");
+        assert!(negotiate_language(&["en-US"], "en;q=0, *").is_err());
+
+        assert_eq!(negotiate_encoding(&["br", "identity"], "gzip").unwrap(), "identity");
+        assert_eq!(negotiate_encoding(&["x-gzip", "identity"], "gzip, identity;q=0.5").unwrap(), "x-gzip");
+        assert!(negotiate_encoding(&["identity"], "gzip, *;q=0").is_err());
+        assert!(negotiate_encoding(&["identity"], "identity;q=0").is_err());
+        assert!(negotiate_charset(&["utf-8"], "iso-8859-1, *;q=0").is_err());
+        assert_eq!(negotiate_charset(&["iso-8859-1", "utf-8"], "UTF-8, *;q=0.5").unwrap(), "utf-8");
+
+        let accept = Header::new(HeaderName::Standard(StandardHeaderName::Accept), "application/json;q=0");
+        assert_eq!(negotiate(&["application/json"], &accept).map_err(StatusCode::from), Err(StatusCode::NotAcceptable));
+    }
+}
@@ -0,0 +1,455 @@
+use core::net::{ IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr };
+
+/// the longest v1 header, including the CRLF
+const V1_MAX_LEN: usize = 107;
+const V1_PREFIX: &[u8; 6] = b"PROXY ";
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyError {
+    /// more input is needed, at least this many bytes in total
+    Incomplete(usize),
+    /// the input doesn't start with a PROXY protocol header
+    Missing,
+    /// a malformed header, or an unknown version, command or address family
+    Invalid,
+}
+
+/// whether the proxied connection carries a client's traffic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyCommand {
+    /// a connection of the proxy itself, e.g. a health check, which uses the connection's own addresses
+    Local,
+    Proxy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyTransport {
+    Unspecified,
+    Stream,
+    Datagram,
+}
+
+/// the original addresses of a proxied connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyAddresses<'p> {
+    /// unknown or not sent, the connection's own addresses are used
+    Unknown,
+    Inet { source: SocketAddr, destination: SocketAddr },
+    /// unix socket paths, without the trailing NULs
+    Unix { source: &'p [u8], destination: &'p [u8] },
+}
+
+/// a PROXY protocol header (haproxy's proxy-protocol.txt), borrowing from the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader<'p> {
+    /// 1 for the text header, 2 for the binary one
+    pub version: u8,
+    pub command: ProxyCommand,
+    pub transport: ProxyTransport,
+    pub addresses: ProxyAddresses<'p>,
+    tlvs: &'p [u8],
+}
+
+impl<'p> ProxyHeader<'p> {
+    /// the client's address, `None` when the connection's own address is to be used
+    pub fn source(&self) -> Option<SocketAddr> {
+        match self.addresses {
+            ProxyAddresses::Inet { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// the address the client connected to, `None` when the connection's own address is to be used
+    pub fn destination(&self) -> Option<SocketAddr> {
+        match self.addresses {
+            ProxyAddresses::Inet { destination, .. } => Some(destination),
+            _ => None,
+        }
+    }
+
+    /// the (already validated) type-length-values of a v2 header
+    pub fn tlvs(&self) -> TlvIter<'p> { TlvIter(self.tlvs) }
+
+    /// the protocol negotiated with ALPN, e.g. `h2`
+    pub fn alpn(&self) -> Option<&'p [u8]> {
+        self.tlvs().find_map(|tlv| match tlv {
+            Tlv::Alpn(alpn) => Some(alpn),
+            _ => None,
+        })
+    }
+
+    /// the host name the client sent with SNI
+    pub fn authority(&self) -> Option<&'p str> {
+        self.tlvs().find_map(|tlv| match tlv {
+            Tlv::Authority(authority) => Some(authority),
+            _ => None,
+        })
+    }
+
+    /// the tls details of the client's connection
+    pub fn ssl(&self) -> Option<SslInfo<'p>> {
+        self.tlvs().find_map(|tlv| match tlv {
+            Tlv::Ssl(ssl) => Some(ssl),
+            _ => None,
+        })
+    }
+}
+
+/// a type-length-value of a v2 header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tlv<'p> {
+    Alpn(&'p [u8]),
+    Authority(&'p str),
+    /// the crc32c checksum of the whole header, with this value zeroed
+    Crc32c(u32),
+    /// padding to be ignored
+    Noop,
+    UniqueId(&'p [u8]),
+    Ssl(SslInfo<'p>),
+    /// the network namespace the connection was accepted in
+    Netns(&'p str),
+    /// other and custom types, also those with a value that isn't utf-8 where a string is expected
+    Unknown(u8, &'p [u8]),
+}
+
+impl<'p> Tlv<'p> {
+    fn new(ty: u8, value: &'p [u8]) -> Self {
+        let string = core::str::from_utf8(value);
+        match (ty, string) {
+            (0x01, _) => Self::Alpn(value),
+            (0x02, Ok(authority)) => Self::Authority(authority),
+            (0x03, _) if value.len() == 4 => Self::Crc32c(u32::from_be_bytes([value[0], value[1], value[2], value[3]])),
+            (0x04, _) => Self::Noop,
+            (0x05, _) => Self::UniqueId(value),
+            (0x20, _) => SslInfo::parse(value).map_or(Self::Unknown(ty, value), Self::Ssl),
+            (0x30, Ok(netns)) => Self::Netns(netns),
+            _ => Self::Unknown(ty, value),
+        }
+    }
+}
+
+/// iterates over the type-length-values of a v2 header, or the sub-values of an ssl value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlvIter<'p>(&'p [u8]);
+
+impl<'p> TlvIter<'p> {
+    fn next_raw(&mut self) -> Option<(u8, &'p [u8])> {
+        let (&[ty, a, b], rest) = self.0.split_first_chunk::<3>()?;
+        let len = u16::from_be_bytes([a, b]) as usize;
+        let value = rest.get(..len)?;
+        self.0 = &rest[len..];
+        Some((ty, value))
+    }
+
+    // whether the values exactly fill the input
+    fn is_valid(mut self) -> bool {
+        while self.next_raw().is_some() {}
+        self.0.is_empty()
+    }
+}
+
+impl<'p> Iterator for TlvIter<'p> {
+    type Item = Tlv<'p>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_raw().map(|(ty, value)| Tlv::new(ty, value))
+    }
+}
+
+/// the tls details of the client's connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SslInfo<'p> {
+    /// `PP2_CLIENT_SSL` (0x1), `PP2_CLIENT_CERT_CONN` (0x2) and `PP2_CLIENT_CERT_SESS` (0x4)
+    pub client: u8,
+    /// 0 when a client certificate was presented and verified
+    pub verify: u32,
+    /// the tls version, e.g. `TLSv1.3`
+    pub version: Option<&'p str>,
+    /// the common name of the client certificate's subject
+    pub common_name: Option<&'p str>,
+    pub cipher: Option<&'p str>,
+    pub signature_algorithm: Option<&'p str>,
+    pub key_algorithm: Option<&'p str>,
+}
+
+impl<'p> SslInfo<'p> {
+    fn parse(value: &'p [u8]) -> Option<Self> {
+        let (&[client, a, b, c, d], sub) = value.split_first_chunk::<5>()?;
+        if !TlvIter(sub).is_valid() { return None; }
+        let mut ssl = Self {
+            client,
+            verify: u32::from_be_bytes([a, b, c, d]),
+            version: None,
+            common_name: None,
+            cipher: None,
+            signature_algorithm: None,
+            key_algorithm: None,
+        };
+        let mut sub = TlvIter(sub);
+        while let Some((ty, value)) = sub.next_raw() {
+            let field = match ty {
+                0x21 => &mut ssl.version,
+                0x22 => &mut ssl.common_name,
+                0x23 => &mut ssl.cipher,
+                0x24 => &mut ssl.signature_algorithm,
+                0x25 => &mut ssl.key_algorithm,
+                _ => continue,
+            };
+            *field = core::str::from_utf8(value).ok();
+        }
+        Some(ssl)
+    }
+
+    /// whether the client connected over tls
+    pub fn is_tls(&self) -> bool { self.client & 0x1 != 0 }
+    /// whether the client presented a certificate that was verified
+    pub fn is_verified(&self) -> bool { self.client & 0x2 != 0 && self.verify == 0 }
+}
+
+/// parse a v1 or v2 PROXY protocol header from the start of a connection, returns the header and the offset the
+/// http message starts at, e.g. for [`parse_request`](crate::parse_request)
+///
+/// only connections from trusted proxies may send one, anyone else could claim any address
+pub fn parse_proxy_header(input: &[u8]) -> Result<(ProxyHeader<'_>, usize), ProxyError> {
+    let prefix = |signature: &[u8]| {
+        let len = input.len().min(signature.len());
+        input[..len] == signature[..len]
+    };
+    match (prefix(V1_PREFIX), prefix(V2_SIGNATURE)) {
+        (true, _) if input.len() < V1_PREFIX.len() => Err(ProxyError::Incomplete(V1_PREFIX.len())),
+        (true, _) => parse_v1(input),
+        (_, true) if input.len() < V2_SIGNATURE.len() => Err(ProxyError::Incomplete(V2_SIGNATURE.len())),
+        (_, true) => parse_v2(input),
+        _ => Err(ProxyError::Missing),
+    }
+}
+
+fn parse_v1(input: &[u8]) -> Result<(ProxyHeader<'_>, usize), ProxyError> {
+    let line = &input[..input.len().min(V1_MAX_LEN)];
+    let Some(end) = line.windows(2).position(|crlf| crlf == b"\r\n") else {
+        return Err(match line.len() {
+            V1_MAX_LEN => ProxyError::Invalid,
+            len => ProxyError::Incomplete(len + 1),
+        });
+    };
+    let line = core::str::from_utf8(&line[V1_PREFIX.len()..end]).map_err(|_| ProxyError::Invalid)?;
+    let mut fields = line.split(' ');
+    let mut header = ProxyHeader {
+        version: 1,
+        command: ProxyCommand::Proxy,
+        transport: ProxyTransport::Stream,
+        addresses: ProxyAddresses::Unknown,
+        tlvs: &[],
+    };
+    let ipv6 = match fields.next() {
+        Some("TCP4") => false,
+        Some("TCP6") => true,
+        // the rest of the line is ignored
+        Some("UNKNOWN") => {
+            header.transport = ProxyTransport::Unspecified;
+            return Ok((header, end + 2));
+        }
+        _ => return Err(ProxyError::Invalid),
+    };
+    let mut field = || fields.next().ok_or(ProxyError::Invalid);
+    let (source, destination) = (field()?, field()?);
+    let ip = |address: &str| match ipv6 {
+        false => address.parse::<Ipv4Addr>().map(IpAddr::V4),
+        true => address.parse::<Ipv6Addr>().map(IpAddr::V6),
+    }.map_err(|_| ProxyError::Invalid);
+    let port = |port: &str| match port.as_bytes() {
+        [b'0', _, ..] => Err(ProxyError::Invalid),
+        _ => port.parse::<u16>().map_err(|_| ProxyError::Invalid),
+    };
+    let (source_port, destination_port) = (field()?, field()?);
+    if fields.next().is_some() { return Err(ProxyError::Invalid); }
+    header.addresses = ProxyAddresses::Inet {
+        source: SocketAddr::new(ip(source)?, port(source_port)?),
+        destination: SocketAddr::new(ip(destination)?, port(destination_port)?),
+    };
+    Ok((header, end + 2))
+}
+
+fn parse_v2(input: &[u8]) -> Result<(ProxyHeader<'_>, usize), ProxyError> {
+    let Some(&[version_command, family, a, b]) = input.get(12..16).and_then(|fixed| fixed.first_chunk::<4>()) else {
+        return Err(ProxyError::Incomplete(16));
+    };
+    let len = 16 + u16::from_be_bytes([a, b]) as usize;
+    let payload = input.get(16..len).ok_or(ProxyError::Incomplete(len))?;
+    let command = match version_command {
+        0x20 => ProxyCommand::Local,
+        0x21 => ProxyCommand::Proxy,
+        _ => return Err(ProxyError::Invalid),
+    };
+    let transport = match family & 0x0f {
+        0x0 => ProxyTransport::Unspecified,
+        0x1 => ProxyTransport::Stream,
+        0x2 => ProxyTransport::Datagram,
+        _ => return Err(ProxyError::Invalid),
+    };
+
+    let address_len = match family >> 4 {
+        0x0 => 0,
+        0x1 => 12,
+        0x2 => 36,
+        0x3 => 216,
+        _ => return Err(ProxyError::Invalid),
+    };
+    let (addresses, tlvs) = payload.split_at_checked(address_len).ok_or(ProxyError::Invalid)?;
+    if !TlvIter(tlvs).is_valid() { return Err(ProxyError::Invalid); }
+    let port = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]);
+    let addresses = match (command, family >> 4) {
+        // the addresses of a local connection are ignored
+        (ProxyCommand::Local, _) | (_, 0x0) => ProxyAddresses::Unknown,
+        (_, 0x1) => {
+            let ip = |bytes: &[u8]| IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]));
+            ProxyAddresses::Inet {
+                source: SocketAddr::new(ip(&addresses[..4]), port(&addresses[8..])),
+                destination: SocketAddr::new(ip(&addresses[4..]), port(&addresses[10..])),
+            }
+        }
+        (_, 0x2) => {
+            let ip = |bytes: &[u8]| IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(&bytes[..16]).unwrap_or_default()));
+            ProxyAddresses::Inet {
+                source: SocketAddr::new(ip(&addresses[..16]), port(&addresses[32..])),
+                destination: SocketAddr::new(ip(&addresses[16..]), port(&addresses[34..])),
+            }
+        }
+        _ => {
+            fn path(bytes: &[u8]) -> &[u8] {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                &bytes[..end]
+            }
+            ProxyAddresses::Unix { source: path(&addresses[..108]), destination: path(&addresses[108..]) }
+        }
+    };
+    Ok((ProxyHeader { version: 2, command, transport, addresses, tlvs }, len))
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+    use super::*;
+
+    fn v2(command: u8, family: u8, addresses: &[u8], tlvs: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[command, family]);
+        header.extend_from_slice(&((addresses.len() + tlvs.len()) as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header.extend_from_slice(tlvs);
+        header
+    }
+
+    #[test]
+    fn v1_headers() {
+        let input = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nGET / HTTP/1.1\r\n";
+        let (header, len) = parse_proxy_header(input).unwrap();
+        assert_eq!(&input[len..], b"GET / HTTP/1.1\r\n");
+        assert_eq!((header.version, header.command, header.transport), (1, ProxyCommand::Proxy, ProxyTransport::Stream));
+        assert_eq!(header.source(), "192.168.0.1:56324".parse().ok());
+        assert_eq!(header.destination(), "192.168.0.11:443".parse().ok());
+        assert_eq!(header.tlvs().next(), None);
+
+        // the longest possible header
+        let input = b"PROXY TCP6 ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff 65535 65535\r\n";
+        let (header, len) = parse_proxy_header(input).unwrap();
+        assert_eq!(len, input.len());
+        assert_eq!(header.source(), "[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff]:65535".parse().ok());
+
+        let (header, len) = parse_proxy_header(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n").unwrap();
+        assert_eq!((header.transport, header.addresses, len), (ProxyTransport::Unspecified, ProxyAddresses::Unknown, 35));
+    }
+
+    #[test]
+    fn invalid_v1_headers() {
+        assert_eq!(parse_proxy_header(b"PRO"), Err(ProxyError::Incomplete(6)));
+        assert_eq!(parse_proxy_header(b"PROXY TCP4 1.2"), Err(ProxyError::Incomplete(15)));
+        assert_eq!(parse_proxy_header(b"GET / HTTP/1.1\r\n"), Err(ProxyError::Missing));
+        assert_eq!(parse_proxy_header(&[b' '; 107]), Err(ProxyError::Missing));
+        let mut long = *b"PROXY UNKNOWN                                                                                              ";
+        assert_eq!(parse_proxy_header(&long), Err(ProxyError::Invalid));
+        long[105..].copy_from_slice(b"\r\n");
+        assert!(parse_proxy_header(&long).is_ok());
+        for input in [
+            &b"PROXY TCP4 1.2.3.4 5.6.7.8 080 80\r\n"[..],
+            b"PROXY TCP4 1.2.3.4 5.6.7.8 65536 80\r\n",
+            b"PROXY TCP4 ::1 ::2 80 80\r\n",
+            b"PROXY TCP6 1.2.3.4 5.6.7.8 80 80\r\n",
+            b"PROXY TCP4 1.2.3.4 5.6.7.8 80\r\n",
+            b"PROXY TCP4 1.2.3.4 5.6.7.8 80 80 80\r\n",
+            b"PROXY TCP4  1.2.3.4 5.6.7.8 80 80\r\n",
+            b"PROXY UDP4 1.2.3.4 5.6.7.8 80 80\r\n",
+        ] {
+            assert_eq!(parse_proxy_header(input), Err(ProxyError::Invalid), "{:?}", core::str::from_utf8(input));
+        }
+    }
+
+    #[test]
+    fn v2_headers() {
+        let ssl = b"\x20\x00\x1b\x03\0\0\0\0\x21\x00\x07TLSv1.3\x22\x00\x06client\x99\x00\x00";
+        let tlvs = [&b"\x01\x00\x02h2\x02\x00\x0bexample.com\x04\x00\x01\0\xe0\x00\x01x"[..], ssl].concat();
+        let mut input = v2(0x21, 0x11, b"\xc0\xa8\x00\x01\xc0\xa8\x00\x0b\xdc\x04\x01\xbb", &tlvs);
+        let header_len = input.len();
+        input.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let (header, len) = parse_proxy_header(&input).unwrap();
+        assert_eq!(len, header_len);
+        assert_eq!((header.version, header.command, header.transport), (2, ProxyCommand::Proxy, ProxyTransport::Stream));
+        assert_eq!(header.source(), "192.168.0.1:56324".parse().ok());
+        assert_eq!(header.destination(), "192.168.0.11:443".parse().ok());
+        assert_eq!(header.alpn(), Some(&b"h2"[..]));
+        assert_eq!(header.authority(), Some("example.com"));
+        let ssl = header.ssl().unwrap();
+        assert!(ssl.is_tls() && ssl.is_verified());
+        assert_eq!((ssl.version, ssl.common_name, ssl.cipher), (Some("TLSv1.3"), Some("client"), None));
+        assert_eq!(header.tlvs().nth(2), Some(Tlv::Noop));
+        assert_eq!(header.tlvs().nth(3), Some(Tlv::Unknown(0xe0, b"x")));
+        assert_eq!(header.tlvs().count(), 5);
+
+        let addresses = [[1; 16], [2; 16]].concat();
+        let input = v2(0x21, 0x22, &[&addresses[..], b"\x00\x35\x00\x35"].concat(), b"");
+        let (header, _) = parse_proxy_header(&input).unwrap();
+        assert_eq!(header.transport, ProxyTransport::Datagram);
+        assert_eq!(header.source(), Some(SocketAddr::new(Ipv6Addr::from([1; 16]).into(), 53)));
+        assert_eq!(header.destination(), Some(SocketAddr::new(Ipv6Addr::from([2; 16]).into(), 53)));
+
+        let mut addresses = [0; 216];
+        addresses[..9].copy_from_slice(b"/tmp/src\0");
+        addresses[108..116].copy_from_slice(b"/tmp/dst");
+        let input = v2(0x21, 0x31, &addresses, b"");
+        let (header, _) = parse_proxy_header(&input).unwrap();
+        assert_eq!(header.addresses, ProxyAddresses::Unix { source: b"/tmp/src", destination: b"/tmp/dst" });
+
+        // a health check, its addresses are ignored
+        let input = v2(0x20, 0x11, &[1; 12], b"");
+        let (header, len) = parse_proxy_header(&input).unwrap();
+        assert_eq!((header.command, header.addresses, len), (ProxyCommand::Local, ProxyAddresses::Unknown, 28));
+        let input = v2(0x20, 0x00, b"", b"");
+        let (header, _) = parse_proxy_header(&input).unwrap();
+        assert_eq!((header.transport, header.source()), (ProxyTransport::Unspecified, None));
+    }
+
+    #[test]
+    fn invalid_v2_headers() {
+        let input = v2(0x21, 0x11, &[1; 12], b"\x01\x00\x02h2");
+        assert_eq!(parse_proxy_header(&input[..8]), Err(ProxyError::Incomplete(12)));
+        assert_eq!(parse_proxy_header(&input[..14]), Err(ProxyError::Incomplete(16)));
+        assert_eq!(parse_proxy_header(&input[..input.len() - 1]), Err(ProxyError::Incomplete(input.len())));
+        for input in [
+            v2(0x11, 0x11, &[1; 12], b""),
+            v2(0x22, 0x11, &[1; 12], b""),
+            v2(0x21, 0x13, &[1; 12], b""),
+            v2(0x21, 0x41, &[1; 12], b""),
+            v2(0x21, 0x21, &[1; 12], b""),
+            v2(0x21, 0x11, &[1; 12], b"\x01\x00\x03h2"),
+            v2(0x21, 0x11, &[1; 12], b"\x01\x00"),
+        ] {
+            assert_eq!(parse_proxy_header(&input), Err(ProxyError::Invalid), "{input:?}");
+        }
+        // an ssl value with a truncated sub-value is kept as unknown
+        let input = v2(0x21, 0x11, &[1; 12], b"\x20\x00\x08\x01\0\0\0\0\x21\x00\x01");
+        let (header, _) = parse_proxy_header(&input).unwrap();
+        assert_eq!(header.ssl(), None);
+        assert_eq!(header.tlvs().next(), Some(Tlv::Unknown(0x20, b"\x01\0\0\0\0\x21\x00\x01")));
+    }
+}
@@ -0,0 +1,248 @@
+use core::fmt::{ self, Display, Write };
+
+use crate::syntax;
+
+/// one `range-spec` of a `Range: bytes=...` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeSpec {
+    /// `first-last`
+    FromTo(u64, u64),
+    /// `first-`, everything from the first byte on
+    From(u64),
+    /// `-length`, the last `length` bytes
+    Suffix(u64),
+}
+
+#[derive(Debug)]
+pub enum RangeError {
+    /// the header isn't a valid `bytes` range set
+    InvalidRange,
+    /// the range unit isn't `bytes`
+    UnsupportedUnit,
+    /// none of the ranges overlap the representation, respond with `416 Range Not Satisfiable`
+    Unsatisfiable,
+    /// more ranges were requested than allowed
+    TooManyRanges,
+}
+
+impl RangeSpec {
+    fn parse(input: &str) -> Result<Self, RangeError> {
+        let (first, last) = input.split_once('-').ok_or(RangeError::InvalidRange)?;
+        let number = |input: &str| -> Result<u64, RangeError> {
+            if input.is_empty() || !input.bytes().all(|b| b.is_ascii_digit()) { return Err(RangeError::InvalidRange); }
+            input.parse().map_err(|_| RangeError::InvalidRange)
+        };
+        match (first, last) {
+            ("", last) => Ok(Self::Suffix(number(last)?)),
+            (first, "") => Ok(Self::From(number(first)?)),
+            (first, last) => {
+                let (first, last) = (number(first)?, number(last)?);
+                if first > last { return Err(RangeError::InvalidRange); }
+                Ok(Self::FromTo(first, last))
+            }
+        }
+    }
+
+    /// the bytes selected from a representation of `length` bytes, `None` if the spec is unsatisfiable
+    pub fn resolve(&self, length: u64) -> Option<ByteRange> {
+        match *self {
+            Self::FromTo(first, _) | Self::From(first) if first >= length => None,
+            Self::FromTo(first, last) => Some(ByteRange { start: first, end: last.min(length - 1) }),
+            Self::From(first) => Some(ByteRange { start: first, end: length - 1 }),
+            Self::Suffix(0) => None,
+            Self::Suffix(_) if length == 0 => None,
+            Self::Suffix(suffix) => Some(ByteRange { start: length.saturating_sub(suffix), end: length - 1 }),
+        }
+    }
+}
+
+/// the specs of a `Range` header, which have all been validated
+pub struct RangeSpecs<'r>(syntax::Split<'r>);
+
+impl Iterator for RangeSpecs<'_> {
+    type Item = RangeSpec;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().and_then(|spec| RangeSpec::parse(spec).ok())
+    }
+}
+
+/// parse a `Range` header value such as `bytes=0-499, 1000-, -500`
+pub fn parse_range(input: &str) -> Result<RangeSpecs<'_>, RangeError> {
+    let (unit, set) = syntax::trim_ows(input).split_once('=').ok_or(RangeError::InvalidRange)?;
+    if !unit.eq_ignore_ascii_case("bytes") { return Err(RangeError::UnsupportedUnit); }
+    let mut specs = syntax::list(set).peekable();
+    if specs.peek().is_none() { return Err(RangeError::InvalidRange); }
+    for spec in specs {
+        RangeSpec::parse(spec)?;
+    }
+    Ok(RangeSpecs(syntax::list(set)))
+}
+
+/// an inclusive range of byte positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    /// `end` is inclusive, like in `Content-Range`, `None` when `start` is after `end` or `end` is `u64::MAX`, as
+    /// the length of a representation can't be larger than that
+    pub fn new(start: u64, end: u64) -> Option<Self> {
+        (start <= end && end < u64::MAX).then_some(Self { start, end })
+    }
+
+    pub fn start(&self) -> u64 { self.start }
+    /// the last byte position, inclusive
+    pub fn end(&self) -> u64 { self.end }
+    pub fn len(&self) -> u64 { self.end - self.start + 1 }
+    pub fn is_empty(&self) -> bool { false }
+}
+
+/// resolve a `Range` header against a representation of `length` bytes
+///
+/// unsatisfiable specs are dropped and the rest are sorted, with overlapping or adjacent ranges coalesced. at most
+/// `out.len()` specs are accepted, guarding against requests for many tiny or overlapping ranges
+pub fn resolve_ranges<'o>(input: &str, length: u64, out: &'o mut [ByteRange]) -> Result<&'o [ByteRange], RangeError> {
+    let mut count = 0;
+    for range in parse_range(input)?.filter_map(|spec| spec.resolve(length)) {
+        *out.get_mut(count).ok_or(RangeError::TooManyRanges)? = range;
+        count += 1;
+    }
+    if count == 0 { return Err(RangeError::Unsatisfiable); }
+
+    let ranges = &mut out[..count];
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged = 0;
+    for idx in 1..ranges.len() {
+        let range = ranges[idx];
+        if range.start <= ranges[merged].end.saturating_add(1) {
+            ranges[merged].end = ranges[merged].end.max(range.end);
+        } else {
+            merged += 1;
+            ranges[merged] = range;
+        }
+    }
+    Ok(&out[..merged + 1])
+}
+
+/// a `Content-Range` header value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentRange {
+    /// `bytes first-last/complete-length`, the complete length may be unknown (`*`)
+    Satisfied(ByteRange, Option<u64>),
+    /// `bytes */complete-length`, sent with `416 Range Not Satisfiable`
+    Unsatisfied(u64),
+}
+
+impl Display for ContentRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Satisfied(range, Some(length)) => write!(f, "bytes {}-{}/{length}", range.start, range.end),
+            Self::Satisfied(range, None) => write!(f, "bytes {}-{}/*", range.start, range.end),
+            Self::Unsatisfied(length) => write!(f, "bytes */{length}"),
+        }
+    }
+}
+
+/// writes the framing of a `multipart/byteranges` body, the range contents are written by the caller between parts
+///
+/// a body is made up of [`part_header`](Self::part_header) followed by the bytes of that range for every range,
+/// then the [`closing`](Self::closing) delimiter
+#[derive(Debug, Clone, Copy)]
+pub struct ByteRangesWriter<'w> {
+    boundary: &'w str,
+    content_type: &'w str,
+    complete_length: u64,
+}
+
+impl<'w> ByteRangesWriter<'w> {
+    /// `content_type` is the media type of the selected representation
+    pub fn new(boundary: &'w str, content_type: &'w str, complete_length: u64) -> Self {
+        Self { boundary, content_type, complete_length }
+    }
+
+    /// the `Content-Type` of the response, `multipart/byteranges; boundary=...`
+    pub fn content_type(&self) -> ByteRangesContentType<'w> { ByteRangesContentType(self.boundary) }
+
+    /// the delimiter and headers preceding the bytes of `range`
+    pub fn part_header(&self, range: ByteRange) -> ByteRangesPartHeader<'w> { ByteRangesPartHeader { writer: *self, range } }
+
+    /// the final delimiter, after the last part
+    pub fn closing(&self) -> ByteRangesClosing<'w> { ByteRangesClosing(self.boundary) }
+
+    /// the `Content-Length` of the whole multipart body
+    pub fn content_length(&self, ranges: &[ByteRange]) -> u64 {
+        let parts: u64 = ranges.iter().map(|range| displayed_len(self.part_header(*range)) + range.len()).sum();
+        parts + displayed_len(self.closing())
+    }
+}
+
+pub struct ByteRangesContentType<'w>(&'w str);
+
+impl Display for ByteRangesContentType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "multipart/byteranges; boundary={}", self.0)
+    }
+}
+
+pub struct ByteRangesPartHeader<'w> {
+    writer: ByteRangesWriter<'w>,
+    range: ByteRange,
+}
+
+impl Display for ByteRangesPartHeader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ByteRangesWriter { boundary, content_type, complete_length } = self.writer;
+        write!(f, "\r\n--{boundary}\r\nContent-Type: {content_type}\r\n")?;
+        write!(f, "Content-Range: {}\r\n\r\n", ContentRange::Satisfied(self.range, Some(complete_length)))
+    }
+}
+
+pub struct ByteRangesClosing<'w>(&'w str);
+
+impl Display for ByteRangesClosing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\r\n--{}--\r\n", self.0)
+    }
+}
+
+fn displayed_len(value: impl Display) -> u64 {
+    struct Counter(u64);
+    impl Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len() as u64;
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    // counting never fails
+    let _ = write!(counter, "{value}");
+    counter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_range_bounds() {
+        assert_eq!(ByteRange::new(10, 9), None);
+        let range = ByteRange::new(9, 9).unwrap();
+        assert_eq!((range.start(), range.end(), range.len()), (9, 9, 1));
+        assert_eq!(ByteRange::new(0, u64::MAX - 1).map(|range| range.len()), Some(u64::MAX));
+        assert_eq!(ByteRange::new(0, u64::MAX), None);
+        assert_eq!(ByteRange::new(u64::MAX, u64::MAX), None);
+        let range = RangeSpec::From(0).resolve(u64::MAX).unwrap();
+        assert_eq!((range.end(), range.len()), (u64::MAX - 1, u64::MAX));
+    }
+
+    #[test]
+    fn ranges_are_resolved_and_coalesced() {
+        let mut out = [ByteRange::default(); 5];
+        let ranges = resolve_ranges("bytes=9000-, 500-999, 0-0, -1, 1-1", 10_000, &mut out).unwrap();
+        let expected = [ByteRange::new(0, 1), ByteRange::new(500, 999), ByteRange::new(9000, 9999)];
+        assert_eq!(ranges, expected.map(Option::unwrap));
+        assert!(matches!(resolve_ranges("bytes=0-0, 1-1", 10, &mut out[..1]), Err(RangeError::TooManyRanges)));
+    }
+}
@@ -0,0 +1,231 @@
+use core::fmt::{ self, Display, Write };
+
+#[cfg(feature = "alloc")]
+mod decoder;
+#[cfg(feature = "alloc")]
+pub use decoder::{ DecodedEvent, EventStreamDecoder };
+
+/// a line of a `text/event-stream`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventStreamLine<'l> {
+    /// `name: value`, a line without a colon is a field with an empty value
+    Field { name: &'l str, value: &'l str },
+    /// `:comment`, often sent to keep the connection alive
+    Comment(&'l str),
+    /// an empty line, which dispatches the event
+    Blank,
+    /// a line that isn't utf-8, as its raw bytes, to be decoded with replacement characters
+    Invalid(&'l [u8]),
+}
+
+/// splits a `text/event-stream` into lines as its bytes arrive
+///
+/// lines can end in CR, LF or CRLF, even with the CR and LF in different chunks, a leading byte order mark is
+/// skipped and lines that aren't utf-8 are returned as `Invalid`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventStreamParser {
+    started: bool,
+    skip_lf: bool,
+}
+
+impl EventStreamParser {
+    pub fn new() -> Self { Self::default() }
+
+    /// the next whole line at the start of `input` and the number of bytes it took up, `None` if more input is
+    /// needed
+    pub fn next_line<'i>(&mut self, input: &'i [u8]) -> Option<(EventStreamLine<'i>, usize)> {
+        let mut consumed = 0;
+        // a CR ending the last line may be followed by a LF, which isn't known until more input arrives
+        if self.skip_lf {
+            match input.first() {
+                None => return None,
+                Some(b'\n') => consumed = 1,
+                Some(_) => {}
+            }
+        }
+
+        // state is only updated once a line is returned, so input that is fed again is parsed the same way
+        let rest = &input[consumed..];
+        let end = rest.iter().position(|&b| b == b'\r' || b == b'\n')?;
+        let mut skip_lf = false;
+        consumed += end + 1;
+        if rest[end] == b'\r' {
+            match rest.get(end + 1) {
+                Some(b'\n') => consumed += 1,
+                Some(_) => {}
+                None => skip_lf = true,
+            }
+        }
+
+        let mut line = &rest[..end];
+        if !self.started {
+            line = line.strip_prefix(b"\xef\xbb\xbf").unwrap_or(line);
+        }
+        self.started = true;
+        self.skip_lf = skip_lf;
+        match core::str::from_utf8(line) {
+            Ok(line) => Some((parse_line(line), consumed)),
+            Err(_) => Some((EventStreamLine::Invalid(line), consumed)),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> EventStreamLine<'_> {
+    if line.is_empty() { return EventStreamLine::Blank; }
+    if let Some(comment) = line.strip_prefix(':') { return EventStreamLine::Comment(comment); }
+    match line.split_once(':') {
+        Some((name, value)) => EventStreamLine::Field { name, value: value.strip_prefix(' ').unwrap_or(value) },
+        None => EventStreamLine::Field { name: line, value: "" },
+    }
+}
+
+/// an event to send in a `text/event-stream`
+///
+/// multi-line data is split into a `data` field per line, line breaks in the event type and id are dropped as
+/// they can't be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SseEvent<'e> {
+    event: Option<&'e str>,
+    data: &'e str,
+    id: Option<&'e str>,
+    retry: Option<u64>,
+}
+
+impl<'e> SseEvent<'e> {
+    pub fn new(data: &'e str) -> Self {
+        Self { event: None, data, id: None, retry: None }
+    }
+
+    pub fn event(&self) -> Option<&'e str> { self.event }
+    pub fn data(&self) -> &'e str { self.data }
+    pub fn id(&self) -> Option<&'e str> { self.id }
+    pub fn retry(&self) -> Option<u64> { self.retry }
+
+    /// the event type, `message` when not set
+    pub fn set_event(&mut self, event: &'e str) { self.event = Some(event); }
+    pub fn set_id(&mut self, id: &'e str) { self.id = Some(id); }
+    /// the reconnection time in milliseconds
+    pub fn set_retry(&mut self, retry: u64) { self.retry = Some(retry); }
+}
+
+impl Display for SseEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(event) = self.event { writeln!(f, "event: {}", SingleLine(event))?; }
+        if let Some(id) = self.id { writeln!(f, "id: {}", SingleLine(id))?; }
+        if let Some(retry) = self.retry { writeln!(f, "retry: {retry}")?; }
+        for line in lines(self.data) {
+            writeln!(f, "data: {line}")?;
+        }
+        f.write_char('\n')
+    }
+}
+
+/// a comment to send in a `text/event-stream`, e.g. to keep the connection alive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SseComment<'c>(&'c str);
+
+impl<'c> SseComment<'c> {
+    pub fn new(comment: &'c str) -> Self { Self(comment) }
+}
+
+impl Display for SseComment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in lines(self.0) {
+            writeln!(f, ":{line}")?;
+        }
+        Ok(())
+    }
+}
+
+// split on CRLF, CR or LF, a trailing line break leaves an empty last line
+fn lines(input: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(input);
+    core::iter::from_fn(move || {
+        let input = rest?;
+        match input.find(['\r', '\n']) {
+            Some(end) => {
+                let next = &input[end + 1..];
+                rest = Some(if input[end..].starts_with("\r\n") { &next[1..] } else { next });
+                Some(&input[..end])
+            }
+            None => rest.take(),
+        }
+    })
+}
+
+// displays a string without its line breaks and nul characters
+struct SingleLine<'s>(&'s str);
+
+impl Display for SingleLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.chars().filter(|c| !matches!(c, '\r' | '\n' | '\0')).try_for_each(|c| f.write_char(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::{ string::{ String, ToString }, vec::Vec };
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Line {
+        Field(String, String),
+        Comment(String),
+        Blank,
+        Invalid(Vec<u8>),
+    }
+
+    // parses the stream as it arrives in two chunks split at `split`
+    fn parse(input: &[u8], split: usize) -> Vec<Line> {
+        let mut parser = EventStreamParser::new();
+        let mut lines = Vec::new();
+        let mut buf = Vec::new();
+        for chunk in [&input[..split], &input[split..]] {
+            buf.extend_from_slice(chunk);
+            let mut consumed = 0;
+            while let Some((line, len)) = parser.next_line(&buf[consumed..]) {
+                assert!(len > 0);
+                consumed += len;
+                lines.push(match line {
+                    EventStreamLine::Field { name, value } => Line::Field(name.into(), value.into()),
+                    EventStreamLine::Comment(comment) => Line::Comment(comment.into()),
+                    EventStreamLine::Blank => Line::Blank,
+                    EventStreamLine::Invalid(line) => Line::Invalid(line.into()),
+                });
+            }
+            buf.drain(..consumed);
+        }
+        lines
+    }
+
+    #[test]
+    fn split_chunks_give_the_same_lines() {
+        let input = b"\xef\xbb\xbfdata: a\r\n:c\rdata\r\rid: \xffx\n\n\xef\xbb\xbfretry:1\r\nevent:  e \r";
+        let expected = [
+            Line::Field("data".into(), "a".into()),
+            Line::Comment("c".into()),
+            Line::Field("data".into(), "".into()),
+            Line::Blank,
+            Line::Invalid(b"id: \xffx".into()),
+            Line::Blank,
+            // only the byte order mark at the start of the stream is skipped
+            Line::Field("\u{feff}retry".into(), "1".into()),
+            Line::Field("event".into(), " e ".into()),
+        ];
+        for split in 0..=input.len() {
+            assert_eq!(parse(input, split), expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn events_are_written_line_by_line() {
+        let mut event = SseEvent::new("a\r\nb\rc\n");
+        event.set_event("up\r\ndate");
+        event.set_id("1\02");
+        event.set_retry(10);
+        assert_eq!(event.to_string(), "event: update\nid: 12\nretry: 10\ndata: a\ndata: b\ndata: c\ndata: \n\n");
+        assert_eq!(SseEvent::new("").to_string(), "data: \n\n");
+        assert_eq!(SseComment::new("a\nb").to_string(), ":a\n:b\n");
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{ EventStreamLine, EventStreamParser, parse_line };
+
+/// an event dispatched from a `text/event-stream`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedEvent {
+    /// the event type, `message` by default
+    pub event: String,
+    /// the data lines joined by `\n`
+    pub data: String,
+    /// the last event id, which carries over from earlier events
+    pub id: String,
+    /// the reconnection time in milliseconds, if it was set along with this event
+    pub retry: Option<u64>,
+}
+
+/// assembles events from the chunks of a `text/event-stream` body (html living standard section 9.2.6)
+#[derive(Debug, Default)]
+pub struct EventStreamDecoder {
+    parser: EventStreamParser,
+    buf: Vec<u8>,
+    pos: usize,
+    event: String,
+    data: String,
+    retry: Option<u64>,
+    last_event_id: String,
+    reconnection_time: Option<u64>,
+}
+
+impl EventStreamDecoder {
+    pub fn new() -> Self { Self::default() }
+
+    /// add the next chunk of the body
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// the next whole event, `None` until more of the body is pushed
+    ///
+    /// an unfinished event at the end of the stream is never dispatched, lines that aren't utf-8 are decoded with
+    /// `U+FFFD` replacement characters
+    pub fn next_event(&mut self) -> Option<DecodedEvent> {
+        loop {
+            let (mut line, len) = self.parser.next_line(&self.buf[self.pos..])?;
+            let lossy;
+            if let EventStreamLine::Invalid(bytes) = line {
+                lossy = String::from_utf8_lossy(bytes);
+                line = parse_line(&lossy);
+            }
+            self.pos += len;
+            match line {
+                EventStreamLine::Comment(_) | EventStreamLine::Invalid(_) => {}
+                EventStreamLine::Field { name: "event", value } => value.clone_into(&mut self.event),
+                EventStreamLine::Field { name: "data", value } => {
+                    self.data.push_str(value);
+                    self.data.push('\n');
+                }
+                EventStreamLine::Field { name: "id", value } if !value.contains('\0') => {
+                    value.clone_into(&mut self.last_event_id);
+                }
+                EventStreamLine::Field { name: "retry", value } if is_digits(value) => {
+                    self.retry = value.parse().ok();
+                    self.reconnection_time = self.retry.or(self.reconnection_time);
+                }
+                EventStreamLine::Field { .. } => {}
+                EventStreamLine::Blank => {
+                    let retry = self.retry.take();
+                    let event = core::mem::take(&mut self.event);
+                    if self.data.is_empty() { continue; }
+                    let mut data = core::mem::take(&mut self.data);
+                    data.pop();
+                    return Some(DecodedEvent {
+                        event: if event.is_empty() { "message".into() } else { event },
+                        data,
+                        id: self.last_event_id.clone(),
+                        retry,
+                    });
+                }
+            }
+        }
+    }
+
+    /// the last event id, sent as `Last-Event-ID` when reconnecting
+    pub fn last_event_id(&self) -> &str { &self.last_event_id }
+    /// the latest reconnection time set by the stream, in milliseconds
+    pub fn reconnection_time(&self) -> Option<u64> { self.reconnection_time }
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // decodes the stream as it arrives in two chunks split at `split`
+    fn decode(input: &[u8], split: usize) -> (Vec<DecodedEvent>, EventStreamDecoder) {
+        let mut decoder = EventStreamDecoder::new();
+        let mut events = Vec::new();
+        for chunk in [&input[..split], &input[split..]] {
+            decoder.push(chunk);
+            events.extend(core::iter::from_fn(|| decoder.next_event()));
+        }
+        (events, decoder)
+    }
+
+    #[test]
+    fn split_chunks_give_the_same_events() {
+        let input = b"\xef\xbb\xbfretry: 10\r\ndata: a\xffb\r\ndata\rid: 7\n\r\n\
+            event: e\n\xffdata: ignored\ndata: x\r\r\
+            id\r\nretry: 1s\n\n\
+            event: f\ndata: unfinished\n";
+        let message = |event: &str, data: &str, id: &str, retry| {
+            DecodedEvent { event: event.into(), data: data.into(), id: id.into(), retry }
+        };
+        let expected = [message("message", "a\u{fffd}b\n", "7", Some(10)), message("e", "x", "7", None)];
+        for split in 0..=input.len() {
+            let (events, decoder) = decode(input, split);
+            assert_eq!(events, expected, "split at {split}");
+            assert_eq!(decoder.last_event_id(), "");
+            assert_eq!(decoder.reconnection_time(), Some(10));
+        }
+    }
+}
@@ -1,5 +1,5 @@
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StatusCode {
     Continue = 100,
     SwitchingProtocols = 101,
@@ -68,6 +68,145 @@ pub enum StatusCode {
     NetworkAuthenticationRequired = 511,
 }
 
+impl StatusCode {
+    pub fn as_u16(&self) -> u16 { *self as u16 }
+
+    /// `None` for codes without a variant
+    pub fn from_u16(code: u16) -> Option<Self> {
+        match code {
+            100 => Some(Self::Continue),
+            101 => Some(Self::SwitchingProtocols),
+            102 => Some(Self::Processing),
+            103 => Some(Self::EarlyHints),
+            200 => Some(Self::Ok),
+            201 => Some(Self::Created),
+            202 => Some(Self::Accepted),
+            203 => Some(Self::NonAuthoritativeInfo),
+            204 => Some(Self::NoContext),
+            205 => Some(Self::ResetContent),
+            206 => Some(Self::PartialContent),
+            207 => Some(Self::MultiStatus),
+            208 => Some(Self::AlreadyReported),
+            226 => Some(Self::IMUsed),
+            300 => Some(Self::MultipleChoices),
+            301 => Some(Self::MovedPermanently),
+            302 => Some(Self::Found),
+            303 => Some(Self::SeeOther),
+            304 => Some(Self::NotModified),
+            307 => Some(Self::TemporaryRedirect),
+            308 => Some(Self::PermanentRedirect),
+            400 => Some(Self::BadRequest),
+            401 => Some(Self::Unauthorized),
+            402 => Some(Self::PaymentRequired),
+            403 => Some(Self::Forbidden),
+            404 => Some(Self::NotFound),
+            405 => Some(Self::MethodNotAllowed),
+            406 => Some(Self::NotAcceptable),
+            407 => Some(Self::ProxyAuthenticationRequired),
+            408 => Some(Self::RequestTimeout),
+            409 => Some(Self::Conflict),
+            410 => Some(Self::Gone),
+            411 => Some(Self::LengthRequired),
+            412 => Some(Self::PreconditionFailed),
+            413 => Some(Self::PayloadTooLarge),
+            414 => Some(Self::URITooLong),
+            415 => Some(Self::UnsupportedMediaType),
+            416 => Some(Self::RangeNotSatisfiable),
+            417 => Some(Self::ExpectationFailed),
+            418 => Some(Self::ImATeapot),
+            421 => Some(Self::MisdirectedRequest),
+            422 => Some(Self::UnprocessableContent),
+            423 => Some(Self::Locked),
+            424 => Some(Self::FailedDependency),
+            425 => Some(Self::TooEarly),
+            426 => Some(Self::UpgradeRequired),
+            428 => Some(Self::PreconditionRequired),
+            429 => Some(Self::TooManyRequests),
+            431 => Some(Self::RequestHeaderFieldsTooLarge),
+            451 => Some(Self::UnavailableForLegalReasons),
+            500 => Some(Self::InternalServerError),
+            501 => Some(Self::NotImplemented),
+            502 => Some(Self::BadGateway),
+            503 => Some(Self::ServiceUnavailable),
+            504 => Some(Self::GatewayTimeout),
+            505 => Some(Self::HTTPVersionNotSupported),
+            506 => Some(Self::VariantAlsoNegotiates),
+            507 => Some(Self::InsufficientStorage),
+            508 => Some(Self::LoopDetected),
+            510 => Some(Self::NotExtended),
+            511 => Some(Self::NetworkAuthenticationRequired),
+            _ => None,
+        }
+    }
+
+    /// the three digit code, e.g. `"404"`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Continue => "100",
+            Self::SwitchingProtocols => "101",
+            Self::Processing => "102",
+            Self::EarlyHints => "103",
+            Self::Ok => "200",
+            Self::Created => "201",
+            Self::Accepted => "202",
+            Self::NonAuthoritativeInfo => "203",
+            Self::NoContext => "204",
+            Self::ResetContent => "205",
+            Self::PartialContent => "206",
+            Self::MultiStatus => "207",
+            Self::AlreadyReported => "208",
+            Self::IMUsed => "226",
+            Self::MultipleChoices => "300",
+            Self::MovedPermanently => "301",
+            Self::Found => "302",
+            Self::SeeOther => "303",
+            Self::NotModified => "304",
+            Self::TemporaryRedirect => "307",
+            Self::PermanentRedirect => "308",
+            Self::BadRequest => "400",
+            Self::Unauthorized => "401",
+            Self::PaymentRequired => "402",
+            Self::Forbidden => "403",
+            Self::NotFound => "404",
+            Self::MethodNotAllowed => "405",
+            Self::NotAcceptable => "406",
+            Self::ProxyAuthenticationRequired => "407",
+            Self::RequestTimeout => "408",
+            Self::Conflict => "409",
+            Self::Gone => "410",
+            Self::LengthRequired => "411",
+            Self::PreconditionFailed => "412",
+            Self::PayloadTooLarge => "413",
+            Self::URITooLong => "414",
+            Self::UnsupportedMediaType => "415",
+            Self::RangeNotSatisfiable => "416",
+            Self::ExpectationFailed => "417",
+            Self::ImATeapot => "418",
+            Self::MisdirectedRequest => "421",
+            Self::UnprocessableContent => "422",
+            Self::Locked => "423",
+            Self::FailedDependency => "424",
+            Self::TooEarly => "425",
+            Self::UpgradeRequired => "426",
+            Self::PreconditionRequired => "428",
+            Self::TooManyRequests => "429",
+            Self::RequestHeaderFieldsTooLarge => "431",
+            Self::UnavailableForLegalReasons => "451",
+            Self::InternalServerError => "500",
+            Self::NotImplemented => "501",
+            Self::BadGateway => "502",
+            Self::ServiceUnavailable => "503",
+            Self::GatewayTimeout => "504",
+            Self::HTTPVersionNotSupported => "505",
+            Self::VariantAlsoNegotiates => "506",
+            Self::InsufficientStorage => "507",
+            Self::LoopDetected => "508",
+            Self::NotExtended => "510",
+            Self::NetworkAuthenticationRequired => "511",
+        }
+    }
+}
+
 impl Default for StatusCode {
     fn default() -> Self { Self::Ok }
 }
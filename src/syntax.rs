@@ -0,0 +1,161 @@
+/// `tchar` from rfc 9110 section 5.6.2
+pub(crate) fn is_tchar(byte: u8) -> bool {
+    matches!(byte,
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' |
+        b'^' | b'_' | b'`' | b'|' | b'~' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z')
+}
+
+pub(crate) fn is_token(input: &str) -> bool {
+    !input.is_empty() && input.bytes().all(is_tchar)
+}
+
+/// trim optional whitespace (spaces and horizontal tabs) from both ends
+pub(crate) fn trim_ows(input: &str) -> &str {
+    input.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+/// strip the surrounding quotes off of a quoted string, leaving any escapes intact
+pub(crate) fn unquote(input: &str) -> &str {
+    if input.len() >= 2 && input.starts_with('"') && input.ends_with('"') {
+        &input[1..input.len() - 1]
+    } else {
+        input
+    }
+}
+
+/// check that a quoted string is well formed (balanced quotes, no dangling escapes)
+pub(crate) fn is_quoted_string(input: &str) -> bool {
+    let bytes = input.as_bytes();
+    if bytes.len() < 2 || bytes[0] != b'"' || bytes[bytes.len() - 1] != b'"' { return false; }
+    let mut escaped = false;
+    for &byte in &bytes[1..bytes.len() - 1] {
+        match (escaped, byte) {
+            (true, _) => escaped = false,
+            (false, b'\\') => escaped = true,
+            (false, b'"') => return false,
+            _ => {}
+        }
+    }
+    !escaped
+}
+
+/// check that `input` can be written between quotes as it is: quotes and backslashes are escaped, and there are
+/// no control characters other than tabs
+pub(crate) fn is_escaped(input: &str) -> bool {
+    let mut escaped = false;
+    for byte in input.bytes() {
+        match (escaped, byte) {
+            (_, b'\t') => escaped = false,
+            (_, 0..=0x1f | 0x7f) => return false,
+            (true, _) => escaped = false,
+            (false, b'\\') => escaped = true,
+            (false, b'"') => return false,
+            _ => {}
+        }
+    }
+    !escaped
+}
+
+/// split `input` on `delim`, ignoring delimiters inside of quoted strings
+///
+/// elements are trimmed of optional whitespace, empty elements are skipped
+#[derive(Clone)]
+pub(crate) struct Split<'s> {
+    rest: &'s str,
+    delim: u8,
+}
+
+impl<'s> Split<'s> {
+    pub(crate) fn new(input: &'s str, delim: u8) -> Self {
+        Self { rest: input, delim }
+    }
+}
+
+impl<'s> Iterator for Split<'s> {
+    type Item = &'s str;
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let bytes = self.rest.as_bytes();
+            let mut quoted = false;
+            let mut escaped = false;
+            let mut end = bytes.len();
+            for (idx, &byte) in bytes.iter().enumerate() {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' if quoted => escaped = true,
+                    b'"' => quoted = !quoted,
+                    b if b == self.delim && !quoted => { end = idx; break; }
+                    _ => {}
+                }
+            }
+            let element = trim_ows(&self.rest[..end]);
+            self.rest = if end < bytes.len() { &self.rest[end + 1..] } else { "" };
+            if !element.is_empty() { return Some(element); }
+        }
+        None
+    }
+}
+
+/// comma separated list elements (the `#rule` from rfc 9110 section 5.6.1)
+pub(crate) fn list(input: &str) -> Split<'_> {
+    Split::new(input, b',')
+}
+
+/// split a `name=value` parameter, trimming whitespace around the `=` and unquoting the value
+pub(crate) fn param(input: &str) -> Option<(&str, &str)> {
+    let (name, value) = input.split_once('=')?;
+    Some((trim_ows(name), unquote(trim_ows(value))))
+}
+
+/// the elements of a comma separated list, such as the field names of `no-cache="..."`
+pub struct List<'l>(Split<'l>);
+
+impl<'l> List<'l> {
+    pub(crate) fn new(input: &'l str) -> Self {
+        Self(list(input))
+    }
+}
+
+impl<'l> Iterator for List<'l> {
+    type Item = &'l str;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// semicolon separated `name=value` parameters
+pub struct Params<'p>(Split<'p>);
+
+impl<'p> Params<'p> {
+    pub(crate) fn new(input: &'p str) -> Self {
+        Self(Split::new(input, b';'))
+    }
+}
+
+impl<'p> Iterator for Params<'p> {
+    /// the name and value of the parameter, quoted values are unquoted but their escapes are kept
+    type Item = (&'p str, &'p str);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.by_ref().find_map(param)
+    }
+}
+
+/// find a parameter by its (case-insensitive) name
+pub(crate) fn find_param<'p>(params: &'p str, name: &str) -> Option<&'p str> {
+    Params::new(params).find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+}
+
+/// displays a string as a quoted string, escaping any quotes and backslashes
+pub(crate) struct Quoted<'q>(pub(crate) &'q str);
+
+impl core::fmt::Display for Quoted<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use core::fmt::Write;
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            if c == '"' || c == '\\' { f.write_char('\\')?; }
+            f.write_char(c)?;
+        }
+        f.write_char('"')
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{ Header, Method, Request, Response, StatusCode, Version };
+use crate::base64;
+use crate::hash::Sha1;
+use crate::header::{ self, StandardHeaderName, has_token, list_values };
+use crate::syntax;
+
+mod frame;
+pub use frame::{ Opcode, FrameError, Role, FrameHeader, Frame, CloseCode, CloseFrame, apply_mask };
+#[cfg(feature = "alloc")]
+mod message;
+#[cfg(feature = "alloc")]
+pub use message::{ Message, MessageAssembler };
+
+/// the only websocket protocol version, sent in `Sec-WebSocket-Version`
+pub const WEBSOCKET_VERSION: &str = "13";
+
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+#[derive(Debug)]
+pub enum HandshakeError {
+    /// the upgrade request isn't a `GET`
+    InvalidMethod,
+    /// the upgrade request isn't at least HTTP/1.1
+    InvalidVersion,
+    MissingHost,
+    /// `Upgrade` doesn't include `websocket`
+    MissingUpgrade,
+    /// `Connection` doesn't include `Upgrade`
+    MissingConnectionUpgrade,
+    /// `Sec-WebSocket-Key` is missing or isn't 16 base64 encoded bytes
+    InvalidKey,
+    /// `Sec-WebSocket-Version` isn't 13
+    UnsupportedVersion,
+    /// the server didn't respond with `101 Switching Protocols`
+    InvalidStatus,
+    /// `Sec-WebSocket-Accept` doesn't match the key
+    InvalidAccept,
+    /// the server selected a subprotocol that wasn't requested
+    InvalidProtocol,
+    /// there is no room left in the header buffer
+    HeaderBufferFull,
+}
+
+impl HandshakeError {
+    /// the status code for a server to reject the request with, an unsupported version is answered with
+    /// `426 Upgrade Required` and a `Sec-WebSocket-Version` of [`WEBSOCKET_VERSION`]
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedVersion => StatusCode::UpgradeRequired,
+            _ => StatusCode::BadRequest,
+        }
+    }
+}
+
+/// a `Sec-WebSocket-Key`, the base64 of 16 random bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WebSocketKey([u8; 24]);
+
+impl WebSocketKey {
+    /// the nonce has to be freshly chosen at random for every connection
+    pub fn new(nonce: [u8; 16]) -> Self {
+        let mut key = [0; 24];
+        // 16 bytes always encode to 24 characters
+        let _ = base64::encode_to_slice(&nonce, &mut key);
+        Self(key)
+    }
+
+    pub fn as_str(&self) -> &str { core::str::from_utf8(&self.0).unwrap_or_default() }
+
+    /// the `Sec-WebSocket-Accept` a server answers this key with
+    pub fn accept(&self) -> AcceptKey { AcceptKey::new(self.as_str()) }
+}
+
+/// a `Sec-WebSocket-Accept`, the base64 of the sha-1 of the key and a fixed guid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptKey([u8; 28]);
+
+impl AcceptKey {
+    pub fn new(key: &str) -> Self {
+        let mut sha1 = Sha1::new();
+        sha1.update(syntax::trim_ows(key).as_bytes());
+        sha1.update(ACCEPT_GUID);
+        let mut accept = [0; 28];
+        // 20 bytes always encode to 28 characters
+        let _ = base64::encode_to_slice(&sha1.finish(), &mut accept);
+        Self(accept)
+    }
+
+    pub fn as_str(&self) -> &str { core::str::from_utf8(&self.0).unwrap_or_default() }
+}
+
+/// a validated websocket upgrade request (rfc 6455 section 4.2.1)
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketUpgrade<'a, 'r> {
+    key: &'r str,
+    headers: &'a [Header<'r>],
+}
+
+impl<'a, 'r> WebSocketUpgrade<'a, 'r> {
+    pub fn from_request<B>(request: &'a Request<'r, '_, B>) -> Result<Self, HandshakeError> {
+        if request.method != Method::Get { return Err(HandshakeError::InvalidMethod); }
+        if request.version == Version::V10 { return Err(HandshakeError::InvalidVersion); }
+        if request.header(StandardHeaderName::Host).is_none() { return Err(HandshakeError::MissingHost); }
+        if !has_token(request.headers(), StandardHeaderName::Upgrade, "websocket") {
+            return Err(HandshakeError::MissingUpgrade);
+        }
+        if !has_token(request.headers(), StandardHeaderName::Connection, "upgrade") {
+            return Err(HandshakeError::MissingConnectionUpgrade);
+        }
+        let key = request.header(StandardHeaderName::SecWebsocketKey)
+            .map(|header| syntax::trim_ows(header.value()))
+            .filter(|key| base64::decoded_len(key) == Some(16) && base64::decode(key, &mut [0; 16]).is_some())
+            .ok_or(HandshakeError::InvalidKey)?;
+        let version = request.header(StandardHeaderName::SecWebsocketVersion).map(|header| syntax::trim_ows(header.value()));
+        if version != Some(WEBSOCKET_VERSION) { return Err(HandshakeError::UnsupportedVersion); }
+
+        Ok(Self { key, headers: request.headers() })
+    }
+
+    pub fn key(&self) -> &'r str { self.key }
+    pub fn accept_key(&self) -> AcceptKey { AcceptKey::new(self.key) }
+
+    /// the subprotocols requested by the client, in order of preference
+    pub fn protocols(&self) -> impl Iterator<Item = &'r str> + 'a {
+        list_values(self.headers, StandardHeaderName::SecWebsocketProtocol)
+    }
+
+    /// the extensions offered by the client, each with its `;` parameters
+    pub fn extensions(&self) -> impl Iterator<Item = &'r str> + 'a {
+        list_values(self.headers, StandardHeaderName::SecWebsocketExtensions)
+    }
+
+    /// turn `response` into the `101 Switching Protocols` accepting the upgrade, `protocol` has to be one of the
+    /// requested [`protocols`](Self::protocols)
+    ///
+    /// the headers are added to the unused (empty) slots of the response's header buffer, the response is left
+    /// unchanged if they don't fit
+    pub fn accept<'s, B>(
+        &self,
+        response: &mut Response<'s, '_, B>,
+        accept_key: &'s AcceptKey,
+        protocol: Option<&'s str>,
+    ) -> Result<(), HandshakeError> {
+        if header::unused_slots(response.headers) < 3 + protocol.is_some() as usize {
+            return Err(HandshakeError::HeaderBufferFull);
+        }
+        push_header(response.headers, StandardHeaderName::Upgrade, "websocket")?;
+        push_header(response.headers, StandardHeaderName::Connection, "Upgrade")?;
+        push_header(response.headers, StandardHeaderName::SecWebsocketAccept, accept_key.as_str())?;
+        if let Some(protocol) = protocol {
+            push_header(response.headers, StandardHeaderName::SecWebsocketProtocol, protocol)?;
+        }
+        response.status_code = StatusCode::SwitchingProtocols;
+        Ok(())
+    }
+}
+
+/// turn `request` into a websocket upgrade request, the `Host` header is left to the caller
+///
+/// the headers are added to the unused (empty) slots of the request's header buffer, the request is left unchanged
+/// if they don't fit
+pub fn upgrade_request<'r, B>(
+    request: &mut Request<'r, '_, B>,
+    key: &'r WebSocketKey,
+    protocols: Option<&'r str>,
+) -> Result<(), HandshakeError> {
+    if header::unused_slots(request.headers) < 4 + protocols.is_some() as usize {
+        return Err(HandshakeError::HeaderBufferFull);
+    }
+    push_header(request.headers, StandardHeaderName::Upgrade, "websocket")?;
+    push_header(request.headers, StandardHeaderName::Connection, "Upgrade")?;
+    push_header(request.headers, StandardHeaderName::SecWebsocketKey, key.as_str())?;
+    push_header(request.headers, StandardHeaderName::SecWebsocketVersion, WEBSOCKET_VERSION)?;
+    if let Some(protocols) = protocols {
+        push_header(request.headers, StandardHeaderName::SecWebsocketProtocol, protocols)?;
+    }
+    request.method = Method::Get;
+    request.version = Version::V11;
+    Ok(())
+}
+
+/// check the server's response to an upgrade request sent with `key` and the requested `protocols`, returns the
+/// selected subprotocol
+pub fn verify_response<'r, B>(
+    response: &Response<'r, '_, B>,
+    key: &WebSocketKey,
+    protocols: Option<&str>,
+) -> Result<Option<&'r str>, HandshakeError> {
+    if response.status_code != StatusCode::SwitchingProtocols { return Err(HandshakeError::InvalidStatus); }
+    if !has_token(response.headers(), StandardHeaderName::Upgrade, "websocket") {
+        return Err(HandshakeError::MissingUpgrade);
+    }
+    if !has_token(response.headers(), StandardHeaderName::Connection, "upgrade") {
+        return Err(HandshakeError::MissingConnectionUpgrade);
+    }
+    let accept = response.header(StandardHeaderName::SecWebsocketAccept).map(|header| syntax::trim_ows(header.value()));
+    if accept != Some(key.accept().as_str()) { return Err(HandshakeError::InvalidAccept); }
+
+    let Some(protocol) = response.header(StandardHeaderName::SecWebsocketProtocol) else { return Ok(None) };
+    let protocol = syntax::trim_ows(protocol.value());
+    match protocols.is_some_and(|protocols| syntax::list(protocols).any(|requested| requested == protocol)) {
+        true => Ok(Some(protocol)),
+        false => Err(HandshakeError::InvalidProtocol),
+    }
+}
+
+fn push_header<'r>(headers: &mut [Header<'r>], name: StandardHeaderName, value: &'r str) -> Result<(), HandshakeError> {
+    header::push_header(headers, name, value).then_some(()).ok_or(HandshakeError::HeaderBufferFull)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_request;
+    use crate::header::EMPTY_HEADER;
+
+    const REQUEST: &str = "GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Protocol: chat, superchat\r\n\
+        Sec-WebSocket-Version: 13\r\n\r\n";
+
+    #[test]
+    fn accept_sets_the_status_after_the_headers() {
+        let mut request_headers = [EMPTY_HEADER; 8];
+        let request = parse_request(REQUEST, &mut request_headers).unwrap();
+        let upgrade = WebSocketUpgrade::from_request(&request).unwrap();
+        let accept_key = upgrade.accept_key();
+        assert_eq!(accept_key.as_str(), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        // the protocol doesn't fit, so nothing is changed
+        let mut headers = [EMPTY_HEADER; 3];
+        let mut response = Response::new((), &mut headers);
+        let status_code = response.status_code;
+        assert!(matches!(upgrade.accept(&mut response, &accept_key, Some("chat")), Err(HandshakeError::HeaderBufferFull)));
+        assert_eq!(response.status_code, status_code);
+        assert!(response.headers().iter().all(|header| header.name().as_str().is_empty()));
+
+        upgrade.accept(&mut response, &accept_key, None).unwrap();
+        assert_eq!(response.status_code, StatusCode::SwitchingProtocols);
+        assert_eq!(response.header(StandardHeaderName::SecWebsocketAccept).map(Header::value), Some(accept_key.as_str()));
+    }
+
+    #[test]
+    fn upgrade_request_is_unchanged_without_room() {
+        let key = WebSocketKey::new(*b"the sample nonce");
+        assert_eq!(key.as_str(), "dGhlIHNhbXBsZSBub25jZQ==");
+        let mut headers = [EMPTY_HEADER; 5];
+        headers[0] = Header::new(crate::HeaderName::Standard(StandardHeaderName::Host), "server.example.com");
+        let mut request = Request::new((), &mut headers);
+        request.method(Method::Post);
+        request.version(Version::V10);
+        assert!(matches!(upgrade_request(&mut request, &key, Some("chat")), Err(HandshakeError::HeaderBufferFull)));
+        assert_eq!((request.method, request.version), (Method::Post, Version::V10));
+        assert!(request.headers()[1..].iter().all(|header| header.name().as_str().is_empty()));
+
+        upgrade_request(&mut request, &key, None).unwrap();
+        assert_eq!((request.method, request.version), (Method::Get, Version::V11));
+        let upgrade = WebSocketUpgrade::from_request(&request).unwrap();
+        assert_eq!(upgrade.accept_key(), key.accept());
+    }
+}
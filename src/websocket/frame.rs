@@ -0,0 +1,346 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// a later fragment of a fragmented message
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl Opcode {
+    /// `None` for the reserved opcodes
+    pub fn from_u8(opcode: u8) -> Option<Self> {
+        match opcode {
+            0x0 => Some(Self::Continuation),
+            0x1 => Some(Self::Text),
+            0x2 => Some(Self::Binary),
+            0x8 => Some(Self::Close),
+            0x9 => Some(Self::Ping),
+            0xa => Some(Self::Pong),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            Self::Continuation => 0x0,
+            Self::Text => 0x1,
+            Self::Binary => 0x2,
+            Self::Close => 0x8,
+            Self::Ping => 0x9,
+            Self::Pong => 0xa,
+        }
+    }
+
+    /// control frames can't be fragmented and carry at most 125 bytes
+    pub fn is_control(&self) -> bool { matches!(self, Self::Close | Self::Ping | Self::Pong) }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// more input is needed, at least this many bytes in total
+    Incomplete(usize),
+    /// a reserved bit is set without an extension defining it
+    ReservedBits,
+    ReservedOpcode,
+    /// a control frame is fragmented or longer than 125 bytes
+    InvalidControlFrame,
+    /// a frame from a client isn't masked, or a frame from a server is
+    InvalidMasking,
+    /// the payload is longer than can be handled
+    PayloadTooLarge,
+    /// a close frame with a 1 byte payload, an invalid close code or a reason that isn't utf-8
+    InvalidCloseFrame,
+    /// text isn't valid utf-8
+    InvalidUtf8,
+    /// a continuation frame without a message to continue
+    UnexpectedContinuation,
+    /// a new data frame while a fragmented message is unfinished
+    ExpectedContinuation,
+    /// the output buffer is too small
+    BufferTooSmall,
+}
+
+/// which end of the connection is parsing, servers only accept masked frames and clients only unmasked ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// the header of a frame (rfc 6455 section 5.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    fin: bool,
+    rsv: u8,
+    opcode: Opcode,
+    mask: Option<[u8; 4]>,
+    payload_len: u64,
+}
+
+impl FrameHeader {
+    /// an unmasked, final frame header
+    pub fn new(opcode: Opcode, payload_len: u64) -> Self {
+        Self { fin: true, rsv: 0, opcode, mask: None, payload_len }
+    }
+
+    /// parse a header from the start of `input`, returning it and its length
+    ///
+    /// reserved bits are returned as is, it is up to any negotiated extension to check them
+    pub fn parse(input: &[u8]) -> Result<(Self, usize), FrameError> {
+        let [first, second, ..] = *input else { return Err(FrameError::Incomplete(2)) };
+        let opcode = Opcode::from_u8(first & 0x0f).ok_or(FrameError::ReservedOpcode)?;
+        let masked = second & 0x80 != 0;
+        let (payload_len, mut len) = match second & 0x7f {
+            126 => (u16::from_be_bytes(array(input, 2)?) as u64, 4),
+            127 => {
+                let payload_len = u64::from_be_bytes(array(input, 2)?);
+                if payload_len >> 63 != 0 { return Err(FrameError::PayloadTooLarge); }
+                (payload_len, 10)
+            }
+            payload_len => (payload_len as u64, 2),
+        };
+        let mask = match masked {
+            true => Some(array(input, len)?),
+            false => None,
+        };
+        if masked { len += 4; }
+
+        let header = Self { fin: first & 0x80 != 0, rsv: first >> 4 & 0x7, opcode, mask, payload_len };
+        if opcode.is_control() && (!header.fin || payload_len > 125) { return Err(FrameError::InvalidControlFrame); }
+        Ok((header, len))
+    }
+
+    /// whether this is the last frame of a message
+    pub fn fin(&self) -> bool { self.fin }
+    /// the three reserved bits, as `RSV1 RSV2 RSV3` from most to least significant
+    pub fn rsv(&self) -> u8 { self.rsv }
+    pub fn opcode(&self) -> Opcode { self.opcode }
+    pub fn mask(&self) -> Option<[u8; 4]> { self.mask }
+    pub fn payload_len(&self) -> u64 { self.payload_len }
+
+    pub fn set_fin(&mut self, fin: bool) { self.fin = fin; }
+    /// only the low three bits are used
+    pub fn set_rsv(&mut self, rsv: u8) { self.rsv = rsv & 0x7; }
+    /// clients mask every frame with a fresh random key
+    pub fn set_mask(&mut self, mask: Option<[u8; 4]>) { self.mask = mask; }
+
+    /// the length of the encoded header, 2 to 14 bytes
+    pub fn encoded_len(&self) -> usize {
+        let len = match self.payload_len {
+            0..=125 => 2,
+            126..=0xffff => 4,
+            _ => 10,
+        };
+        if self.mask.is_some() { len + 4 } else { len }
+    }
+
+    /// write the header to the start of `out`, returning its length
+    ///
+    /// fragmented control frames and ones longer than 125 bytes are rejected
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, FrameError> {
+        if self.opcode.is_control() && (!self.fin || self.payload_len > 125) { return Err(FrameError::InvalidControlFrame); }
+        let len = self.encoded_len();
+        let out = out.get_mut(..len).ok_or(FrameError::BufferTooSmall)?;
+        out[0] = (self.fin as u8) << 7 | self.rsv << 4 | self.opcode.as_u8();
+        let masked = (self.mask.is_some() as u8) << 7;
+        let mask_start = match self.payload_len {
+            0..=125 => {
+                out[1] = masked | self.payload_len as u8;
+                2
+            }
+            126..=0xffff => {
+                out[1] = masked | 126;
+                out[2..4].copy_from_slice(&(self.payload_len as u16).to_be_bytes());
+                4
+            }
+            _ => {
+                out[1] = masked | 127;
+                out[2..10].copy_from_slice(&self.payload_len.to_be_bytes());
+                10
+            }
+        };
+        if let Some(mask) = self.mask { out[mask_start..].copy_from_slice(&mask); }
+        Ok(len)
+    }
+}
+
+/// xor `payload` with the masking key, `offset` is the position of `payload` within the whole frame payload
+///
+/// masking and unmasking are the same operation
+pub fn apply_mask(payload: &mut [u8], mask: [u8; 4], offset: usize) {
+    for (idx, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[(offset + idx) % 4];
+    }
+}
+
+/// a frame and its unmasked payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'f> {
+    header: FrameHeader,
+    payload: &'f [u8],
+}
+
+impl<'f> Frame<'f> {
+    /// an unmasked, final frame
+    pub fn new(opcode: Opcode, payload: &'f [u8]) -> Self {
+        Self { header: FrameHeader::new(opcode, payload.len() as u64), payload }
+    }
+
+    /// parse a whole frame from the start of `input`, unmasking its payload in place, returns the frame and the
+    /// number of bytes it took up
+    ///
+    /// frames with reserved bits set are rejected, as no extensions are supported
+    pub fn parse(input: &'f mut [u8], role: Role) -> Result<(Self, usize), FrameError> {
+        let (header, header_len) = FrameHeader::parse(input)?;
+        if header.rsv != 0 { return Err(FrameError::ReservedBits); }
+        if header.mask.is_some() != (role == Role::Server) { return Err(FrameError::InvalidMasking); }
+        let payload_len = usize::try_from(header.payload_len).map_err(|_| FrameError::PayloadTooLarge)?;
+        let len = header_len.checked_add(payload_len).ok_or(FrameError::PayloadTooLarge)?;
+        let payload = input.get_mut(header_len..len).ok_or(FrameError::Incomplete(len))?;
+        if let Some(mask) = header.mask { apply_mask(payload, mask, 0); }
+        Ok((Self { header, payload }, len))
+    }
+
+    pub fn header(&self) -> &FrameHeader { &self.header }
+    pub fn fin(&self) -> bool { self.header.fin }
+    pub fn opcode(&self) -> Opcode { self.header.opcode }
+    pub fn payload(&self) -> &'f [u8] { self.payload }
+
+    /// the payload of an unfragmented text frame
+    pub fn text(&self) -> Result<&'f str, FrameError> {
+        core::str::from_utf8(self.payload).map_err(|_| FrameError::InvalidUtf8)
+    }
+
+    /// the payload of a close frame
+    pub fn close(&self) -> Result<CloseFrame<'f>, FrameError> {
+        CloseFrame::parse(self.payload)
+    }
+
+    pub fn set_fin(&mut self, fin: bool) { self.header.fin = fin; }
+    /// the payload is masked as it is written
+    pub fn set_mask(&mut self, mask: Option<[u8; 4]>) { self.header.mask = mask; }
+
+    pub fn encoded_len(&self) -> usize { self.header.encoded_len() + self.payload.len() }
+
+    /// write the frame to the start of `out`, masking the payload if a mask is set, returns the frame's length
+    ///
+    /// fragmented control frames and ones longer than 125 bytes are rejected
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, FrameError> {
+        let header_len = self.header.write(out)?;
+        if out.len() < header_len + self.payload.len() { return Err(FrameError::BufferTooSmall); }
+        let payload = &mut out[header_len..header_len + self.payload.len()];
+        payload.copy_from_slice(self.payload);
+        if let Some(mask) = self.header.mask { apply_mask(payload, mask, 0); }
+        Ok(header_len + self.payload.len())
+    }
+}
+
+/// a close status code (rfc 6455 section 7.4)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseCode(pub u16);
+
+impl CloseCode {
+    pub const NORMAL: Self = Self(1000);
+    pub const GOING_AWAY: Self = Self(1001);
+    pub const PROTOCOL_ERROR: Self = Self(1002);
+    pub const UNSUPPORTED_DATA: Self = Self(1003);
+    /// reported when a close frame has no code, never sent
+    pub const NO_STATUS: Self = Self(1005);
+    /// reported when the connection closed without a close frame, never sent
+    pub const ABNORMAL: Self = Self(1006);
+    pub const INVALID_PAYLOAD: Self = Self(1007);
+    pub const POLICY_VIOLATION: Self = Self(1008);
+    pub const MESSAGE_TOO_BIG: Self = Self(1009);
+    pub const MANDATORY_EXTENSION: Self = Self(1010);
+    pub const INTERNAL_ERROR: Self = Self(1011);
+
+    /// whether the code may be sent in a close frame
+    pub fn is_valid(&self) -> bool {
+        matches!(self.0, 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
+
+/// the payload of a close frame, an optional code and a reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseFrame<'f> {
+    code: Option<CloseCode>,
+    reason: &'f str,
+}
+
+impl<'f> CloseFrame<'f> {
+    /// the reason can be at most 123 bytes
+    pub fn new(code: CloseCode, reason: &'f str) -> Self {
+        Self { code: Some(code), reason }
+    }
+
+    pub fn parse(payload: &'f [u8]) -> Result<Self, FrameError> {
+        match payload {
+            [] => Ok(Self { code: None, reason: "" }),
+            [_] => Err(FrameError::InvalidCloseFrame),
+            [high, low, reason @ ..] => {
+                let code = CloseCode(u16::from_be_bytes([*high, *low]));
+                if !code.is_valid() { return Err(FrameError::InvalidCloseFrame); }
+                let reason = core::str::from_utf8(reason).map_err(|_| FrameError::InvalidCloseFrame)?;
+                Ok(Self { code: Some(code), reason })
+            }
+        }
+    }
+
+    /// `None` when the close frame had no payload, reported as [`CloseCode::NO_STATUS`]
+    pub fn code(&self) -> Option<CloseCode> { self.code }
+    pub fn reason(&self) -> &'f str { self.reason }
+
+    /// write the payload of the close frame into `out`
+    pub fn payload<'o>(&self, out: &'o mut [u8; 125]) -> Result<&'o [u8], FrameError> {
+        let Some(code) = self.code else { return Ok(&[]) };
+        let len = 2 + self.reason.len();
+        if len > out.len() { return Err(FrameError::InvalidControlFrame); }
+        out[..2].copy_from_slice(&code.0.to_be_bytes());
+        out[2..len].copy_from_slice(self.reason.as_bytes());
+        Ok(&out[..len])
+    }
+}
+
+fn array<const N: usize>(input: &[u8], start: usize) -> Result<[u8; N], FrameError> {
+    input.get(start..start + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(FrameError::Incomplete(start + N))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut out = [0; 16];
+        // a masked "Hello" from rfc 6455 section 5.7
+        let mut frame = Frame::new(Opcode::Text, b"Hello");
+        frame.set_mask(Some([0x37, 0xfa, 0x21, 0x3d]));
+        let len = frame.write(&mut out).unwrap();
+        assert_eq!(&out[..len], b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58");
+        let (parsed, parsed_len) = Frame::parse(&mut out[..len], Role::Server).unwrap();
+        assert_eq!((parsed.text(), parsed_len), (Ok("Hello"), len));
+        assert_eq!(Frame::parse(&mut out[..len - 1], Role::Server).err(), Some(FrameError::Incomplete(len)));
+    }
+
+    #[test]
+    fn invalid_control_frames_are_not_written() {
+        let mut out = [0; 256];
+        let payload = [0; 126];
+        assert_eq!(Frame::new(Opcode::Ping, &payload).write(&mut out), Err(FrameError::InvalidControlFrame));
+        assert_eq!(Frame::new(Opcode::Ping, &payload[..125]).write(&mut out), Ok(127));
+
+        let mut frame = Frame::new(Opcode::Close, &[0x03, 0xe8]);
+        frame.set_fin(false);
+        assert_eq!(frame.write(&mut out), Err(FrameError::InvalidControlFrame));
+        // data frames can be fragmented and long
+        let mut frame = Frame::new(Opcode::Binary, &payload);
+        frame.set_fin(false);
+        assert_eq!(frame.write(&mut out), Ok(130));
+        assert_eq!(frame.write(&mut out[..129]), Err(FrameError::BufferTooSmall));
+    }
+}
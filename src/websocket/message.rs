@@ -0,0 +1,102 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::frame::{ Frame, FrameError, Opcode };
+
+/// a whole data message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// reassembles fragmented messages from their data frames
+///
+/// control frames can arrive between fragments, they aren't part of the message and are left to the caller
+#[derive(Debug)]
+pub struct MessageAssembler {
+    opcode: Option<Opcode>,
+    payload: Vec<u8>,
+    max_len: usize,
+}
+
+impl MessageAssembler {
+    /// messages longer than `max_len` bytes are rejected
+    pub fn new(max_len: usize) -> Self {
+        Self { opcode: None, payload: Vec::new(), max_len }
+    }
+
+    /// whether a fragmented message has been started but not finished
+    pub fn is_partial(&self) -> bool { self.opcode.is_some() }
+
+    /// add a frame, returning the message once its final fragment arrives
+    ///
+    /// an error drops the partial message, the next message starts from scratch
+    pub fn push(&mut self, frame: &Frame<'_>) -> Result<Option<Message>, FrameError> {
+        let message = self.assemble(frame);
+        if message.is_err() {
+            self.opcode = None;
+            self.payload.clear();
+        }
+        message
+    }
+
+    fn assemble(&mut self, frame: &Frame<'_>) -> Result<Option<Message>, FrameError> {
+        let opcode = match (frame.opcode(), self.opcode) {
+            (opcode, _) if opcode.is_control() => return Ok(None),
+            (Opcode::Continuation, Some(opcode)) => opcode,
+            (Opcode::Continuation, None) => return Err(FrameError::UnexpectedContinuation),
+            (_, Some(_)) => return Err(FrameError::ExpectedContinuation),
+            (opcode, None) => opcode,
+        };
+        if self.payload.len() + frame.payload().len() > self.max_len { return Err(FrameError::PayloadTooLarge); }
+
+        self.payload.extend_from_slice(frame.payload());
+        if !frame.fin() {
+            self.opcode = Some(opcode);
+            return Ok(None);
+        }
+        self.opcode = None;
+        let payload = core::mem::take(&mut self.payload);
+        match opcode {
+            Opcode::Text => String::from_utf8(payload).map(|text| Some(Message::Text(text))).map_err(|_| FrameError::InvalidUtf8),
+            _ => Ok(Some(Message::Binary(payload))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(opcode: Opcode, payload: &[u8], fin: bool) -> Frame<'_> {
+        let mut frame = Frame::new(opcode, payload);
+        frame.set_fin(fin);
+        frame
+    }
+
+    #[test]
+    fn fragments_are_reassembled_around_control_frames() {
+        let mut assembler = MessageAssembler::new(16);
+        assert_eq!(assembler.push(&fragment(Opcode::Text, b"Hel", false)), Ok(None));
+        assert_eq!(assembler.push(&Frame::new(Opcode::Ping, b"")), Ok(None));
+        assert!(assembler.is_partial());
+        assert_eq!(assembler.push(&fragment(Opcode::Continuation, b"lo", true)), Ok(Some(Message::Text("Hello".into()))));
+        assert!(!assembler.is_partial());
+    }
+
+    #[test]
+    fn errors_drop_the_partial_message() {
+        let mut assembler = MessageAssembler::new(8);
+        assembler.push(&fragment(Opcode::Binary, b"12345", false)).unwrap();
+        assert_eq!(assembler.push(&fragment(Opcode::Continuation, b"6789", true)), Err(FrameError::PayloadTooLarge));
+        assert!(!assembler.is_partial());
+        assert_eq!(assembler.push(&Frame::new(Opcode::Binary, b"abc")), Ok(Some(Message::Binary(b"abc".into()))));
+
+        assembler.push(&fragment(Opcode::Text, b"\xe2\x82", false)).unwrap();
+        assert_eq!(assembler.push(&fragment(Opcode::Continuation, b"", true)), Err(FrameError::InvalidUtf8));
+        assembler.push(&fragment(Opcode::Text, b"a", false)).unwrap();
+        assert_eq!(assembler.push(&Frame::new(Opcode::Text, b"b")), Err(FrameError::ExpectedContinuation));
+        assert_eq!(assembler.push(&Frame::new(Opcode::Text, b"b")), Ok(Some(Message::Text("b".into()))));
+    }
+}